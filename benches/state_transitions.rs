@@ -0,0 +1,104 @@
+/*
+    Cost of one state transition as the symbol table grows.
+
+    `persistent` goes through AppState::apply_action like every state-changing command does.
+    `std_hashmap_clone` is the previous design, which copied a std HashMap on every transition,
+    kept here as the baseline. Run with `cargo bench --bench state_transitions`.
+*/
+
+use std::collections::HashMap;
+
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use jrepl::{
+    appstate::AppState,
+    command_interpreter::{
+        interpreter::interpret,
+        number::Number,
+        types::{Expr, JsonObject, Referent},
+    },
+    statics::commands::get_commands,
+};
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+// A document of a few hundred nodes, standing in for a loaded JSON file.
+fn document(seed: usize) -> Expr {
+    let rows = (0..50)
+        .map(|i| {
+            let mut row = JsonObject::new();
+            row.insert("id".to_string(), Expr::Number(Number::from(seed * 50 + i)));
+            row.insert("name".to_string(), Expr::String(format!("row {}", i)));
+            row.insert("active".to_string(), Expr::Bool(i % 2 == 0));
+            Expr::object(row)
+        })
+        .collect();
+    let mut doc = JsonObject::new();
+    doc.insert("rows".to_string(), Expr::array(rows));
+    Expr::object(doc)
+}
+
+fn bindings(n: usize) -> Vec<(String, Referent)> {
+    (0..n)
+        .map(|i| (format!("doc{}", i), Referent::Expr(document(i))))
+        .collect()
+}
+
+fn app_state_with(n: usize) -> AppState {
+    let mut app_state = AppState::new();
+    app_state.set_commands(get_commands());
+    let next = app_state.apply_action(|state| {
+        for (symbol, referent) in bindings(n) {
+            state.symbol_table.insert(symbol, referent);
+        }
+    });
+    app_state.set_next_state(next);
+    app_state
+}
+
+fn transition(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transition");
+    for n in SIZES {
+        let app_state = app_state_with(n);
+        group.bench_with_input(BenchmarkId::new("persistent", n), &n, |b, _| {
+            b.iter(|| {
+                black_box(app_state.apply_action(|state| {
+                    state.symbol_table.insert(
+                        "x".to_string(),
+                        Referent::Expr(Expr::Number(Number::Int(1))),
+                    );
+                }))
+            })
+        });
+
+        let table: HashMap<String, Referent> = bindings(n).into_iter().collect();
+        group.bench_with_input(BenchmarkId::new("std_hashmap_clone", n), &n, |b, _| {
+            b.iter(|| {
+                let mut next = table.clone();
+                next.insert(
+                    "x".to_string(),
+                    Referent::Expr(Expr::Number(Number::Int(1))),
+                );
+                black_box(next)
+            })
+        });
+    }
+    group.finish();
+}
+
+// A full REPL step: interpret a defn and commit the result into the undo history.
+fn defn_with_history(c: &mut Criterion) {
+    let mut group = c.benchmark_group("defn_with_history");
+    for n in SIZES {
+        let mut app_state = app_state_with(n);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            b.iter(|| {
+                let effect = interpret(&app_state, "(defn x 1)");
+                app_state.commit(effect.next_state.unwrap(), "(defn x 1)");
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, transition, defn_with_history);
+criterion_main!(benches);
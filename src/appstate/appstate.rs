@@ -1,33 +1,160 @@
 use crate::{
+    appstate::history::History,
     command_interpreter::{
         command::Command,
-        types::{Expr, Referent},
+        types::{Expr, Lambda, Referent, Scope},
     },
     errors::errors::JreplErr,
 };
-use std::{collections::HashMap, rc::Rc};
+use im_rc::HashMap;
+use std::rc::Rc;
 
 // -------------------------------- AppState -------------------------------- //
 
+#[derive(Clone)]
 pub struct AppState {
-    state: State,
+    state: Rc<State>,
+    // local bindings of the function call being evaluated, None at the top level
+    scope: Option<Rc<Scope>>,
+    // how many user function calls deep the evaluation is
+    call_depth: usize,
+}
+
+impl Default for AppState {
+    fn default() -> AppState {
+        AppState::new()
+    }
 }
 
 impl AppState {
     pub fn new() -> AppState {
-        AppState { state: State::new() }
+        AppState {
+            state: Rc::new(State::new()),
+            scope: None,
+            call_depth: 0,
+        }
     }
 
     pub fn set_next_state(&mut self, state: State) {
-        self.state = state;
+        self.state = Rc::new(state);
+    }
+
+    /// Moves to `state`, recording the current state and the `input` that produced the change so
+    /// it can be undone. States that rewrote the history themselves (undo, redo) are taken as is.
+    pub fn commit(&mut self, mut state: State, input: &str) {
+        if Rc::ptr_eq(&state.history, &self.state.history) {
+            let mut history = History::clone(&state.history);
+            history.record(self.state.snapshot(), input);
+            state.history = Rc::new(history);
+        }
+        self.state = Rc::new(state);
+    }
+
+    /// The state `steps` changes back, with the undone changes available to redo.
+    pub fn undo(&self, steps: usize) -> Result<State, JreplErr> {
+        self.travel(steps, "undo", History::undo)
+    }
+
+    /// The state `steps` undone changes forward.
+    pub fn redo(&self, steps: usize) -> Result<State, JreplErr> {
+        self.travel(steps, "redo", History::redo)
+    }
+
+    fn travel<F>(&self, steps: usize, what: &str, step: F) -> Result<State, JreplErr>
+    where
+        F: Fn(&mut History, Rc<State>) -> Option<Rc<State>>,
+    {
+        let mut history = History::clone(&self.state.history);
+        let mut current = self.state.snapshot();
+        for done in 0..steps {
+            current = step(&mut history, current).ok_or_else(|| {
+                JreplErr::EvalErr(format!(
+                    "Nothing to {}: asked for {} step(s), only {} available.",
+                    what, steps, done
+                ))
+            })?;
+        }
+        let mut state = State::clone(&current);
+        state.history = Rc::new(history);
+        Ok(state)
+    }
+
+    pub fn history(&self) -> &History {
+        &self.state.history
+    }
+
+    /// A view of the same state with `scope` layered over the symbol table. The state is shared,
+    /// not copied.
+    pub fn with_scope(&self, scope: Rc<Scope>) -> AppState {
+        AppState {
+            state: self.state.clone(),
+            scope: Some(scope),
+            call_depth: self.call_depth,
+        }
+    }
+
+    /// Like `with_scope`, for evaluating the body of a user function: one call deeper.
+    pub fn with_call(&self, scope: Rc<Scope>) -> AppState {
+        AppState {
+            call_depth: self.call_depth + 1,
+            ..self.with_scope(scope)
+        }
+    }
+
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    /// The same view with `state` in place of the current one, keeping the local scope.
+    pub fn with_state(&self, state: State) -> AppState {
+        AppState {
+            state: Rc::new(state),
+            scope: self.scope.clone(),
+            call_depth: self.call_depth,
+        }
+    }
+
+    pub fn into_state(self) -> State {
+        Rc::try_unwrap(self.state).unwrap_or_else(|shared| (*shared).clone())
+    }
+
+    pub fn scope(&self) -> Option<Rc<Scope>> {
+        self.scope.clone()
+    }
+
+    /// Whether `symbol` is bound in the local scope, shadowing any global binding.
+    pub fn is_local(&self, symbol: &str) -> bool {
+        self.scope
+            .as_ref()
+            .is_some_and(|scope| scope.lookup(symbol).is_some())
+    }
+
+    /// The user function `symbol` is bound to, locally or globally, if any.
+    pub fn lookup_lambda(&self, symbol: &str) -> Option<Rc<Lambda>> {
+        let local = self.scope.as_ref().and_then(|scope| scope.lookup(symbol));
+        let global = || match self.state.symbol_table.get(symbol) {
+            Some(Referent::Expr(expr)) => Some(expr),
+            _ => None,
+        };
+        match local.or_else(global) {
+            Some(Expr::Lambda(lambda)) => Some(lambda.clone()),
+            _ => None,
+        }
     }
 
     pub fn resolve_symbol_value(&self, symbol: &str) -> Result<Expr, JreplErr> {
-        let referent = self
-            .state
-            .symbol_table
-            .get(symbol)
-            .ok_or_else(|| JreplErr::UndefinedSymbol(format!("Undefined symbol: {}.", symbol)))?;
+        if let Some(value) = self.scope.as_ref().and_then(|scope| scope.lookup(symbol)) {
+            return Ok(value.clone());
+        }
+
+        let referent =
+            self.state
+                .symbol_table
+                .get(symbol)
+                .ok_or_else(|| JreplErr::UndefinedSymbol {
+                    name: Some(symbol.to_string()),
+                    msg: format!("Undefined symbol: {}.", symbol),
+                })?;
 
         match referent {
             Referent::Command(_) => Err(JreplErr::OperatorFormatErr(format!(
@@ -45,34 +172,58 @@ impl AppState {
         let symbol_table = &self.state.symbol_table;
 
         if symbol_table.is_empty() {
-            return Err(JreplErr::UndefinedSymbol("symbol table is empty".to_string()));
+            return Err(JreplErr::UndefinedSymbol {
+                name: None,
+                msg: "symbol table is empty".to_string(),
+            });
         }
 
         let referent = symbol_table
             .get(symbol)
-            .ok_or_else(|| JreplErr::UndefinedSymbol(format!("Undefined symbol: {}. Expected a command.", symbol)))?;
+            .ok_or_else(|| JreplErr::UndefinedSymbol {
+                name: Some(symbol.to_string()),
+                msg: format!("Undefined symbol: {}. Expected a command.", symbol),
+            })?;
 
         match referent {
             Referent::Command(cmd) => Ok(cmd),
-            Referent::Expr(expr) => Err(JreplErr::UndefinedSymbol(format!(
-                "Found symbol '{}' but it resolves to a non-command: {:?}.",
-                symbol, expr
-            ))),
+            Referent::Expr(expr) => Err(JreplErr::UndefinedSymbol {
+                name: Some(symbol.to_string()),
+                msg: format!(
+                    "Found symbol '{}' but it resolves to a non-command: {:?}.",
+                    symbol, expr
+                ),
+            }),
         }
     }
 
+    /// What `symbol` is bound to in the symbol table, ignoring local bindings.
+    pub fn global_referent(&self, symbol: &str) -> Option<&Referent> {
+        self.state.symbol_table.get(symbol)
+    }
+
+    /// The file the value of global `symbol` was last loaded from or saved to, if any. None while
+    /// a local binding shadows it.
+    pub fn provenance(&self, symbol: &str) -> Option<&str> {
+        if self.is_local(symbol) {
+            return None;
+        }
+        self.state.provenance.get(symbol).map(String::as_str)
+    }
+
     pub fn get_commands(&self) -> &[Rc<Command>] {
         &self.state.commands
     }
 
     pub fn set_commands(&mut self, commands: Vec<Command>) {
-        self.state.commands = commands.into_iter().map(Rc::new).collect();
-        self.register_commands_with_symbol_table();
+        let state = Rc::make_mut(&mut self.state);
+        state.commands = commands.into_iter().map(Rc::new).collect::<Vec<_>>().into();
+        Self::register_commands_with_symbol_table(state);
     }
 
-    fn register_commands_with_symbol_table(&mut self) {
-        let symbol_table = &mut self.state.symbol_table;
-        for command in &self.state.commands {
+    fn register_commands_with_symbol_table(state: &mut State) {
+        let symbol_table = &mut state.symbol_table;
+        for command in state.commands.iter() {
             symbol_table.insert(command.symbol.clone(), Referent::Command(command.clone()));
         }
     }
@@ -91,12 +242,24 @@ impl AppState {
     }
 }
 
+/// One immutable snapshot of the session. The symbol table is a persistent hash map and the
+/// command list is shared, so cloning a state or deriving the next one through a `StateBuilder`
+/// costs O(log n) per changed binding and leaves every unchanged binding shared.
 #[derive(Clone)]
 pub struct State {
     editor: Editor,
     exit: bool,
-    commands: Vec<Rc<Command>>,
+    commands: Rc<[Rc<Command>]>,
     symbol_table: HashMap<String, Referent>,
+    // the file each global symbol's value was last loaded from or saved to
+    provenance: HashMap<String, String>,
+    history: Rc<History>,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
 }
 
 impl State {
@@ -104,17 +267,29 @@ impl State {
         State {
             editor: Editor::new(),
             exit: false,
-            commands: Vec::new(),
+            commands: Rc::new([]),
             symbol_table: HashMap::new(),
+            provenance: HashMap::new(),
+            history: Rc::new(History::default()),
         }
     }
 
+    // A copy without the history, for keeping in the history. Cheap: the table is shared.
+    fn snapshot(&self) -> Rc<State> {
+        Rc::new(State {
+            history: Rc::new(History::default()),
+            ..self.clone()
+        })
+    }
+
     pub fn to_builder(&self) -> StateBuilder {
         StateBuilder {
             editor: self.editor.clone(),
             exit: self.exit,
             commands: self.commands.clone(),
             symbol_table: self.symbol_table.clone(),
+            provenance: self.provenance.clone(),
+            history: self.history.clone(),
         }
     }
 
@@ -132,13 +307,19 @@ impl State {
 }
 
 #[derive(Clone, Debug, PartialEq)]
-struct Editor {
+pub struct Editor {
     pub prompt_symbol: String,
     // pub prompt_text: LinkedList<String>,
     pub prompt_text: String,
     pub cursor_pos: usize,
 }
 
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor::new()
+    }
+}
+
 impl Editor {
     pub fn new() -> Editor {
         Editor {
@@ -152,8 +333,10 @@ impl Editor {
 pub struct StateBuilder {
     pub editor: Editor,
     pub exit: bool,
-    pub commands: Vec<Rc<Command>>,
+    pub commands: Rc<[Rc<Command>]>,
     pub symbol_table: HashMap<String, Referent>,
+    pub provenance: HashMap<String, String>,
+    history: Rc<History>,
 }
 
 impl StateBuilder {
@@ -163,6 +346,8 @@ impl StateBuilder {
             exit: self.exit,
             commands: self.commands,
             symbol_table: self.symbol_table,
+            provenance: self.provenance,
+            history: self.history,
         }
     }
 }
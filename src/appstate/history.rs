@@ -0,0 +1,110 @@
+use std::{collections::VecDeque, rc::Rc};
+
+use crate::appstate::State;
+
+/// How many undo steps are kept. Older entries are dropped first.
+pub const HISTORY_LIMIT: usize = 100;
+
+/// A state the session passed through, with the input line that led away from it (for undo) or
+/// back to it (for redo).
+#[derive(Clone)]
+pub struct HistoryEntry {
+    pub state: Rc<State>,
+    pub input: String,
+}
+
+/// Undo and redo stacks. The states held here carry no history of their own, and share their
+/// symbol tables and bound values with the states around them.
+#[derive(Clone, Default)]
+pub struct History {
+    past: VecDeque<HistoryEntry>,
+    future: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Records that `input` moved the session away from `state`. Anything undone is forgotten.
+    pub fn record(&mut self, state: Rc<State>, input: &str) {
+        self.past.push_back(HistoryEntry {
+            state,
+            input: input.to_string(),
+        });
+        if self.past.len() > HISTORY_LIMIT {
+            self.past.pop_front();
+        }
+        self.future.clear();
+    }
+
+    /// Steps back from `current`, returning the earlier state. `None` when there is nothing left.
+    pub fn undo(&mut self, current: Rc<State>) -> Option<Rc<State>> {
+        let entry = self.past.pop_back()?;
+        self.future.push(HistoryEntry {
+            state: current,
+            input: entry.input,
+        });
+        Some(entry.state)
+    }
+
+    /// Steps forward again from `current`. `None` when nothing has been undone.
+    pub fn redo(&mut self, current: Rc<State>) -> Option<Rc<State>> {
+        let entry = self.future.pop()?;
+        self.past.push_back(HistoryEntry {
+            state: current,
+            input: entry.input,
+        });
+        Some(entry.state)
+    }
+
+    /// Inputs that can be undone, oldest first.
+    pub fn past(&self) -> impl Iterator<Item = &str> {
+        self.past.iter().map(|entry| entry.input.as_str())
+    }
+
+    /// Inputs that can be redone, next first.
+    pub fn future(&self) -> impl Iterator<Item = &str> {
+        self.future.iter().rev().map(|entry| entry.input.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state() -> Rc<State> {
+        Rc::new(State::new())
+    }
+
+    #[test]
+    fn history_undo_then_redo_round_trips() {
+        let (a, b) = (state(), state());
+        let mut history = History::default();
+        history.record(a.clone(), "(defn x 1)");
+
+        let back = history.undo(b.clone()).unwrap();
+        assert!(Rc::ptr_eq(&back, &a));
+        assert_eq!(history.future().collect::<Vec<_>>(), vec!["(defn x 1)"]);
+
+        let forward = history.redo(back).unwrap();
+        assert!(Rc::ptr_eq(&forward, &b));
+        assert!(history.redo(forward).is_none());
+    }
+
+    #[test]
+    fn history_record_clears_redo() {
+        let mut history = History::default();
+        history.record(state(), "one");
+        history.undo(state());
+        history.record(state(), "two");
+        assert_eq!(history.past().collect::<Vec<_>>(), vec!["two"]);
+        assert_eq!(history.future().count(), 0);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut history = History::default();
+        for i in 0..HISTORY_LIMIT + 5 {
+            history.record(state(), &i.to_string());
+        }
+        assert_eq!(history.past().count(), HISTORY_LIMIT);
+        assert_eq!(history.past().next(), Some("5"));
+    }
+}
@@ -1,3 +1,5 @@
+#[allow(clippy::module_inception)]
 pub mod appstate;
+pub mod history;
 pub use appstate::AppState;
 pub use appstate::State;
@@ -30,10 +30,12 @@ use crate::{
     errors::errors::JreplErr,
 };
 
+pub type EvalFn = Box<dyn Fn(&AppState, &[Expr]) -> Result<Effect, JreplErr>>;
+
 pub struct Command {
     pub symbol: String,
     pub description: String,
-    pub eval_fn_ptr: Box<dyn Fn(&AppState, &[Expr]) -> Result<Effect, JreplErr>>,
+    pub eval_fn_ptr: EvalFn,
 }
 
 impl Command {
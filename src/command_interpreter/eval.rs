@@ -1,13 +1,38 @@
-use crate::{appstate::AppState, command_interpreter::types::Effect};
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    appstate::AppState,
+    command_interpreter::{
+        number::Number,
+        types::{Effect, JsonObject, Lambda, Scope},
+    },
+};
 use crate::{command_interpreter::types::Expr, errors::errors::JreplErr};
 
 pub fn eval(app_state: &AppState, expr: &Expr) -> Result<Effect, JreplErr> {
     match expr {
-        // terminals pass through
-        Expr::File(_) | Expr::String(_) | Expr::Number(_) | Expr::Bool(_) | Expr::None => {
-            Ok(Effect::from_eval_value(expr.clone()))
+        // [a b] and {"k" v} literals with expressions inside evaluate them, in order
+        Expr::Array(items) if !expr.is_literal() => Ok(Effect::from_eval_value(Expr::array(
+            values_of(app_state, items)?,
+        ))),
+        Expr::Object(entries) if !expr.is_literal() => {
+            let entries = entries
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), value_of(app_state, value)?)))
+                .collect::<Result<JsonObject, JreplErr>>()?;
+            Ok(Effect::from_eval_value(Expr::object(entries)))
         }
 
+        // terminals pass through
+        Expr::File(_)
+        | Expr::String(_)
+        | Expr::Number(_)
+        | Expr::Bool(_)
+        | Expr::None
+        | Expr::Array(_)
+        | Expr::Object(_)
+        | Expr::Lambda(_) => Ok(Effect::from_eval_value(expr.clone())),
+
         // resolve symbol to terminal
         Expr::Symbol(symbol) => {
             let v = app_state.resolve_symbol_value(symbol)?;
@@ -20,14 +45,21 @@ pub fn eval(app_state: &AppState, expr: &Expr) -> Result<Effect, JreplErr> {
                 return Ok(Effect::from_eval_value(Expr::None));
             }
 
-            // single element: zero-arg command OR grouped expression
+            // single element: zero-arg call OR grouped expression
             if expr_list.len() == 1 {
                 if let Expr::Symbol(s) = &expr_list[0] {
+                    if let Some(lambda) = app_state.lookup_lambda(s) {
+                        return call_lambda(app_state, &lambda, Vec::new());
+                    }
                     if let Ok(cmd) = app_state.get_command_from_symbol(s) {
                         return (cmd.eval_fn_ptr)(app_state, &[]);
                     }
                 }
                 let v = value_of(app_state, &expr_list[0])?;
+                // ((fn () 7)) calls the function the inner form produced
+                if let (Expr::List(_), Expr::Lambda(lambda)) = (&expr_list[0], &v) {
+                    return call_lambda(app_state, lambda, Vec::new());
+                }
                 return Ok(Effect::from_eval_value(v));
             }
 
@@ -36,12 +68,26 @@ pub fn eval(app_state: &AppState, expr: &Expr) -> Result<Effect, JreplErr> {
                 return Ok(Effect::from_eval_value(expr.clone()));
             }
 
-            // otherwise must be a command form
+            // otherwise must be a command or function call
             match &expr_list[0] {
                 Expr::Symbol(symbol) => {
+                    if let Some(lambda) = app_state.lookup_lambda(symbol) {
+                        let args = values_of(app_state, &expr_list[1..])?;
+                        return call_lambda(app_state, &lambda, args);
+                    }
                     let command = app_state.get_command_from_symbol(symbol)?;
                     (command.eval_fn_ptr)(app_state, &expr_list[1..])
                 }
+                // e.g. ((fn (x) (* x 2)) 21)
+                head @ Expr::List(_) => match value_of(app_state, head)? {
+                    Expr::Lambda(lambda) => {
+                        let args = values_of(app_state, &expr_list[1..])?;
+                        call_lambda(app_state, &lambda, args)
+                    }
+                    other => Err(JreplErr::OperatorFormatErr(format!(
+                        "Invalid list: the expression at position 0 evaluated to {other:?}, which is not a function."
+                    ))),
+                },
                 head => Err(JreplErr::OperatorFormatErr(format!(
                     "Invalid list: expected a command symbol at position 0, found {head:?}. \
                      Multi-element lists must start with a command (e.g., (+ 1 2)). \
@@ -58,19 +104,109 @@ pub fn value_of(app_state: &AppState, expr: &Expr) -> Result<Expr, JreplErr> {
     }
     match expr {
         Expr::Symbol(s) => app_state.resolve_symbol_value(s),
-        Expr::List(_) => {
+        Expr::List(_) | Expr::Array(_) | Expr::Object(_) => {
             let eff = eval(app_state, expr)?;
-            eff.eval_value
-                .ok_or_else(|| JreplErr::OperatorFormatErr("Subexpression returned no value".to_string()))
+            eff.eval_value.ok_or_else(|| {
+                JreplErr::OperatorFormatErr("Subexpression returned no value".to_string())
+            })
         }
         _ => unreachable!(),
     }
 }
 
-pub fn number_of(app_state: &AppState, expr: &Expr) -> Result<f64, JreplErr> {
+pub fn values_of(app_state: &AppState, exprs: &[Expr]) -> Result<Vec<Expr>, JreplErr> {
+    exprs.iter().map(|e| value_of(app_state, e)).collect()
+}
+
+/// Calls `f` on already evaluated `args` and returns its value. `f` may be a symbol naming a
+/// command or user function, or any expression that evaluates to a function.
+pub fn apply(app_state: &AppState, f: &Expr, args: &[Expr]) -> Result<Expr, JreplErr> {
+    let effect = match f {
+        Expr::Symbol(symbol) if app_state.lookup_lambda(symbol).is_none() => {
+            let command = app_state.get_command_from_symbol(symbol)?;
+            (command.eval_fn_ptr)(app_state, args)?
+        }
+        other => match value_of(app_state, other)? {
+            Expr::Lambda(lambda) => call_lambda(app_state, &lambda, args.to_vec())?,
+            value => {
+                return Err(JreplErr::OperatorFormatErr(format!(
+                    "Expected a function, got {:?}",
+                    value
+                )));
+            }
+        },
+    };
+    effect
+        .eval_value
+        .ok_or_else(|| JreplErr::OperatorFormatErr(format!("Function {:?} returned no value", f)))
+}
+
+/// How deeply user functions may call each other. Each call takes several Rust stack frames, so
+/// unbounded recursion would otherwise overflow the stack and abort the whole session.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// The stack a session should be evaluated on. A debug build needs about 16 KiB per call, which
+/// puts MAX_CALL_DEPTH out of reach of the main thread's 8 MiB; this leaves a wide margin.
+pub const EVAL_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Binds `args` to the parameters in a new frame over the function's captured scope, then
+/// evaluates the body there. The global symbol table is never touched.
+pub fn call_lambda(
+    app_state: &AppState,
+    lambda: &Lambda,
+    args: Vec<Expr>,
+) -> Result<Effect, JreplErr> {
+    if args.len() != lambda.params.len() {
+        return Err(JreplErr::OperatorFormatErr(format!(
+            "{:?} expects {} argument(s), got {}",
+            lambda,
+            lambda.params.len(),
+            args.len()
+        )));
+    }
+
+    if app_state.call_depth() >= MAX_CALL_DEPTH {
+        return Err(JreplErr::EvalErr(format!(
+            "Maximum call depth {} exceeded in '{}'",
+            MAX_CALL_DEPTH,
+            lambda.name.as_deref().unwrap_or("fn")
+        )));
+    }
+
+    let frame = Scope {
+        bindings: lambda
+            .params
+            .iter()
+            .cloned()
+            .zip(args)
+            .collect::<HashMap<_, _>>(),
+        parent: lambda.scope.clone(),
+    };
+    eval_sequence(&app_state.with_call(Rc::new(frame)), &lambda.body)
+}
+
+/// Evaluates `exprs` in order, each one seeing the state the previous one produced. Returns the
+/// last value together with the final state, if any form changed it.
+pub fn eval_sequence(app_state: &AppState, exprs: &[Expr]) -> Result<Effect, JreplErr> {
+    let mut current: Option<AppState> = None;
+    let mut last = Effect::from_eval_value(Expr::None);
+
+    for expr in exprs {
+        let mut effect = eval(current.as_ref().unwrap_or(app_state), expr)?;
+        if let Some(state) = effect.next_state.take() {
+            current = Some(app_state.with_state(state));
+        }
+        last = effect;
+    }
+
+    last.next_state = current.map(AppState::into_state);
+    Ok(last)
+}
+
+pub fn number_of(app_state: &AppState, expr: &Expr) -> Result<Number, JreplErr> {
     match value_of(app_state, expr)? {
         Expr::Number(n) => Ok(n),
-        other => Err(JreplErr::UndefinedSymbol(format!(
+        other => Err(JreplErr::TypeErr(format!(
             "Type error: expected Number, got {:?}",
             other
         ))),
@@ -129,10 +265,15 @@ mod test {
     fn eval_plus_simple() {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
-        let ast = Expr::List(vec![sym("+"), Expr::Number(1.0), Expr::Number(2.0), Expr::Number(3.5)]);
+        let ast = Expr::List(vec![
+            sym("+"),
+            Expr::Number(Number::Int(1)),
+            Expr::Number(Number::Int(2)),
+            Expr::Number(Number::float(3.5)),
+        ]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 6.5),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 6.5),
             other => panic!("{:?}", other),
         }
     }
@@ -144,12 +285,16 @@ mod test {
         // (+ 1 (- 5 2)) => 4
         let ast = Expr::List(vec![
             sym("+"),
-            Expr::Number(1.0),
-            Expr::List(vec![sym("-"), Expr::Number(5.0), Expr::Number(2.0)]),
+            Expr::Number(Number::Int(1)),
+            Expr::List(vec![
+                sym("-"),
+                Expr::Number(Number::Int(5)),
+                Expr::Number(Number::Int(2)),
+            ]),
         ]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 4.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 4.0),
             other => panic!("{:?}", other),
         }
     }
@@ -158,7 +303,11 @@ mod test {
     fn eval_plus_type_error_non_number_arg() {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
-        let ast = Expr::List(vec![sym("+"), Expr::Number(1.0), Expr::String("a".into())]);
+        let ast = Expr::List(vec![
+            sym("+"),
+            Expr::Number(Number::Int(1)),
+            Expr::String("a".into()),
+        ]);
         let out = eval(&app_state, &ast);
         assert!(out.is_err());
     }
@@ -169,10 +318,10 @@ mod test {
     fn eval_minus_unary() {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
-        let ast = Expr::List(vec![sym("-"), Expr::Number(5.0)]);
+        let ast = Expr::List(vec![sym("-"), Expr::Number(Number::Int(5))]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, -5.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), -5.0),
             other => panic!("{:?}", other),
         }
     }
@@ -184,14 +333,14 @@ mod test {
         // (- 10 1 2 3) => 4
         let ast = Expr::List(vec![
             sym("-"),
-            Expr::Number(10.0),
-            Expr::Number(1.0),
-            Expr::Number(2.0),
-            Expr::Number(3.0),
+            Expr::Number(Number::Int(10)),
+            Expr::Number(Number::Int(1)),
+            Expr::Number(Number::Int(2)),
+            Expr::Number(Number::Int(3)),
         ]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 4.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 4.0),
             other => panic!("{:?}", other),
         }
     }
@@ -203,12 +352,16 @@ mod test {
         // (- (+ 10 5) 3) => 12
         let ast = Expr::List(vec![
             sym("-"),
-            Expr::List(vec![sym("+"), Expr::Number(10.0), Expr::Number(5.0)]),
-            Expr::Number(3.0),
+            Expr::List(vec![
+                sym("+"),
+                Expr::Number(Number::Int(10)),
+                Expr::Number(Number::Int(5)),
+            ]),
+            Expr::Number(Number::Int(3)),
         ]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 12.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 12.0),
             other => panic!("{:?}", other),
         }
     }
@@ -217,7 +370,11 @@ mod test {
     fn eval_minus_type_error_non_number_arg() {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
-        let ast = Expr::List(vec![sym("-"), Expr::Number(1.0), Expr::String("x".into())]);
+        let ast = Expr::List(vec![
+            sym("-"),
+            Expr::Number(Number::Int(1)),
+            Expr::String("x".into()),
+        ]);
         let out = eval(&app_state, &ast);
         assert!(out.is_err());
     }
@@ -229,10 +386,15 @@ mod test {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
         // (* 2 3 4) => 24
-        let ast = Expr::List(vec![sym("*"), Expr::Number(2.0), Expr::Number(3.0), Expr::Number(4.0)]);
+        let ast = Expr::List(vec![
+            sym("*"),
+            Expr::Number(Number::Int(2)),
+            Expr::Number(Number::Int(3)),
+            Expr::Number(Number::Int(4)),
+        ]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 24.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 24.0),
             other => panic!("{:?}", other),
         }
     }
@@ -244,12 +406,16 @@ mod test {
         // (* 2 (+ 3 1)) => 2 * 4 = 8
         let ast = Expr::List(vec![
             sym("*"),
-            Expr::Number(2.0),
-            Expr::List(vec![sym("+"), Expr::Number(3.0), Expr::Number(1.0)]),
+            Expr::Number(Number::Int(2)),
+            Expr::List(vec![
+                sym("+"),
+                Expr::Number(Number::Int(3)),
+                Expr::Number(Number::Int(1)),
+            ]),
         ]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 8.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 8.0),
             other => panic!("{:?}", other),
         }
     }
@@ -258,7 +424,11 @@ mod test {
     fn eval_multiply_type_error_non_number_arg() {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
-        let ast = Expr::List(vec![sym("*"), Expr::Number(2.0), Expr::String("x".into())]);
+        let ast = Expr::List(vec![
+            sym("*"),
+            Expr::Number(Number::Int(2)),
+            Expr::String("x".into()),
+        ]);
         let out = eval(&app_state, &ast);
         assert!(out.is_err());
     }
@@ -271,7 +441,7 @@ mod test {
         let ast = Expr::List(vec![sym("*")]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 1.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 1.0),
             other => panic!("{:?}", other),
         }
     }
@@ -283,10 +453,10 @@ mod test {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
         // (/ 2) => 0.5
-        let ast = Expr::List(vec![sym("/"), Expr::Number(2.0)]);
+        let ast = Expr::List(vec![sym("/"), Expr::Number(Number::Int(2))]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 0.5),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 0.5),
             other => panic!("{:?}", other),
         }
     }
@@ -296,10 +466,15 @@ mod test {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
         // (/ 20 2 5) => 20 / 2 / 5 = 2
-        let ast = Expr::List(vec![sym("/"), Expr::Number(20.0), Expr::Number(2.0), Expr::Number(5.0)]);
+        let ast = Expr::List(vec![
+            sym("/"),
+            Expr::Number(Number::Int(20)),
+            Expr::Number(Number::Int(2)),
+            Expr::Number(Number::Int(5)),
+        ]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 2.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 2.0),
             other => panic!("{:?}", other),
         }
     }
@@ -311,12 +486,16 @@ mod test {
         // (/ 12 (- 5 2)) => 12 / 3 = 4
         let ast = Expr::List(vec![
             sym("/"),
-            Expr::Number(12.0),
-            Expr::List(vec![sym("-"), Expr::Number(5.0), Expr::Number(2.0)]),
+            Expr::Number(Number::Int(12)),
+            Expr::List(vec![
+                sym("-"),
+                Expr::Number(Number::Int(5)),
+                Expr::Number(Number::Int(2)),
+            ]),
         ]);
         let out = eval(&app_state, &ast).map(|e| e.eval_value).unwrap();
         match out {
-            Some(Expr::Number(n)) => assert_eq!(n, 4.0),
+            Some(Expr::Number(n)) => assert_eq!(n.to_f64(), 4.0),
             other => panic!("{:?}", other),
         }
     }
@@ -325,7 +504,11 @@ mod test {
     fn eval_divide_by_zero_error() {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
-        let ast = Expr::List(vec![sym("/"), Expr::Number(1.0), Expr::Number(0.0)]);
+        let ast = Expr::List(vec![
+            sym("/"),
+            Expr::Number(Number::Int(1)),
+            Expr::Number(Number::Int(0)),
+        ]);
         let out = eval(&app_state, &ast);
         assert!(out.is_err());
     }
@@ -334,7 +517,11 @@ mod test {
     fn eval_divide_type_error_non_number_arg() {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
-        let ast = Expr::List(vec![sym("/"), Expr::Number(10.0), Expr::String("a".into())]);
+        let ast = Expr::List(vec![
+            sym("/"),
+            Expr::Number(Number::Int(10)),
+            Expr::String("a".into()),
+        ]);
         let out = eval(&app_state, &ast);
         assert!(out.is_err());
     }
@@ -1,42 +1,322 @@
 use crate::appstate::AppState;
 use crate::command_interpreter::eval;
-use crate::command_interpreter::lexer;
-use crate::command_interpreter::parser::parse;
-use crate::command_interpreter::types::Effect;
+use crate::command_interpreter::lexer::lex;
+use crate::command_interpreter::parser::{Ast, parse_forms};
+use crate::command_interpreter::span::Span;
+use crate::command_interpreter::types::{Effect, Expr};
+use crate::errors::errors::JreplErr;
 
+/// Lexes, parses and evaluates every form in `user_input`, in order. Each form sees the state
+/// the previous one left behind. Evaluation stops at the first error, or once a form asks to
+/// exit, and the returned effect holds the last value together with the final state.
 pub fn interpret(app_state: &AppState, user_input: &str) -> Effect {
-    let lexer_result = lexer(user_input);
-    let tokens = match lexer_result {
+    interpret_each(app_state, user_input, |_| {})
+}
+
+/// Like `interpret`, but also hands the effect of each form that evaluated to `on_form` as soon
+/// as it is known, e.g. to print a script's output as it runs. Its next state has been taken.
+pub fn interpret_each<F>(app_state: &AppState, user_input: &str, mut on_form: F) -> Effect
+where
+    F: FnMut(&Effect),
+{
+    let tokens = match lex(user_input) {
         Ok(tokens) => tokens,
-        Err(err) => return Effect::from_err(err),
+        Err(err) => return error_at(err.node, err.span),
     };
 
     // parens are ballanced by now.
-    // parse returns an AST.
-    let expr = parse(&tokens);
-
-    match eval(app_state, &expr) {
-        Ok(effect) => return effect,
-        Err(err) => return Effect::from_err(err),
+    // parse returns an AST per form that remembers where each node came from.
+    let forms = match parse_forms(&tokens) {
+        Ok(forms) => forms,
+        Err(err) => return error_at(err.node, err.span),
     };
+    if forms.is_empty() {
+        return Effect::from_eval_value(Expr::None);
+    }
+
+    let mut current: Option<AppState> = None;
+    let mut last = Effect::from_eval_value(Expr::None);
+    for form in &forms {
+        let state = current.as_ref().unwrap_or(app_state);
+        match eval(state, &form.to_expr()) {
+            Ok(mut effect) => {
+                if let Some(next_state) = effect.next_state.take() {
+                    current = Some(state.with_state(next_state));
+                }
+                on_form(&effect);
+                last = effect;
+            }
+            Err(err) => {
+                let span = blame(form, &err);
+                last = error_at(err, span);
+                break;
+            }
+        }
+        if current.as_ref().is_some_and(AppState::should_exit) {
+            break;
+        }
+    }
+
+    // changes made by the forms before an error are kept, as if they had been entered one by one
+    last.next_state = current.map(AppState::into_state);
+    last
+}
+
+fn error_at(err: JreplErr, span: Span) -> Effect {
+    Effect {
+        err_span: Some(span),
+        ..Effect::from_err(err)
+    }
+}
+
+// Evaluation errors do not carry positions. An undefined symbol can be found in the form,
+// anything else is blamed on the whole form.
+fn blame(ast: &Ast, err: &JreplErr) -> Span {
+    match err {
+        JreplErr::UndefinedSymbol {
+            name: Some(symbol), ..
+        } => ast.find_symbol(symbol).unwrap_or(ast.span),
+        _ => ast.span,
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{command_interpreter::types::Expr, statics::commands::get_commands};
+    use crate::{
+        command_interpreter::{number::Number, types::Expr},
+        errors::errors::JreplErr,
+        statics::commands::get_commands,
+        test_support::{run, run_session, session},
+    };
 
     use super::*;
 
-    fn run(input: &str) -> Effect {
+    // Like run_session, but commits each state the way the REPL loop does, so history is kept.
+    fn run_recorded(inputs: &[&str]) -> AppState {
+        let mut app_state = session();
+        for input in inputs {
+            let effect = interpret(&app_state, input);
+            assert!(effect.err.is_none(), "{}: {:?}", input, effect.err);
+            if let Some(state) = effect.next_state {
+                app_state.commit(state, input);
+            }
+        }
+        app_state
+    }
+
+    fn value(app_state: &AppState, input: &str) -> Result<Expr, JreplErr> {
+        let effect = interpret(app_state, input);
+        match effect.err {
+            Some(err) => Err(err),
+            None => Ok(effect.eval_value.unwrap()),
+        }
+    }
+
+    #[test]
+    fn interpret_write_read_f() {
+        let _effect1 = run("(defn f (ld \"temp.json\"))");
+        let _effect2 = run("(f)");
+    }
+
+    #[test]
+    fn interpret_ld_json_returns_structured_value() {
+        let effect = run("(ld \"dev/nested_object.json\")");
+        assert!(effect.err.is_none());
+        match effect.eval_value {
+            Some(Expr::Object(map)) => assert!(map.contains_key("user")),
+            other => panic!("unexpected output: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn interpret_defn_binds_json_value() {
+        let effect = run("(defn doc (ld \"dev/array_of_objects.json\"))");
+        assert!(effect.err.is_none());
+        assert!(effect.next_state.is_some());
+        assert!(matches!(effect.eval_value, Some(Expr::Object(_))));
+    }
+
+    #[test]
+    fn interpret_get_by_pointer_dotted_and_segments() {
+        let inputs = [
+            "(get (ld \"dev/deeply_nested.json\") \"/config/window/theme/background\")",
+            "(get (ld \"dev/deeply_nested.json\") \"config.window.theme['background']\")",
+            "(get (ld \"dev/deeply_nested.json\") \"config\" \"window\" \"theme\" \"background\")",
+        ];
+        for input in inputs {
+            let effect = run(input);
+            assert!(effect.err.is_none(), "{}: {:?}", input, effect.err);
+            assert_eq!(
+                effect.eval_value,
+                Some(Expr::String("#ffffff".into())),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn interpret_get_index_segment() {
+        let effect = run("(get (ld \"dev/mixed_types.json\") \"tags\" 2)");
+        assert_eq!(effect.eval_value, Some(Expr::String("cli".into())));
+    }
+
+    #[test]
+    fn interpret_get_missing_path_is_err() {
+        let effect = run("(get (ld \"dev/deeply_nested.json\") \"/config/door\")");
+        assert!(matches!(effect.err, Some(JreplErr::MissingKey(_))));
+    }
+
+    #[test]
+    fn interpret_query_returns_list_of_matches() {
+        let effect =
+            run("(query (ld \"dev/array_of_objects.json\") \"$.users[?(@.age > 26)].name\")");
+        assert!(effect.err.is_none(), "{:?}", effect.err);
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::List(vec![Expr::String("Carol".into())]))
+        );
+    }
+
+    #[test]
+    fn interpret_query_paths_pairs_matches_with_paths() {
+        let effect = run("(query-paths (ld \"dev/deeply_nested.json\") \"$..width\")");
+        let pairs = match effect.eval_value {
+            Some(Expr::List(pairs)) => pairs,
+            other => panic!("unexpected output: {:?}", other),
+        };
+        match &pairs[..] {
+            [Expr::Object(pair)] => {
+                assert_eq!(
+                    pair.get("path"),
+                    Some(&Expr::String("$['config']['window']['width']".into()))
+                );
+                assert_eq!(pair.get("value"), Some(&Expr::Number(Number::Int(800))));
+            }
+            other => panic!("unexpected output: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn interpret_query_invalid_path_is_err() {
+        let effect = run("(query (ld \"dev/array_of_objects.json\") \"users\")");
+        assert!(matches!(effect.err, Some(JreplErr::InvalidPath(_))));
+    }
+
+    #[test]
+    fn interpret_assoc_in_leaves_bound_symbol_unchanged() {
         let mut app_state = AppState::new();
         app_state.set_commands(get_commands());
-        interpret(&app_state, input)
+        let effect = interpret(&app_state, "(defn doc (ld \"dev/deeply_nested.json\"))");
+        app_state.set_next_state(effect.next_state.unwrap());
+
+        let effect = interpret(
+            &app_state,
+            "(get (assoc-in doc \"/config/window/width\" 1024) \"/config/window/width\")",
+        );
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(1024))));
+
+        let effect = interpret(&app_state, "(get doc \"/config/window/width\")");
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(800))));
     }
 
     #[test]
-    fn interpret_write_read_f() {
-        let effect1 = run("(defn f (ld \"temp.json\"))");
-        let effect2 = run("(f)");
+    fn interpret_update_in_with_builtin_command() {
+        let effect = run(
+            "(get (update-in (ld \"dev/deeply_nested.json\") \"config.window.height\" + 10) \"config.window.height\")",
+        );
+        assert!(effect.err.is_none(), "{:?}", effect.err);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(610))));
+    }
+
+    #[test]
+    fn interpret_dissoc_in_removes_key() {
+        let effect = run(
+            "(get (dissoc-in (ld \"dev/deeply_nested.json\") \"/config/window/theme\") \"/config/window/theme\")",
+        );
+        assert!(matches!(effect.err, Some(JreplErr::MissingKey(_))));
+    }
+
+    #[test]
+    fn interpret_defn_function_with_params() {
+        let effect = run_session(&["(defn area (w h) (* w h))", "(area 3 4)"]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(12))));
+    }
+
+    #[test]
+    fn interpret_lambda_called_inline() {
+        let effect = run("((fn (x) (* x 2)) 21)");
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(42))));
+        let effect = run("((lambda () 7))");
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(7))));
+    }
+
+    #[test]
+    fn interpret_closure_captures_defining_scope() {
+        let effect = run_session(&[
+            "(defn adder (n) (fn (x) (+ x n)))",
+            "(defn add5 (adder 5))",
+            "(add5 10)",
+        ]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(15))));
+    }
+
+    #[test]
+    fn interpret_params_shadow_globals_without_mutating_them() {
+        let effect = run_session(&["(defn x 100)", "(defn inc (x) (+ x 1))", "(inc 1)"]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(2))));
+
+        let effect = run_session(&["(defn x 100)", "(defn inc (x) (+ x 1))", "(inc 1)", "(x)"]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(100))));
+
+        let effect = run_session(&["(defn id (y) y)", "(id 1)", "(+ y 1)"]);
+        assert!(matches!(effect.err, Some(JreplErr::UndefinedSymbol { .. })));
+    }
+
+    #[test]
+    fn interpret_higher_order_function() {
+        let effect = run_session(&["(defn twice (f x) (f (f x)))", "(twice (fn (y) (* y 2)) 3)"]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(12))));
+    }
+
+    #[test]
+    fn interpret_update_in_with_lambda() {
+        let effect = run(
+            "(get (update-in (ld \"dev/deeply_nested.json\") \"/config/window/width\" (fn (w) (/ w 2))) \"/config/window/width\")",
+        );
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(400))));
+    }
+
+    #[test]
+    fn interpret_function_arity_mismatch_is_err() {
+        let effect = run_session(&["(defn area (w h) (* w h))", "(area 3)"]);
+        assert!(matches!(effect.err, Some(JreplErr::OperatorFormatErr(_))));
+    }
+
+    #[test]
+    fn interpret_unbounded_recursion_is_err() {
+        use crate::command_interpreter::eval::{EVAL_STACK_SIZE, MAX_CALL_DEPTH};
+
+        // on the stack main gives a session, as the test thread's is far smaller
+        let session = std::thread::Builder::new()
+            .stack_size(EVAL_STACK_SIZE)
+            .spawn(|| {
+                let effect = run("(do (defn f (x) (f x)) (f 1))");
+                assert_eq!(
+                    effect.err,
+                    Some(JreplErr::EvalErr(format!(
+                        "Maximum call depth {} exceeded in 'f'",
+                        MAX_CALL_DEPTH
+                    )))
+                );
+                let effect = run(&format!(
+                    "(do (defn down (n) (if (> n 0) (down (- n 1)) \"done\")) (down {}))",
+                    MAX_CALL_DEPTH - 1
+                ));
+                assert_eq!(effect.eval_value, Some(Expr::String("done".to_string())));
+            })
+            .unwrap();
+        session.join().unwrap();
     }
 
     #[test]
@@ -45,7 +325,7 @@ mod test {
 
         assert!(effect.err.is_none());
         assert!(effect.user_feedback.is_none());
-        assert!(!effect.eval_value.is_none());
+        assert!(effect.eval_value.is_some());
     }
 
     #[test]
@@ -56,7 +336,10 @@ mod test {
         assert!(effect.err.is_none());
         assert!(effect.user_feedback.is_none());
         assert!(effect.eval_value.is_none());
-        assert!(state.get_exit(), "next_state.exit should be true after (exit)");
+        assert!(
+            state.get_exit(),
+            "next_state.exit should be true after (exit)"
+        );
     }
 
     #[test]
@@ -87,4 +370,509 @@ mod test {
         assert!(effect.err.is_some());
         assert!(effect.eval_value.is_none());
     }
+
+    #[test]
+    fn interpret_undo_restores_previous_binding() {
+        let app_state = run_recorded(&["(defn x 1)", "(defn x 2)", "(undo)"]);
+        assert_eq!(value(&app_state, "(x)"), Ok(Expr::Number(Number::Int(1))));
+
+        let app_state = run_recorded(&["(defn x 1)", "(defn y 2)", "(undo 2)"]);
+        assert!(matches!(
+            value(&app_state, "(x)"),
+            Err(JreplErr::UndefinedSymbol { .. })
+        ));
+    }
+
+    #[test]
+    fn interpret_redo_reapplies_undone_change() {
+        let app_state = run_recorded(&["(defn x 1)", "(defn x 2)", "(undo)", "(redo)"]);
+        assert_eq!(value(&app_state, "(x)"), Ok(Expr::Number(Number::Int(2))));
+    }
+
+    #[test]
+    fn interpret_new_change_after_undo_discards_redo() {
+        let app_state = run_recorded(&["(defn x 1)", "(undo)", "(defn x 3)"]);
+        assert!(matches!(
+            value(&app_state, "(redo)"),
+            Err(JreplErr::EvalErr(_))
+        ));
+        assert_eq!(value(&app_state, "(x)"), Ok(Expr::Number(Number::Int(3))));
+    }
+
+    #[test]
+    fn interpret_undo_past_start_is_err_and_changes_nothing() {
+        let app_state = run_recorded(&["(defn x 1)"]);
+        let effect = interpret(&app_state, "(undo 2)");
+        assert!(matches!(effect.err, Some(JreplErr::EvalErr(_))));
+        assert!(effect.next_state.is_none());
+    }
+
+    #[test]
+    fn interpret_history_lists_inputs_and_undone_changes() {
+        let app_state = run_recorded(&["(defn x 1)", "(defn y 2)", "(undo)"]);
+        assert_eq!(
+            value(&app_state, "(history)"),
+            Ok(Expr::String(
+                "   1  (defn x 1)\n   *  (current)\n   2  (defn y 2)  [undone]\n".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn interpret_explain_describes_error_code() {
+        let text = match run("(explain E0304)").eval_value {
+            Some(Expr::String(s)) => s,
+            other => panic!("unexpected output: {:?}", other),
+        };
+        assert!(text.starts_with("E0304 (data): missing key\n"));
+        assert_eq!(
+            run("(explain \"e0304\")").eval_value,
+            Some(Expr::String(text))
+        );
+        assert!(matches!(
+            run("(explain E0000)").err,
+            Some(JreplErr::EvalErr(_))
+        ));
+    }
+
+    #[test]
+    fn interpret_type_errors_are_not_undefined_symbols() {
+        for input in ["(+ 1 \"a\")", "(if 1 2)"] {
+            let err = run(input).err.unwrap();
+            assert!(matches!(err, JreplErr::TypeErr(_)), "{}: {:?}", input, err);
+            assert_eq!(err.hint(), None);
+        }
+    }
+
+    #[test]
+    fn interpret_errors_carry_a_span() {
+        let effect = run("(+ 1 (get missing \"a\"))");
+        assert!(matches!(effect.err, Some(JreplErr::UndefinedSymbol { .. })));
+        let span = effect.err_span.unwrap();
+        assert_eq!((span.start.offset, span.end.offset), (10, 17));
+
+        let span = run("(+ 1 \"a\")").err_span.unwrap();
+        assert_eq!((span.start.offset, span.end.offset), (0, 9));
+
+        // the symbol comes from the error itself, not from its message
+        let effect = run("(do (defn x 1) (set! counter 2))");
+        assert_eq!(
+            effect.err.as_ref().map(JreplErr::code),
+            Some("E0202"),
+            "{:?}",
+            effect.err
+        );
+        let span = effect.err_span.unwrap();
+        assert_eq!((span.start.offset, span.end.offset), (21, 28));
+    }
+
+    #[test]
+    fn interpret_runs_every_form_in_order() {
+        let effect = run("(defn a 1) (defn b (+ a 1)) (+ a b)");
+        assert!(effect.err.is_none(), "{:?}", effect.err);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(3))));
+        assert!(effect.next_state.is_some());
+    }
+
+    #[test]
+    fn interpret_each_reports_every_form_as_it_runs() {
+        let mut app_state = AppState::new();
+        app_state.set_commands(get_commands());
+        let mut values = Vec::new();
+        let effect = interpret_each(&app_state, "(defn a 1) (+ a 1) (nope) (+ a 2)", |form| {
+            assert!(form.next_state.is_none());
+            values.push(form.eval_value.clone());
+        });
+        assert!(matches!(effect.err, Some(JreplErr::UndefinedSymbol { .. })));
+        assert_eq!(
+            values,
+            vec![
+                Some(Expr::Number(Number::Int(1))),
+                Some(Expr::Number(Number::Int(2)))
+            ]
+        );
+    }
+
+    #[test]
+    fn interpret_stops_at_first_error_and_keeps_earlier_changes() {
+        let mut app_state = AppState::new();
+        app_state.set_commands(get_commands());
+        let effect = interpret(&app_state, "(defn a 1)\n(nope)\n(defn b 2)");
+        assert!(matches!(effect.err, Some(JreplErr::UndefinedSymbol { .. })));
+        assert_eq!(effect.err_span.unwrap().start.line, 2);
+
+        app_state.set_next_state(effect.next_state.unwrap());
+        assert_eq!(
+            interpret(&app_state, "(a)").eval_value,
+            Some(Expr::Number(Number::Int(1)))
+        );
+        assert!(interpret(&app_state, "(b)").err.is_some());
+    }
+
+    #[test]
+    fn interpret_stops_after_exit() {
+        let effect = run("(exit) (nope)");
+        assert!(effect.err.is_none());
+        assert!(effect.next_state.unwrap().get_exit());
+    }
+
+    #[test]
+    fn do_threads_state_and_returns_last_value() {
+        let effect = run("(do (defn a 1) (defn b (+ a 1)) (* a b 10))");
+        assert!(effect.err.is_none(), "{:?}", effect.err);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(20))));
+        assert!(effect.next_state.is_some());
+        assert_eq!(run("(do)").eval_value, Some(Expr::None));
+    }
+
+    #[test]
+    fn let_binds_locally_in_sequence() {
+        let effect = run_session(&["(defn x 100)", "(let ((x 1) (y (+ x 1))) (+ x y))"]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(3))));
+        assert!(effect.next_state.is_none());
+
+        // the global binding is untouched and the locals are gone afterwards
+        let effect = run_session(&["(defn x 100)", "(let ((x 1)) x)", "(x)"]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(100))));
+        assert!(run_session(&["(let ((y 1)) y)", "(y)"]).err.is_some());
+    }
+
+    #[test]
+    fn let_closures_capture_bindings() {
+        let effect = run_session(&["(defn add-n (let ((n 5)) (fn (x) (+ x n))))", "(add-n 1)"]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(6))));
+    }
+
+    #[test]
+    fn let_rejects_malformed_bindings() {
+        assert!(matches!(
+            run("(let (x 1) x)").err,
+            Some(JreplErr::OperatorFormatErr(_))
+        ));
+        assert!(matches!(
+            run("(let 1 2)").err,
+            Some(JreplErr::OperatorFormatErr(_))
+        ));
+    }
+
+    #[test]
+    fn set_rebinds_an_existing_symbol() {
+        let effect = run_session(&["(defn count 1)", "(set! count (+ count 1))", "(count)"]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(2))));
+        let effect = run_session(&[
+            "(defn total 0)",
+            "(do (set! total 5) (set! total (* total 2)))",
+            "(total)",
+        ]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(10))));
+    }
+
+    #[test]
+    fn set_requires_a_defined_global() {
+        assert!(matches!(
+            run("(set! nope 1)").err,
+            Some(JreplErr::UndefinedSymbol { .. })
+        ));
+        assert!(matches!(
+            run("(set! help 1)").err,
+            Some(JreplErr::InvalidSymbol(_))
+        ));
+        let effect = run_session(&["(defn x 1)", "(let ((x 2)) (set! x 3))"]);
+        assert!(matches!(effect.err, Some(JreplErr::EvalErr(_))));
+    }
+
+    #[test]
+    fn arithmetic_keeps_integers_exact() {
+        let effect = run("(+ 9007199254740993 0)");
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::Number(Number::Int(9_007_199_254_740_993)))
+        );
+        let effect = run("(* 9223372036854775807 2)");
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::Number(Number::parse("18446744073709551614").unwrap()))
+        );
+        assert_eq!(
+            run("(+ 1 0.5)").eval_value,
+            Some(Expr::Number(Number::float(1.5)))
+        );
+        assert_eq!(
+            run("(/ 6 3)").eval_value,
+            Some(Expr::Number(Number::Int(2)))
+        );
+        assert_eq!(
+            run("(- -5 0x10)").eval_value,
+            Some(Expr::Number(Number::Int(-21)))
+        );
+        assert_eq!(
+            run("(* 1e3 2)").eval_value,
+            Some(Expr::Number(Number::float(2000.0)))
+        );
+    }
+
+    #[test]
+    fn query_matches_non_ascii_names() {
+        let effect =
+            run(r#"(query (ld "dev/international.json") "$.people[?(@.city == '東京')].name")"#);
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::List(vec![Expr::String("山田太郎".to_string())]))
+        );
+        let effect = run_session(&[
+            r#"(defn leute (get (ld "dev/international.json") "people"))"#,
+            r#"(count (filter (fn (p) (= (get p "name") "J\u00fcrgen Gro\u00df")) leute))"#,
+        ]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(1))));
+        let effect = run(
+            r#"(= (get (get (ld "dev/international.json") "people") 2 "name") "Zoë \"Zo\" Ng")"#,
+        );
+        assert_eq!(effect.eval_value, Some(Expr::Bool(true)));
+    }
+
+    #[test]
+    fn pp_renders_values_as_text() {
+        let effect = run(r#"(pp {"b" [1 (+ 1 1)] "a" null})"#);
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::String(
+                "{\n  \"b\": [\n    1,\n    2\n  ],\n  \"a\": null\n}\n".to_string()
+            ))
+        );
+        let effect =
+            run(r#"(pp {"b" [1 2 3] "a" "x"} {"compact" true "sort-keys" true "width" 2})"#);
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::String("{\"a\":\"x\",\"b\":[1,2,…]}\n".to_string()))
+        );
+        assert!(matches!(
+            run("(pp 1 2)").err,
+            Some(JreplErr::OperatorFormatErr(_))
+        ));
+    }
+
+    #[test]
+    fn save_writes_and_remembers_the_path() {
+        let dir = std::env::temp_dir().join(format!("jrepl-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("doc.json").to_string_lossy().into_owned();
+        let _ = std::fs::remove_file(&path);
+
+        let save = format!(r#"(save doc "{}" :compact :no-newline)"#, path);
+        let app_state = run_recorded(&[r#"(defn doc {"b" 1 "a" [true]})"#, &save]);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"{"b":1,"a":[true]}"#
+        );
+        assert_eq!(app_state.provenance("doc"), Some(path.as_str()));
+
+        // an explicit path is never overwritten without :force
+        assert!(matches!(
+            value(&app_state, &save),
+            Err(JreplErr::FileExists(_))
+        ));
+
+        // without a path the value goes back to where it was saved, which also needs :force
+        let saved = format!(r#"(save doc "{}" :force)"#, path);
+        let effect = run_session(&[r#"(defn doc {"b" 2 "a" 0})"#, &saved, "(save doc)"]);
+        assert!(matches!(effect.err, Some(JreplErr::FileExists(_))));
+        let effect = run_session(&[
+            r#"(defn doc {"b" 2 "a" 0})"#,
+            &saved,
+            "(save doc :sort-keys :force)",
+        ]);
+        assert_eq!(effect.eval_value, Some(Expr::String(path.clone())));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "{\n  \"a\": 0,\n  \"b\": 2\n}\n"
+        );
+
+        assert!(matches!(
+            run(r#"(save {"a" 1})"#).err,
+            Some(JreplErr::OperatorFormatErr(_))
+        ));
+        assert!(matches!(
+            run(r#"(save 1 "x.json" :overwrite)"#).err,
+            Some(JreplErr::OperatorFormatErr(_))
+        ));
+    }
+
+    #[test]
+    fn rebinding_forgets_where_a_value_was_saved() {
+        let dir = std::env::temp_dir().join(format!("jrepl-rebind-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("src.json").to_string_lossy().into_owned();
+        std::fs::write(&path, "{}").unwrap();
+        let saved = format!(r#"(save src "{}" :force)"#, path);
+
+        let app_state = run_recorded(&[r#"(defn src {"a" 1})"#, &saved, "(defn src 5)"]);
+        assert_eq!(app_state.provenance("src"), None);
+        let app_state = run_recorded(&[r#"(defn src {"a" 1})"#, &saved, "(set! src 5)"]);
+        assert_eq!(app_state.provenance("src"), None);
+
+        for rebind in ["(defn src 5)", "(set! src 5)"] {
+            let effect =
+                run_session(&[r#"(defn src {"a" 1})"#, &saved, rebind, "(save src :force)"]);
+            assert!(
+                matches!(effect.err, Some(JreplErr::OperatorFormatErr(_))),
+                "{}: {:?}",
+                rebind,
+                effect.err
+            );
+        }
+        let effect = run_session(&[
+            r#"(defn src {"a" 1})"#,
+            &saved,
+            "(let ((src 5)) (save src :force))",
+        ]);
+        assert!(matches!(effect.err, Some(JreplErr::OperatorFormatErr(_))));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "{\n  \"a\": 1\n}\n"
+        );
+    }
+
+    #[test]
+    fn validate_returns_violations() {
+        let effect = run(
+            r#"(validate {"shipping" {"street" "Main St" "zip" "1"}} "dev/schemas/order.json")"#,
+        );
+        let Some(Expr::Array(violations)) = effect.eval_value else {
+            panic!("{:?}", effect.err)
+        };
+        assert_eq!(violations.len(), 2);
+        let Expr::Object(first) = &violations[0] else {
+            unreachable!()
+        };
+        assert_eq!(
+            first.get("instancePath"),
+            Some(&Expr::String("/shipping/zip".to_string()))
+        );
+        assert_eq!(
+            first.get("schemaPath"),
+            Some(&Expr::String(
+                "/properties/shipping/$ref/properties/zip/pattern".to_string()
+            ))
+        );
+
+        let effect = run_session(&[
+            r#"(defn schema {"type" "array" "items" {"type" "integer"}})"#,
+            "(count (validate [1 2 \"3\"] schema))",
+        ]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(1))));
+        assert!(matches!(
+            run(r#"(validate 1 "dev/schemas/missing.json")"#).err,
+            Some(JreplErr::IoErr(_))
+        ));
+    }
+
+    #[test]
+    fn infer_schema_describes_samples_and_validates_them() {
+        let effect = run_session(&[
+            r#"(defn users (get (ld "dev/array_of_objects.json") "users"))"#,
+            "(defn schema (infer-schema users))",
+            r#"[(get schema "/properties/age/type") (get schema "required") (count (filter (fn (u) (> (count (validate u schema)) 0)) users))]"#,
+        ]);
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::array(vec![
+                Expr::String("integer".to_string()),
+                Expr::array(vec![
+                    Expr::String("name".to_string()),
+                    Expr::String("age".to_string())
+                ]),
+                Expr::Number(Number::Int(0)),
+            ]))
+        );
+        let effect = run(r#"(get (infer-schema 1 "a") "type")"#);
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::array(vec![
+                Expr::String("integer".to_string()),
+                Expr::String("string".to_string())
+            ]))
+        );
+    }
+
+    #[test]
+    fn diff_compares_documents() {
+        let effect = run_session(&[
+            r#"(defn old {"users" [{"id" 1 "age" 25} {"id" 2 "age" 28}]})"#,
+            r#"(defn new {"users" [{"id" 2 "age" 29} {"id" 1 "age" 25}]})"#,
+            r#"(diff old new :by "id" :text)"#,
+        ]);
+        assert_eq!(
+            effect.eval_value,
+            Some(Expr::String(
+                "~ /users/1 -> /users/0\n- /users/0/age: 28\n+ /users/0/age: 29\n".to_string()
+            ))
+        );
+        let effect = run(r#"(count (diff [1 2] [1 2 3] :patch))"#);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(1))));
+        assert!(matches!(
+            run("(diff 1)").err,
+            Some(JreplErr::OperatorFormatErr(_))
+        ));
+        assert!(matches!(
+            run("(diff 1 2 :by)").err,
+            Some(JreplErr::OperatorFormatErr(_))
+        ));
+    }
+
+    #[test]
+    fn patch_and_merge_patch_apply_to_values() {
+        let effect = run_session(&[
+            r#"(defn doc {"users" [{"id" 1 "age" 25}] "stale" true})"#,
+            r#"(defn ops [{"op" "add" "path" "/users/-" "value" {"id" 2}} {"op" "remove" "path" "/stale"}])"#,
+            r#"(patch doc ops)"#,
+        ]);
+        assert_eq!(
+            effect
+                .eval_value
+                .map(|v| serde_json::Value::try_from(&v).unwrap()),
+            Some(serde_json::json!({"users": [{"id": 1, "age": 25}, {"id": 2}]}))
+        );
+        let effect = run(
+            r#"(patch {"a" 1} [{"op" "test" "path" "/a" "value" 1} {"op" "test" "path" "/a" "value" 2}])"#,
+        );
+        assert!(matches!(effect.err, Some(JreplErr::PatchErr(1, _))));
+        assert!(matches!(
+            run(r#"(patch {"a" 1} {"op" "remove"})"#).err,
+            Some(JreplErr::OperatorFormatErr(_))
+        ));
+
+        let effect = run(r#"(merge-patch {"a" {"b" 1 "c" 2}} {"a" {"c" null} "d" [1]})"#);
+        assert_eq!(
+            effect
+                .eval_value
+                .map(|v| serde_json::Value::try_from(&v).unwrap()),
+            Some(serde_json::json!({"a": {"b": 1}, "d": [1]}))
+        );
+    }
+
+    #[test]
+    fn collection_literals_evaluate_their_elements() {
+        let effect = run_session(&[
+            "(defn x 41)",
+            r#"(defn doc {"name": "Aaon", "age" (+ x 1), "tags": ["a", x]})"#,
+            r#"(get doc "age")"#,
+        ]);
+        assert_eq!(effect.eval_value, Some(Expr::Number(Number::Int(42))));
+
+        let effect = run_session(&["(defn x 41)", "[1 (+ x 1) [x]]"]);
+        let value = serde_json::Value::try_from(&effect.eval_value.unwrap()).unwrap();
+        assert_eq!(value, serde_json::json!([1, 42, [41]]));
+    }
+
+    #[test]
+    fn pasted_json_reads_as_is() {
+        let source = r#"{"id": 7, "tags": ["a", "b"], "price": -1.5e3, "owner": null, "ok": true}"#;
+        let effect = run(source);
+        assert!(effect.err.is_none(), "{:?}", effect.err);
+        let value = serde_json::Value::try_from(&effect.eval_value.unwrap()).unwrap();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            source.replace(": ", ":").replace(", ", ",")
+        );
+    }
 }
@@ -1,34 +1,105 @@
-use clap::builder::Str;
-
-use crate::errors::errors::JreplErr;
+use crate::{
+    command_interpreter::span::{Position, Span, Spanned},
+    errors::errors::JreplErr,
+};
 
 pub fn lexer(user_input: &str) -> Result<Vec<Token>, JreplErr> {
-    let acc = user_input
-        .chars()
-        .into_iter()
-        .try_fold(Accumulator::new(), |acc, c| transition_table(acc, c));
+    lex(user_input)
+        .map(|tokens| tokens.into_iter().map(|token| token.node).collect())
+        .map_err(|err| err.node)
+}
 
-    match acc {
-        Ok(acc) => {
-            if acc.delimiter_balance == 0 {
-                Ok(acc.tokens)
-            } else {
-                Err(JreplErr::UnbalancedDelimiter(String::from("Unbalanced parentheses.")))
-            }
+/// Tokenizes `user_input`, recording where each token came from. Errors point at the character
+/// that could not be consumed, or at the construct left open at the end of the input.
+pub fn lex(user_input: &str) -> Result<Vec<Spanned<Token>>, Spanned<JreplErr>> {
+    let mut acc = Accumulator::new();
+    let mut spans = Vec::new();
+    let mut pos = Position::start();
+    // where the token being built in `memory` started, and the openers not yet closed
+    let mut memory_start = pos;
+    let mut open_parens: Vec<(char, Span)> = Vec::new();
+
+    for c in user_input.chars() {
+        let next = pos.advance(c);
+        let here = Span::new(pos, next);
+        let (token_count, memory_was_empty) = (acc.tokens.len(), acc.memory.is_empty());
+
+        // newlines and tabs separate tokens like spaces do, but are kept in strings and comments
+        let in_text = matches!(acc.context_stack_peek(), Context::String | Context::Comment);
+        let c_fed = if c.is_whitespace() && !in_text {
+            ' '
+        } else {
+            c
+        };
+        acc = transition_table(acc, c_fed).map_err(|err| Spanned::new(err, here))?;
+
+        if memory_was_empty && !acc.memory.is_empty() {
+            memory_start = pos;
+        }
+        for token in &acc.tokens[token_count..] {
+            let span = match token {
+                Token::OpenParen(_) | Token::OpenBracket(_) | Token::OpenBrace(_) => {
+                    open_parens.push((c, here));
+                    here
+                }
+                Token::CloseParen(_) | Token::CloseBracket(_) | Token::CloseBrace(_) => {
+                    open_parens.pop();
+                    here
+                }
+                Token::Colon(_) => here,
+                // closed by this char, which is part of the token
+                Token::StringLiteral(_) | Token::Comment(_) => Span::new(memory_start, next),
+                // ended by this char, which is not
+                _ => Span::new(memory_start, pos),
+            };
+            spans.push(span);
         }
-        Err(err) => Err(err),
+        pos = next;
+    }
+
+    let unterminated = match acc.context_stack_peek() {
+        Context::String => Some("string literal"),
+        Context::Comment => Some("comment"),
+        _ => None,
+    };
+    if let Some(what) = unterminated {
+        return Err(Spanned::new(
+            JreplErr::UnbalancedDelimiter(format!("Unterminated {}.", what)),
+            Span::new(memory_start, pos),
+        ));
+    }
+    if acc.delimiter_balance != 0 {
+        let (opener, span) = open_parens
+            .last()
+            .copied()
+            .unwrap_or(('(', Span::new(pos, pos)));
+        let kind = match opener {
+            '(' => "parentheses",
+            '[' => "brackets",
+            _ => "braces",
+        };
+        return Err(Spanned::new(
+            JreplErr::UnbalancedDelimiter(format!(
+                "Unbalanced {}: this '{}' is never closed.",
+                kind, opener
+            )),
+            span,
+        ));
     }
+
+    Ok(acc
+        .tokens
+        .into_iter()
+        .zip(spans)
+        .map(|(token, span)| Spanned::new(token, span))
+        .collect())
 }
 
 fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplErr> {
     match acc.context_stack_peek() {
         Context::Init => match c {
-            '(' => {
-                acc.context_stack.push(Context::List);
-                acc.tokens.push(Token::OpenParen(c.to_string()));
-                acc.delimiter_balance_inc();
-                Ok(acc)
-            }
+            // a bare [..] or {..} is JSON pasted at the prompt
+            '(' | '[' | '{' => open_collection(acc, c),
 
             '`' => {
                 acc.context_stack.push(Context::Comment);
@@ -38,26 +109,29 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
 
             ' ' => Ok(acc),
 
-            _ => {
-                return Err(JreplErr::InvalidSymbol(format!(
-                    "Found Invalid char at start of user_input: '{}'.",
-                    c
-                )));
-            }
+            _ => Err(JreplErr::InvalidSymbol(format!(
+                "Found Invalid char at start of user_input: '{}'.",
+                c
+            ))),
         },
 
-        Context::List => match c {
-            '(' => {
-                acc.tokens.push(Token::OpenParen(c.to_string()));
-                acc.context_stack.push(Context::List);
-                acc.delimiter_balance_inc();
+        Context::List | Context::Array | Context::Object => match c {
+            '(' | '[' | '{' => open_collection(acc, c),
+
+            ')' | ']' | '}' => close_collection(acc, c),
+
+            // commas are whitespace, so JSON can be pasted as is
+            ',' => Ok(acc),
+
+            ':' if matches!(acc.context_stack_peek(), Context::Object) => {
+                acc.tokens.push(Token::Colon(c.to_string()));
                 Ok(acc)
             }
 
-            ')' => {
-                acc.tokens.push(Token::CloseParen(c.to_string()));
-                acc.context_stack.pop();
-                acc.delimiter_balance_dec()?;
+            // keyword options of a command, e.g. (save doc "out.json" :force)
+            ':' if matches!(acc.context_stack_peek(), Context::List) => {
+                acc.memory.push(c);
+                acc.context_stack.push(Context::Symbol);
                 Ok(acc)
             }
 
@@ -73,7 +147,7 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
                 Ok(acc)
             }
 
-            number if c.is_numeric() => {
+            number if c.is_ascii_digit() => {
                 acc.memory.push(number);
                 acc.context_stack.push(Context::Number);
                 Ok(acc)
@@ -97,7 +171,8 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
                 Ok(acc)
             }
 
-            _ if c.is_alphabetic() => {
+            // `$` is the input of a pipeline, see main.rs
+            _ if c.is_alphabetic() || c == '_' || c == '$' => {
                 acc.memory.push(c);
                 acc.context_stack.push(Context::Symbol);
                 Ok(acc)
@@ -143,11 +218,8 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
                     Ok(acc)
                 } else {
                     // finalize WITHOUT storing the quotes
-                    let inner = if acc.memory.starts_with('"') {
-                        acc.memory[1..].to_string()
-                    } else {
-                        acc.memory.clone()
-                    };
+                    let raw = acc.memory.strip_prefix('"').unwrap_or(&acc.memory);
+                    let inner = unescape(raw).map_err(JreplErr::LexErr)?;
                     acc.tokens.push(Token::StringLiteral(inner));
                     acc.reset_memory();
                     acc.context_stack.pop();
@@ -156,49 +228,26 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
             }
 
             // TODO: later support string interpolation.
-            _ if c.is_ascii() => {
+            _ => {
                 acc.memory.push(c);
                 Ok(acc)
             }
-
-            _ => Err(JreplErr::InvalidSymbol(format!(
-                "Found invalid char in string literal: {}.",
-                c
-            ))),
         },
 
         Context::Number => match c {
-            ')' => {
-                acc.tokens.push(Token::NumberLiteral(acc.memory.clone()));
-                acc.reset_memory();
-                acc.context_stack.pop();
-
-                acc.tokens.push(Token::CloseParen(String::from(c)));
-                acc.delimiter_balance_dec()?;
-
-                Ok(acc)
-            }
-
-            _ if c.is_numeric() => {
-                acc.memory.push(c);
-                Ok(acc)
-            }
-
-            _ if c == '.' && !acc.memory.contains(".") => {
+            _ if continues_number(&acc.memory, c) => {
                 acc.memory.push(c);
                 Ok(acc)
             }
 
-            ' ' => {
+            _ if ends_token(c) => {
                 if acc.memory.ends_with(".") {
                     return Err(JreplErr::InvalidSymbol(String::from(
                         "Found decimal point at end of number in user_input while tokenizing a Number. Number should end with a digit.",
                     )));
                 }
-                acc.context_stack.pop();
-                acc.tokens.push(Token::NumberLiteral(String::from(acc.memory.clone())));
-                acc.reset_memory();
-                Ok(acc)
+                let token = Token::NumberLiteral(acc.memory.clone());
+                end_token(acc, token, c)
             }
 
             _ => Err(JreplErr::InvalidSymbol(format!(
@@ -208,16 +257,6 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
         },
 
         Context::SymbolOrTrue => match c {
-            _ if acc.memory.ends_with("f") && (c == ' ' || c == ')') => {
-                if c == ')' {
-                    acc.delimiter_balance_dec()?;
-                }
-                acc.tokens.push(Token::Symbol(String::from("t")));
-                acc.context_stack.pop();
-                acc.reset_memory();
-                Ok(acc)
-            }
-
             _ if acc.memory.ends_with("t") && c == 'r' => {
                 acc.memory.push(c);
                 Ok(acc)
@@ -233,24 +272,18 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
                 Ok(acc)
             }
 
-            _ if acc.memory.ends_with("e") && c == ' ' => {
-                acc.tokens.push(Token::BoolLiteral(String::from("true")));
-                acc.context_stack.pop();
-                acc.reset_memory();
-                Ok(acc)
+            _ if acc.memory.ends_with("e") && ends_token(c) => {
+                end_token(acc, Token::BoolLiteral(String::from("true")), c)
             }
 
-            _ if acc.memory.ends_with("e") && c == ')' => {
-                acc.tokens.push(Token::BoolLiteral(String::from("true")));
-                acc.tokens.push(Token::CloseParen(String::from(")")));
+            // a prefix of the literal, e.g. `t` or `fa`, is just a symbol
+            _ if ends_token(c) => {
                 acc.context_stack.pop();
-                acc.context_stack.pop();
-                acc.reset_memory();
-                acc.delimiter_balance_dec()?;
-                Ok(acc)
+                acc.context_stack.push(Context::Symbol);
+                transition_table(acc, c)
             }
 
-            _ if c.is_alphanumeric() => {
+            _ if is_symbol_char(c) => {
                 acc.memory.push(c);
                 acc.context_stack.pop();
                 acc.context_stack.push(Context::Symbol);
@@ -264,16 +297,6 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
         },
 
         Context::SymbolOrFalse => match c {
-            _ if acc.memory.ends_with("f") && (c == ' ' || c == ')') => {
-                if c == ')' {
-                    acc.delimiter_balance_dec()?;
-                }
-                acc.tokens.push(Token::Symbol(String::from("f")));
-                acc.context_stack.pop();
-                acc.reset_memory();
-                Ok(acc)
-            }
-
             _ if acc.memory.ends_with("f") && c == 'a' => {
                 acc.memory.push(c);
                 Ok(acc)
@@ -294,24 +317,18 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
                 Ok(acc)
             }
 
-            _ if acc.memory.ends_with("e") && c == ' ' => {
-                acc.tokens.push(Token::BoolLiteral(String::from("false")));
-                acc.context_stack.pop();
-                acc.reset_memory();
-                Ok(acc)
+            _ if acc.memory.ends_with("e") && ends_token(c) => {
+                end_token(acc, Token::BoolLiteral(String::from("false")), c)
             }
 
-            _ if acc.memory.ends_with("e") && c == ')' => {
-                acc.tokens.push(Token::BoolLiteral(String::from("false")));
-                acc.tokens.push(Token::CloseParen(String::from(")")));
-                acc.context_stack.pop();
+            // a prefix of the literal, e.g. `t` or `fa`, is just a symbol
+            _ if ends_token(c) => {
                 acc.context_stack.pop();
-                acc.reset_memory();
-                acc.delimiter_balance_dec()?;
-                Ok(acc)
+                acc.context_stack.push(Context::Symbol);
+                transition_table(acc, c)
             }
 
-            _ if c.is_alphanumeric() => {
+            _ if is_symbol_char(c) => {
                 acc.memory.push(c);
                 acc.context_stack.pop();
                 acc.context_stack.push(Context::Symbol);
@@ -325,28 +342,34 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
         },
 
         Context::Symbol => match c {
-            _ if c.is_alphanumeric() => {
+            // a minus sign directly before a digit makes a negative number, e.g. -5
+            _ if acc.memory == "-" && c.is_ascii_digit() => {
                 acc.memory.push(c);
+                acc.context_stack.pop();
+                acc.context_stack.push(Context::Number);
                 Ok(acc)
             }
 
-            ' ' => {
-                acc.tokens.push(Token::Symbol(String::from(acc.memory.clone())));
-                acc.reset_memory();
-                acc.context_stack.pop();
+            _ if is_symbol_char(c) => {
+                acc.memory.push(c);
                 Ok(acc)
             }
 
-            ')' => {
-                acc.tokens.push(Token::Symbol(String::from(acc.memory.clone())));
-                acc.reset_memory();
-                acc.context_stack.pop(); // leaving symbol
-                acc.context_stack.pop(); // leaving list
-                acc.tokens.push(Token::CloseParen(String::from(c)));
-                acc.delimiter_balance_dec()?;
+            // operators of more than one char, e.g. <= and !=
+            _ if is_op_char(c) && acc.memory.chars().all(is_op_char) => {
+                acc.memory.push(c);
                 Ok(acc)
             }
 
+            _ if acc.memory == ":" => Err(JreplErr::InvalidSymbol(
+                "A keyword needs a name after the ':', e.g. :force.".to_string(),
+            )),
+
+            _ if ends_token(c) => {
+                let token = Token::Symbol(acc.memory.clone());
+                end_token(acc, token, c)
+            }
+
             _ => Err(JreplErr::InvalidSymbol(format!(
                 "Found Invalid char in user_input while tokenizing a Symbol. Char: {}.",
                 c
@@ -355,8 +378,147 @@ fn transition_table(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplE
     }
 }
 
+/// Decodes the JSON escapes in the body of a string literal: \" \\ \/ \b \f \n \r \t and
+/// \uXXXX, where a surrogate pair spells one character. Other escapes are an error.
+fn unescape(raw: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let decoded = match chars.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let high = hex_escape(&mut chars)?;
+                if (0xDC00..0xE000).contains(&high) {
+                    return Err(format!(
+                        "Unpaired low surrogate \\u{:04X} in string literal.",
+                        high
+                    ));
+                }
+                let code = if (0xD800..0xDC00).contains(&high) {
+                    let low = match (chars.next(), chars.next()) {
+                        (Some('\\'), Some('u')) => hex_escape(&mut chars)?,
+                        _ => 0,
+                    };
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(format!(
+                            "High surrogate \\u{:04X} must be followed by a low surrogate \\uDC00-\\uDFFF.",
+                            high
+                        ));
+                    }
+                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    high
+                };
+                char::from_u32(code)
+                    .ok_or_else(|| format!("Invalid escape \\u{:04X} in string literal.", code))?
+            }
+            Some(other) => {
+                return Err(format!(
+                    "Unknown escape '\\{}' in string literal. Use \\\\ for a backslash.",
+                    other
+                ));
+            }
+            None => return Err("String literal ends with a lone backslash.".to_string()),
+        };
+        out.push(decoded);
+    }
+    Ok(out)
+}
+
+// The four hex digits after \u.
+fn hex_escape(chars: &mut std::str::Chars) -> Result<u32, String> {
+    let digits: String = chars.take(4).collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "Expected 4 hex digits after \\u in string literal, got '{}'.",
+            digits
+        ));
+    }
+    u32::from_str_radix(&digits, 16).map_err(|e| e.to_string())
+}
+
 fn is_op_char(ch: char) -> bool {
-    matches!(ch, '+' | '-' | '*' | '/' | '<' | '>' | '=')
+    matches!(ch, '+' | '-' | '*' | '/' | '<' | '>' | '=' | '!')
+}
+
+fn open_collection(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplErr> {
+    let (token, context) = match c {
+        '(' => (Token::OpenParen(c.to_string()), Context::List),
+        '[' => (Token::OpenBracket(c.to_string()), Context::Array),
+        _ => (Token::OpenBrace(c.to_string()), Context::Object),
+    };
+    acc.tokens.push(token);
+    acc.context_stack.push(context);
+    acc.delimiter_balance_inc();
+    Ok(acc)
+}
+
+fn close_collection(mut acc: Accumulator, c: char) -> Result<Accumulator, JreplErr> {
+    let expected = match acc.context_stack_peek() {
+        Context::List => ')',
+        Context::Array => ']',
+        _ => '}',
+    };
+    if c != expected {
+        return Err(JreplErr::UnbalancedDelimiter(format!(
+            "Mismatched '{}': the innermost open collection is closed with '{}'.",
+            c, expected
+        )));
+    }
+    acc.tokens.push(match c {
+        ')' => Token::CloseParen(c.to_string()),
+        ']' => Token::CloseBracket(c.to_string()),
+        _ => Token::CloseBrace(c.to_string()),
+    });
+    acc.context_stack.pop();
+    acc.delimiter_balance_dec()?;
+    Ok(acc)
+}
+
+// Chars that end a number, bool or symbol without being part of it.
+fn ends_token(c: char) -> bool {
+    matches!(c, ' ' | ')' | ']' | '}' | ',' | ':')
+}
+
+// Emits the finished `token`, then lets the enclosing collection handle `c`, e.g. a closer.
+fn end_token(mut acc: Accumulator, token: Token, c: char) -> Result<Accumulator, JreplErr> {
+    acc.tokens.push(token);
+    acc.reset_memory();
+    acc.context_stack.pop();
+    transition_table(acc, c)
+}
+
+// Decimal digits with an optional fraction and exponent, or 0x and hex digits. The parser
+// checks the whole literal, e.g. that an exponent has digits.
+fn continues_number(memory: &str, ch: char) -> bool {
+    let unsigned = memory.strip_prefix('-').unwrap_or(memory);
+    if unsigned.starts_with("0x") || unsigned.starts_with("0X") {
+        return ch.is_ascii_hexdigit();
+    }
+    let has_exponent = unsigned.contains(['e', 'E']);
+    match ch {
+        '0'..='9' => true,
+        'x' | 'X' => unsigned == "0",
+        '.' => !unsigned.contains('.') && !has_exponent,
+        'e' | 'E' => !has_exponent && unsigned.ends_with(|c: char| c.is_ascii_digit()),
+        '+' | '-' => unsigned.ends_with(['e', 'E']),
+        _ => false,
+    }
+}
+
+fn is_symbol_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '-' | '!')
 }
 
 struct Accumulator {
@@ -380,7 +542,7 @@ impl Accumulator {
         if self.context_stack.is_empty() {
             return Context::Init;
         }
-        return self.context_stack.last().unwrap().clone();
+        self.context_stack.last().unwrap().clone()
     }
 
     fn reset_memory(&mut self) {
@@ -394,7 +556,9 @@ impl Accumulator {
     fn delimiter_balance_dec(&mut self) -> Result<(), JreplErr> {
         self.delimiter_balance -= 1;
         if self.delimiter_balance < 0 {
-            return Err(JreplErr::UnbalancedDelimiter(String::from("")));
+            return Err(JreplErr::UnbalancedDelimiter(String::from(
+                "Unexpected ')' with no matching '('.",
+            )));
         }
         Ok(())
     }
@@ -404,6 +568,11 @@ impl Accumulator {
 pub enum Token {
     OpenParen(String),
     CloseParen(String),
+    OpenBracket(String),
+    CloseBracket(String),
+    OpenBrace(String),
+    CloseBrace(String),
+    Colon(String),
     Comment(String),
     Symbol(String),
 
@@ -416,6 +585,8 @@ pub enum Token {
 enum Context {
     Init,
     List,
+    Array,
+    Object,
     Comment,
 
     SymbolOrTrue,
@@ -455,7 +626,8 @@ mod test {
 
     #[test]
     fn lexer_load_file_and_defn() {
-        let result = lexer("(defn f (ld \"temp.json\"))").expect("[lexer_load_file_and_defn] Produced an error.");
+        let result = lexer("(defn f (ld \"temp.json\"))")
+            .expect("[lexer_load_file_and_defn] Produced an error.");
         assert_eq!(
             result,
             vec![
@@ -474,7 +646,170 @@ mod test {
     #[test]
     fn lexer_load_file() {
         let result = lexer("(ld \"temp.json\")").expect("[lexer_load_file] Produced an error.");
-        assert_eq!(result, vec![open(), symbol("ld"), strlit("temp.json"), close()]);
+        assert_eq!(
+            result,
+            vec![open(), symbol("ld"), strlit("temp.json"), close()]
+        );
+    }
+
+    #[test]
+    fn lexer_keywords() {
+        let result =
+            lexer("(save doc :force :sort-keys)").expect("[lexer_keywords] Produced an error.");
+        assert_eq!(
+            result,
+            vec![
+                open(),
+                symbol("save"),
+                symbol("doc"),
+                symbol(":force"),
+                symbol(":sort-keys"),
+                close()
+            ]
+        );
+        assert!(lexer("[:force]").is_err());
+    }
+
+    #[test]
+    fn lexer_multi_char_operators() {
+        let result = lexer("(and (<= a 1) (!= b 2) (set! c 3))")
+            .expect("[lexer_multi_char_operators] Produced an error.");
+        assert_eq!(
+            result,
+            vec![
+                open(),
+                symbol("and"),
+                open(),
+                symbol("<="),
+                symbol("a"),
+                number("1"),
+                close(),
+                open(),
+                symbol("!="),
+                symbol("b"),
+                number("2"),
+                close(),
+                open(),
+                symbol("set!"),
+                symbol("c"),
+                number("3"),
+                close(),
+                close()
+            ]
+        );
+        assert!(lexer("(a<b 1)").is_err());
+    }
+
+    #[test]
+    fn lexer_number_forms() {
+        let result = lexer("(+ -5 1e10 2.5E-3 0x1F -0xff (- 5))")
+            .expect("[lexer_number_forms] Produced an error.");
+        assert_eq!(
+            result,
+            vec![
+                open(),
+                symbol("+"),
+                number("-5"),
+                number("1e10"),
+                number("2.5E-3"),
+                number("0x1F"),
+                number("-0xff"),
+                open(),
+                symbol("-"),
+                number("5"),
+                close(),
+                close()
+            ]
+        );
+        assert!(lexer("(+ 1e5e5)").is_err());
+        assert!(lexer("(+ 0x1.5)").is_err());
+    }
+
+    #[test]
+    fn lexer_string_escapes_are_decoded() {
+        let result = lexer(r#"("a\"b\\c\/\n\t\u00e9 \uD83D\uDE00")"#)
+            .expect("[lexer_string_escapes_are_decoded] Produced an error.");
+        assert_eq!(result, vec![open(), strlit("a\"b\\c/\n\té 😀"), close()]);
+    }
+
+    #[test]
+    fn lexer_strings_and_symbols_hold_unicode() {
+        let result = lexer("(defn 名前 \"Jürgen 山田\")")
+            .expect("[lexer_strings_and_symbols_hold_unicode] Produced an error.");
+        assert_eq!(
+            result,
+            vec![
+                open(),
+                symbol("defn"),
+                symbol("名前"),
+                strlit("Jürgen 山田"),
+                close()
+            ]
+        );
+    }
+
+    #[test]
+    fn lexer_bad_escapes_error() {
+        for input in [
+            r#"("\q")"#,
+            r#"("\u12")"#,
+            r#"("\uD83D")"#,
+            r#"("\uDE00")"#,
+            r#"("\uD83Dx")"#,
+        ] {
+            assert!(
+                matches!(lexer(input), Err(JreplErr::LexErr(_))),
+                "{}: {:?}",
+                input,
+                lexer(input)
+            );
+        }
+    }
+
+    #[test]
+    fn lexer_array_and_object_literals() {
+        let result = lexer(r#"{"a": [1, true], "b" -2}"#)
+            .expect("[lexer_array_and_object_literals] Produced an error.");
+        assert_eq!(
+            result,
+            vec![
+                Token::OpenBrace("{".into()),
+                strlit("a"),
+                Token::Colon(":".into()),
+                Token::OpenBracket("[".into()),
+                number("1"),
+                bool("true"),
+                Token::CloseBracket("]".into()),
+                strlit("b"),
+                number("-2"),
+                Token::CloseBrace("}".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lexer_mismatched_closer_error() {
+        assert_eq!(
+            lexer("(+ [1 2)"),
+            Err(JreplErr::UnbalancedDelimiter(
+                "Mismatched ')': the innermost open collection is closed with ']'.".to_string()
+            ))
+        );
+        assert_eq!(
+            lexer("(get {\"a\" [1 2}"),
+            Err(JreplErr::UnbalancedDelimiter(
+                "Mismatched '}': the innermost open collection is closed with ']'.".to_string()
+            ))
+        );
+        let err = lex("(list [1 2").unwrap_err();
+        assert_eq!(
+            err.node,
+            JreplErr::UnbalancedDelimiter(
+                "Unbalanced brackets: this '[' is never closed.".to_string()
+            )
+        );
+        assert_eq!(columns(err.span), (7, 8));
+        assert!(lexer("(a : b)").is_err());
     }
 
     #[test]
@@ -497,7 +832,8 @@ mod test {
 
     #[test]
     fn lexer_handles_backtick_comment() {
-        let result = lexer("(foo `this is a comment` bar)").expect("[lexer_help_command] Produced an error.");
+        let result = lexer("(foo `this is a comment` bar)")
+            .expect("[lexer_help_command] Produced an error.");
         assert_eq!(
             result,
             vec![
@@ -512,27 +848,45 @@ mod test {
 
     #[test]
     fn lexer_handles_single_char_operators_as_tokens() {
-        let result = lexer("(+ 1 2)").expect("[lexer_handles_single_char_operators_as_tokens] Produced an error.");
-        assert_eq!(result, vec![open(), symbol("+"), number("1"), number("2"), close()]);
+        let result = lexer("(+ 1 2)")
+            .expect("[lexer_handles_single_char_operators_as_tokens] Produced an error.");
+        assert_eq!(
+            result,
+            vec![open(), symbol("+"), number("1"), number("2"), close()]
+        );
     }
 
     #[test]
     fn lexer_handles_plus_symbol_operator() {
-        let result = lexer("(+ 1 2)").expect("[lexer_handles_plus_symbol_operator] Produced an error.");
-        assert_eq!(result, vec![open(), symbol("+"), number("1"), number("2"), close()]);
+        let result =
+            lexer("(+ 1 2)").expect("[lexer_handles_plus_symbol_operator] Produced an error.");
+        assert_eq!(
+            result,
+            vec![open(), symbol("+"), number("1"), number("2"), close()]
+        );
     }
 
     #[test]
     fn lexer_white_space_in_command_allowed() {
-        assert_eq!(lexer("( help   )").unwrap(), vec![open(), symbol("help"), close()]);
+        assert_eq!(
+            lexer("( help   )").unwrap(),
+            vec![open(), symbol("help"), close()]
+        );
     }
 
     #[test]
     fn lexer_numbers_and_symbols() {
-        let result = lexer("(add 123 foo456)").expect("[lexer_numbers_and_symbols] Produced an error.");
+        let result =
+            lexer("(add 123 foo456)").expect("[lexer_numbers_and_symbols] Produced an error.");
         assert_eq!(
             result,
-            vec![open(), symbol("add"), number("123"), symbol("foo456"), close()],
+            vec![
+                open(),
+                symbol("add"),
+                number("123"),
+                symbol("foo456"),
+                close()
+            ],
         );
     }
 
@@ -557,15 +911,26 @@ mod test {
 
     #[test]
     fn lexer_ignores_extra_spaces() {
-        let result = lexer(" (   add    1    2  ) ").expect("[lexer_ignores_extra_spaces] Produced an error.");
-        assert_eq!(result, vec![open(), symbol("add"), number("1"), number("2"), close()]);
+        let result = lexer(" (   add    1    2  ) ")
+            .expect("[lexer_ignores_extra_spaces] Produced an error.");
+        assert_eq!(
+            result,
+            vec![open(), symbol("add"), number("1"), number("2"), close()]
+        );
     }
 
     #[test]
     fn lexer_handles_multiple_expressions() {
         assert_eq!(
             lexer("(help)(quit)").unwrap(),
-            vec![open(), symbol("help"), close(), open(), symbol("quit"), close()]
+            vec![
+                open(),
+                symbol("help"),
+                close(),
+                open(),
+                symbol("quit"),
+                close()
+            ]
         );
     }
 
@@ -584,6 +949,48 @@ mod test {
         );
     }
 
+    #[test]
+    fn lexer_symbols_with_underscore_and_dash() {
+        assert_eq!(
+            lexer("(sort-by deeply_nested _tmp)").unwrap(),
+            vec![
+                open(),
+                symbol("sort-by"),
+                symbol("deeply_nested"),
+                symbol("_tmp"),
+                close()
+            ]
+        );
+    }
+
+    #[test]
+    fn lexer_prefixes_of_bool_literals_are_symbols() {
+        assert_eq!(
+            lexer("(f t fa tru)").unwrap(),
+            vec![
+                open(),
+                symbol("f"),
+                symbol("t"),
+                symbol("fa"),
+                symbol("tru"),
+                close()
+            ]
+        );
+        assert_eq!(lexer("(f)").unwrap(), vec![open(), symbol("f"), close()]);
+        assert_eq!(
+            lexer("(fn (t) t)").unwrap(),
+            vec![
+                open(),
+                symbol("fn"),
+                open(),
+                symbol("t"),
+                close(),
+                symbol("t"),
+                close()
+            ]
+        );
+    }
+
     #[test]
     fn lexer_parses_paren_in_string() {
         assert_eq!(
@@ -626,21 +1033,25 @@ mod test {
 
     #[test]
     fn lexer_multiple_whitespace_everywhere() {
-        let result =
-            lexer(" (    add   1     foo   )  ").expect("[lexer_multiple_whitespace_everywhere] Produced an error.");
-        assert_eq!(result, vec![open(), symbol("add"), number("1"), symbol("foo"), close()]);
+        let result = lexer(" (    add   1     foo   )  ")
+            .expect("[lexer_multiple_whitespace_everywhere] Produced an error.");
+        assert_eq!(
+            result,
+            vec![open(), symbol("add"), number("1"), symbol("foo"), close()]
+        );
     }
 
     #[test]
     fn lexer_comment_only_expr() {
-        let result = lexer("(`just a comment`)").expect("[lexer_comment_only_expr] Produced an error.");
+        let result =
+            lexer("(`just a comment`)").expect("[lexer_comment_only_expr] Produced an error.");
         assert_eq!(result, vec![open(), comment("`just a comment`"), close()]);
     }
 
     #[test]
     fn lexer_comment_at_start_middle_end() {
-        let result =
-            lexer("(`start` foo `middle` bar `end`)").expect("[lexer_comment_at_start_middle_end] Produced an error.");
+        let result = lexer("(`start` foo `middle` bar `end`)")
+            .expect("[lexer_comment_at_start_middle_end] Produced an error.");
         assert_eq!(
             result,
             vec![
@@ -657,16 +1068,24 @@ mod test {
 
     #[test]
     fn lexer_string_literal_only_expr() {
-        let result = lexer("(\"hello world\")").expect("[lexer_string_literal_only_expr] Produced an error.");
+        let result = lexer("(\"hello world\")")
+            .expect("[lexer_string_literal_only_expr] Produced an error.");
         assert_eq!(result, vec![open(), strlit("hello world"), close()]);
     }
 
     #[test]
     fn lexer_string_literal_and_number() {
-        let result = lexer("(print \"num is\" 100)").expect("[lexer_string_literal_and_number] Produced an error.");
+        let result = lexer("(print \"num is\" 100)")
+            .expect("[lexer_string_literal_and_number] Produced an error.");
         assert_eq!(
             result,
-            vec![open(), symbol("print"), strlit("num is"), number("100"), close()]
+            vec![
+                open(),
+                symbol("print"),
+                strlit("num is"),
+                number("100"),
+                close()
+            ]
         );
     }
 
@@ -689,10 +1108,17 @@ mod test {
 
     #[test]
     fn lexer_number_with_decimal() {
-        let result = lexer("(add 3.14 2.71)").expect("[lexer_number_with_decimal] Produced an error.");
+        let result =
+            lexer("(add 3.14 2.71)").expect("[lexer_number_with_decimal] Produced an error.");
         assert_eq!(
             result,
-            vec![open(), symbol("add"), number("3.14"), number("2.71"), close()]
+            vec![
+                open(),
+                symbol("add"),
+                number("3.14"),
+                number("2.71"),
+                close()
+            ]
         );
     }
 
@@ -715,26 +1141,105 @@ mod test {
     #[test]
     fn lexer_unbalanced_open_paren_error() {
         let result = lexer("(foo bar");
-        assert!(result.is_err(), "Expected an error for unbalanced open paren.");
+        assert!(
+            result.is_err(),
+            "Expected an error for unbalanced open paren."
+        );
     }
 
     #[test]
     fn lexer_unbalanced_close_paren_error() {
         let result = lexer("foo bar)");
-        assert!(result.is_err(), "Expected an error for unbalanced close paren.");
+        assert!(
+            result.is_err(),
+            "Expected an error for unbalanced close paren."
+        );
     }
 
     // Error test (unterminated string)
     #[test]
     fn lexer_unterminated_string_literal_error() {
         let result = lexer("(print \"unterminated)");
-        assert!(result.is_err(), "Expected an error for unterminated string literal.");
+        assert!(
+            result.is_err(),
+            "Expected an error for unterminated string literal."
+        );
     }
 
     // Error test (unterminated comment)
     #[test]
     fn lexer_unterminated_comment_error() {
         let result = lexer("(foo `unterminated comment)");
-        assert!(result.is_err(), "Expected an error for unterminated comment.");
+        assert!(
+            result.is_err(),
+            "Expected an error for unterminated comment."
+        );
+    }
+
+    fn columns(span: Span) -> (usize, usize) {
+        (span.start.column, span.end.column)
+    }
+
+    #[test]
+    fn lex_records_token_spans() {
+        let tokens = lex("(ld \"a b\" `c` true)").unwrap();
+        let spans: Vec<(usize, usize)> = tokens.iter().map(|t| columns(t.span)).collect();
+        assert_eq!(
+            spans,
+            vec![(1, 2), (2, 4), (5, 10), (11, 14), (15, 19), (19, 20)]
+        );
+    }
+
+    #[test]
+    fn lex_tracks_lines_inside_multiline_tokens() {
+        let tokens = lex("(foo `one\ntwo` bar)").unwrap();
+        let bar = &tokens[3].span;
+        assert_eq!((bar.start.line, bar.start.column), (2, 6));
+        assert_eq!(bar.start.offset, 15);
+    }
+
+    #[test]
+    fn lex_errors_point_at_the_problem() {
+        let err = lex("(+ 12x 3)").unwrap_err();
+        assert_eq!(columns(err.span), (6, 7));
+
+        let err = lex("(+ 1 (foo 2)").unwrap_err();
+        assert_eq!(columns(err.span), (1, 2));
+
+        let err = lex("(print \"unterminated)").unwrap_err();
+        assert_eq!(
+            err.node,
+            JreplErr::UnbalancedDelimiter("Unterminated string literal.".to_string())
+        );
+        assert_eq!(columns(err.span), (8, 22));
+    }
+
+    #[test]
+    fn lexer_newlines_and_tabs_separate_tokens() {
+        assert_eq!(
+            lexer("(defn x\n\t1)\r\n(help)\n").unwrap(),
+            vec![
+                open(),
+                symbol("defn"),
+                symbol("x"),
+                number("1"),
+                close(),
+                open(),
+                symbol("help"),
+                close()
+            ]
+        );
+        assert_eq!(
+            lexer("(\"a\tb\")").unwrap(),
+            vec![open(), strlit("a\tb"), close()]
+        );
+    }
+
+    #[test]
+    fn lexer_dollar_is_a_symbol() {
+        assert_eq!(
+            lexer("(get $ \"a\")").unwrap(),
+            vec![open(), symbol("get"), symbol("$"), strlit("a"), close()]
+        );
     }
 }
@@ -2,9 +2,11 @@ pub mod command;
 pub mod eval;
 pub mod interpreter;
 pub mod lexer;
+pub mod number;
 pub mod parser;
+pub mod span;
 pub mod types;
+#[allow(dead_code)]
 pub mod validation;
 
 use eval::eval;
-use lexer::lexer;
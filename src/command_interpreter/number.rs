@@ -0,0 +1,336 @@
+/*
+    Numbers.
+
+    Integers are exact. They stay i64 while they fit and grow into a BigInt when they don't, so
+    IDs past 2^53 survive a load and a save. Floats are f64 and remember the literal they were
+    read from, so writing a document back gives the number exactly as it was written.
+
+    Arithmetic on two integers stays exact, anything involving a float is done in f64. Equality
+    and ordering compare values, so 1 and 1.0 are equal.
+*/
+
+use std::{cmp::Ordering, fmt, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::errors::errors::JreplErr;
+
+#[derive(Clone)]
+pub enum Number {
+    Int(i64),
+    /// Integers outside the i64 range only, see `Number::big`.
+    BigInt(Rc<BigInt>),
+    /// `text` is the JSON literal the value was read from, if any.
+    Float {
+        value: f64,
+        text: Option<Rc<str>>,
+    },
+}
+
+impl Number {
+    pub fn float(value: f64) -> Number {
+        Number::Float { value, text: None }
+    }
+
+    /// An integer, as an i64 when it fits.
+    pub fn big(n: BigInt) -> Number {
+        match n.to_i64() {
+            Some(i) => Number::Int(i),
+            None => Number::BigInt(Rc::new(n)),
+        }
+    }
+
+    /// Reads a literal: a decimal integer, a `0x` hex integer, or a float with a fraction or an
+    /// exponent, each optionally negative. None if `text` is none of these.
+    pub fn parse(text: &str) -> Option<Number> {
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let sign = |n: BigInt| if negative { -n } else { n };
+
+        if let Some(hex) = unsigned
+            .strip_prefix("0x")
+            .or_else(|| unsigned.strip_prefix("0X"))
+        {
+            if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            return BigInt::parse_bytes(hex.as_bytes(), 16).map(|n| Number::big(sign(n)));
+        }
+        if !is_decimal_literal(unsigned) {
+            return None;
+        }
+        if unsigned.bytes().all(|b| b.is_ascii_digit()) {
+            return BigInt::parse_bytes(unsigned.as_bytes(), 10).map(|n| Number::big(sign(n)));
+        }
+
+        let value = text.parse::<f64>().ok()?;
+        // JSON has no leading zeros, so 01.5 is not kept for writing back
+        let is_json = !unsigned.starts_with('0')
+            || unsigned[1..].starts_with(['.', 'e', 'E'])
+            || unsigned == "0";
+        Some(Number::Float {
+            value,
+            text: is_json.then(|| Rc::from(text)),
+        })
+    }
+
+    pub fn from_json(n: &serde_json::Number) -> Number {
+        Number::parse(n.as_str()).unwrap_or_else(|| Number::float(n.as_f64().unwrap_or(f64::NAN)))
+    }
+
+    pub fn to_json(&self) -> Result<serde_json::Number, JreplErr> {
+        let text = match self {
+            Number::Int(i) => return Ok(serde_json::Number::from(*i)),
+            Number::BigInt(n) => n.to_string(),
+            Number::Float {
+                text: Some(text), ..
+            } => text.to_string(),
+            Number::Float { value, text: None } => {
+                return serde_json::Number::from_f64(*value).ok_or_else(|| {
+                    JreplErr::JsonErr(format!("Number {} has no JSON representation", value))
+                });
+            }
+        };
+        serde_json::from_str(&text)
+            .map_err(|e| JreplErr::JsonErr(format!("Invalid number {}: {}", text, e)))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(self, Number::Float { .. })
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Int(i) => *i == 0,
+            Number::BigInt(n) => n.is_zero(),
+            Number::Float { value, .. } => *value == 0.0,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::BigInt(n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Float { value, .. } => *value,
+        }
+    }
+
+    /// The value as an i64 if it is a whole number in range, e.g. 3 or 3.0.
+    pub fn to_i64(&self) -> Option<i64> {
+        match self {
+            Number::Int(i) => Some(*i),
+            Number::BigInt(_) => None,
+            Number::Float { value, .. } => {
+                (value.fract() == 0.0 && value.abs() < i64::MAX as f64).then_some(*value as i64)
+            }
+        }
+    }
+
+    pub fn to_usize(&self) -> Option<usize> {
+        self.to_i64().and_then(|i| usize::try_from(i).ok())
+    }
+
+    fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Number::Int(i) => Some(BigInt::from(*i)),
+            Number::BigInt(n) => Some(BigInt::clone(n)),
+            Number::Float { .. } => None,
+        }
+    }
+
+    fn combine(
+        &self,
+        other: &Number,
+        int_op: fn(i64, i64) -> Option<i64>,
+        big_op: fn(BigInt, BigInt) -> BigInt,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Number {
+        if let (Number::Int(a), Number::Int(b)) = (self, other)
+            && let Some(n) = int_op(*a, *b)
+        {
+            return Number::Int(n);
+        }
+        match (self.to_bigint(), other.to_bigint()) {
+            (Some(a), Some(b)) => Number::big(big_op(a, b)),
+            _ => Number::float(float_op(self.to_f64(), other.to_f64())),
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> Number {
+        self.combine(other, i64::checked_add, |a, b| a + b, |a, b| a + b)
+    }
+
+    pub fn sub(&self, other: &Number) -> Number {
+        self.combine(other, i64::checked_sub, |a, b| a - b, |a, b| a - b)
+    }
+
+    pub fn mul(&self, other: &Number) -> Number {
+        self.combine(other, i64::checked_mul, |a, b| a * b, |a, b| a * b)
+    }
+
+    pub fn neg(&self) -> Number {
+        Number::Int(0).sub(self)
+    }
+
+    /// Integer division stays exact when it divides evenly, otherwise the result is a float.
+    pub fn div(&self, other: &Number) -> Result<Number, JreplErr> {
+        if other.is_zero() {
+            return Err(JreplErr::ArithmeticErr("Division by zero".to_string()));
+        }
+        if let (Some(a), Some(b)) = (self.to_bigint(), other.to_bigint())
+            && (&a % &b).is_zero()
+        {
+            return Ok(Number::big(a / b));
+        }
+        Ok(Number::float(self.to_f64() / other.to_f64()))
+    }
+
+    /// An order over every number, NaN included, for sorting.
+    pub fn total_cmp(&self, other: &Number) -> Ordering {
+        self.partial_cmp(other)
+            .unwrap_or_else(|| self.to_f64().total_cmp(&other.to_f64()))
+    }
+}
+
+fn is_decimal_literal(text: &str) -> bool {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(at) => (&text[..at], Some(&text[at + 1..])),
+        None => (text, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None),
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let exponent_ok = exponent.is_none_or(|e| digits(e.strip_prefix(['+', '-']).unwrap_or(e)));
+    digits(int) && frac.is_none_or(digits) && exponent_ok
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Some(a.cmp(b)),
+            _ => match (self.to_bigint(), other.to_bigint()) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                _ => self.to_f64().partial_cmp(&other.to_f64()),
+            },
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(i: i64) -> Number {
+        Number::Int(i)
+    }
+}
+
+impl From<usize> for Number {
+    fn from(n: usize) -> Number {
+        Number::big(BigInt::from(n))
+    }
+}
+
+// The literal, so values print the way they are written, e.g. Number(3) and Number(1.5).
+impl fmt::Debug for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Int(i) => write!(f, "{}", i),
+            Number::BigInt(n) => write!(f, "{}", n),
+            Number::Float {
+                text: Some(text), ..
+            } => write!(f, "{}", text),
+            Number::Float { value, text: None } => match serde_json::Number::from_f64(*value) {
+                Some(n) => write!(f, "{}", n),
+                None => write!(f, "{}", value),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(text: &str) -> String {
+        let n: serde_json::Number = serde_json::from_str(text).unwrap();
+        Number::from_json(&n).to_json().unwrap().to_string()
+    }
+
+    #[test]
+    fn parse_reads_every_literal_form() {
+        assert_eq!(Number::parse("42"), Some(Number::Int(42)));
+        assert_eq!(Number::parse("-5"), Some(Number::Int(-5)));
+        assert_eq!(Number::parse("0x1F"), Some(Number::Int(31)));
+        assert_eq!(Number::parse("-0xff"), Some(Number::Int(-255)));
+        assert_eq!(Number::parse("1e10"), Some(Number::float(1e10)));
+        assert_eq!(Number::parse("2.5E-3"), Some(Number::float(0.0025)));
+        assert!(matches!(Number::parse("1e10"), Some(Number::Float { .. })));
+        assert!(matches!(
+            Number::parse("123456789012345678901234567890"),
+            Some(Number::BigInt(_))
+        ));
+        for bad in [
+            "", "-", "1.", ".5", "1e", "1e+", "0x", "0xg", "1.2.3", "inf", "NaN", "+1",
+        ] {
+            assert_eq!(Number::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn json_numbers_round_trip_byte_for_byte() {
+        for text in [
+            "9007199254740993",
+            "-9223372036854775809",
+            "123456789012345678901234567890",
+            "1.50",
+            "1e10",
+            "1E+2",
+            "-0.0",
+            "0.1",
+            "3.141592653589793238462643383279",
+        ] {
+            assert_eq!(round_trip(text), text);
+        }
+    }
+
+    #[test]
+    fn integer_arithmetic_is_exact_and_promotes() {
+        let max = Number::Int(i64::MAX);
+        let sum = max.add(&Number::Int(1));
+        assert!(matches!(sum, Number::BigInt(_)));
+        assert_eq!(sum.to_string(), "9223372036854775808");
+        assert_eq!(sum.sub(&Number::Int(1)), max);
+        assert!(matches!(sum.sub(&Number::Int(1)), Number::Int(_)));
+
+        assert_eq!(Number::Int(6).div(&Number::Int(3)), Ok(Number::Int(2)));
+        assert_eq!(Number::Int(7).div(&Number::Int(2)), Ok(Number::float(3.5)));
+        assert!(Number::Int(1).div(&Number::Int(0)).is_err());
+        assert!(matches!(
+            Number::Int(1).add(&Number::float(0.5)),
+            Number::Float { .. }
+        ));
+    }
+
+    #[test]
+    fn comparison_is_by_value() {
+        assert_eq!(Number::Int(1), Number::float(1.0));
+        assert!(Number::Int(2) > Number::float(1.5));
+        assert!(Number::parse("99999999999999999999").unwrap() > Number::Int(i64::MAX));
+        assert_eq!(Number::float(f64::NAN).partial_cmp(&Number::Int(1)), None);
+    }
+}
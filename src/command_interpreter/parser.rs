@@ -1,63 +1,302 @@
-use crate::command_interpreter::{lexer::Token, types::Expr};
-use regex::Regex;
+use crate::{
+    command_interpreter::{
+        lexer::Token,
+        number::Number,
+        span::{Span, Spanned},
+        types::Expr,
+    },
+    errors::errors::JreplErr,
+};
 
-pub fn parse(tokens: &[Token]) -> Expr {
-    let (expr, _) = parse_helper(tokens, 0);
-    expr
+/// How deeply lists, arrays and objects may nest. The parser and everything that walks its
+/// output recurse once per level, so a limit keeps deeply nested input from overflowing the stack.
+pub const MAX_NESTING: usize = 256;
+
+/// A parsed form that remembers the source range of every node, for diagnostics.
+pub type Ast = Spanned<AstNode>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AstNode {
+    List(Vec<Ast>),
+    /// `[a b c]`, elements still to be evaluated.
+    Array(Vec<Ast>),
+    /// `{"k" v}` or `{"k": v}`, values still to be evaluated.
+    Object(Vec<(String, Ast)>),
+    Atom(Expr),
 }
 
-fn parse_helper(tokens: &[Token], mut i: usize) -> (Expr, usize) {
+impl Ast {
+    pub fn to_expr(&self) -> Expr {
+        match &self.node {
+            AstNode::List(items) => Expr::List(items.iter().map(Ast::to_expr).collect()),
+            AstNode::Array(items) => Expr::array(items.iter().map(Ast::to_expr).collect()),
+            AstNode::Object(entries) => Expr::object(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.to_expr()))
+                    .collect(),
+            ),
+            AstNode::Atom(expr) => expr.clone(),
+        }
+    }
+
+    /// Where `symbol` first appears in the form, if it does.
+    pub fn find_symbol(&self, symbol: &str) -> Option<Span> {
+        match &self.node {
+            AstNode::Atom(Expr::Symbol(s)) if s == symbol => Some(self.span),
+            AstNode::List(items) | AstNode::Array(items) => {
+                items.iter().find_map(|item| item.find_symbol(symbol))
+            }
+            AstNode::Object(entries) => entries
+                .iter()
+                .find_map(|(_, value)| value.find_symbol(symbol)),
+            AstNode::Atom(_) => None,
+        }
+    }
+}
+
+/// Parses tokens that carry no position, e.g. ones built by hand.
+pub fn parse(tokens: &[Token]) -> Result<Expr, JreplErr> {
+    let spanned: Vec<Spanned<Token>> = tokens
+        .iter()
+        .map(|token| Spanned::new(token.clone(), Span::default()))
+        .collect();
+    parse_spanned(&spanned)
+        .map(|ast| ast.to_expr())
+        .map_err(|err| err.node)
+}
+
+/// Parses exactly one form. An input with nothing but comments is `Expr::None`. Stray or
+/// missing closers, malformed literals and anything after the form are errors.
+pub fn parse_spanned(tokens: &[Spanned<Token>]) -> Result<Ast, Spanned<JreplErr>> {
+    let mut forms = parse_forms(tokens)?.into_iter();
+    let Some(first) = forms.next() else {
+        let end = tokens.last().map(|t| t.span.end).unwrap_or_default();
+        return Ok(Spanned::new(AstNode::Atom(Expr::None), Span::new(end, end)));
+    };
+
+    if let Some(second) = forms.next() {
+        let last = forms.last().map(|f| f.span).unwrap_or(second.span);
+        return Err(Spanned::new(
+            JreplErr::ParserErr(
+                "Unexpected form after the end of the first one. Only one form is read here."
+                    .to_string(),
+            ),
+            second.span.to(last),
+        ));
+    }
+    Ok(first)
+}
+
+/// Parses every top-level form, in order. Comments between forms are skipped.
+pub fn parse_forms(tokens: &[Spanned<Token>]) -> Result<Vec<Ast>, Spanned<JreplErr>> {
+    let mut forms = Vec::new();
+    let mut i = skip_comments(tokens, 0);
+    while i < tokens.len() {
+        let (form, next_i) = parse_helper(tokens, i, 0)?;
+        forms.push(form);
+        i = skip_comments(tokens, next_i);
+    }
+    Ok(forms)
+}
+
+fn skip_comments(tokens: &[Spanned<Token>], mut i: usize) -> usize {
+    while matches!(
+        tokens.get(i),
+        Some(Spanned {
+            node: Token::Comment(_),
+            ..
+        })
+    ) {
+        i += 1;
+    }
+    i
+}
+
+fn parse_helper(
+    tokens: &[Spanned<Token>],
+    i: usize,
+    depth: usize,
+) -> Result<(Ast, usize), Spanned<JreplErr>> {
     use Token::*;
 
-    match tokens.get(i) {
-        // Start of a list: consume '(', parse until matching ')'
-        Some(OpenParen(_)) => {
-            i += 1; // skip '('
-            let mut exprs = Vec::new();
-
-            while i < tokens.len() {
-                match tokens.get(i) {
-                    // End of this list
-                    Some(CloseParen(_)) => {
-                        i += 1; // consume ')'
-                        break;
-                    }
-                    // Ignore comments inside lists
-                    Some(Comment(_)) => {
-                        i += 1;
-                    }
-                    // Anything else: parse a sub-expression
-                    _ => {
-                        let (expr, next_i) = parse_helper(tokens, i);
-                        exprs.push(expr);
-                        i = next_i;
-                    }
-                }
+    let Some(Spanned { node: token, span }) = tokens.get(i) else {
+        let end = tokens.last().map(|t| t.span.end).unwrap_or_default();
+        return Err(Spanned::new(
+            JreplErr::ParserErr("Unexpected end of input, expected a value.".to_string()),
+            Span::new(end, end),
+        ));
+    };
+
+    // containers are parsed in functions of their own, so that the frame repeated for every
+    // level of nesting stays small
+    let container = matches!(token, OpenParen(_) | OpenBracket(_) | OpenBrace(_));
+    if container && depth >= MAX_NESTING {
+        return Err(Spanned::new(
+            JreplErr::ParserErr(format!("Forms nested deeper than {}.", MAX_NESTING)),
+            *span,
+        ));
+    }
+
+    match token {
+        OpenParen(_) => parse_list(tokens, i, depth),
+        OpenBracket(_) => parse_array(tokens, i, depth),
+        OpenBrace(_) => parse_object(tokens, i, depth),
+        // Skip comments in atom position and continue parsing the next thing.
+        Comment(_) => parse_helper(tokens, skip_comments(tokens, i), depth),
+        _ => parse_atom(token, *span).map(|atom| (atom, i + 1)),
+    }
+}
+
+// Start of a list: consume '(', parse until matching ')'
+fn parse_list(
+    tokens: &[Spanned<Token>],
+    mut i: usize,
+    depth: usize,
+) -> Result<(Ast, usize), Spanned<JreplErr>> {
+    let span = tokens[i].span;
+    i += 1; // skip '('
+    let mut items = Vec::new();
+
+    loop {
+        match tokens.get(i).map(|t| &t.node) {
+            // End of this list
+            Some(Token::CloseParen(_)) => {
+                let list = Spanned::new(AstNode::List(items), span.to(tokens[i].span));
+                return Ok((list, i + 1)); // consume ')'
+            }
+            // Ignore comments inside lists
+            Some(Token::Comment(_)) => {
+                i += 1;
+            }
+            Some(_) => {
+                let (item, next_i) = parse_helper(tokens, i, depth + 1)?;
+                items.push(item);
+                i = next_i;
+            }
+            None => return Err(unterminated("list", '(', span)),
+        }
+    }
+}
+
+fn parse_array(
+    tokens: &[Spanned<Token>],
+    mut i: usize,
+    depth: usize,
+) -> Result<(Ast, usize), Spanned<JreplErr>> {
+    let span = tokens[i].span;
+    let mut items = Vec::new();
+    i = skip_comments(tokens, i + 1);
+    while !matches!(tokens.get(i).map(|t| &t.node), Some(Token::CloseBracket(_))) {
+        if i >= tokens.len() {
+            return Err(unterminated("array", '[', span));
+        }
+        let (item, next_i) = parse_helper(tokens, i, depth + 1)?;
+        items.push(item);
+        i = skip_comments(tokens, next_i);
+    }
+    let array = Spanned::new(AstNode::Array(items), span.to(tokens[i].span));
+    Ok((array, i + 1))
+}
+
+fn parse_object(
+    tokens: &[Spanned<Token>],
+    mut i: usize,
+    depth: usize,
+) -> Result<(Ast, usize), Spanned<JreplErr>> {
+    let span = tokens[i].span;
+    let mut entries = Vec::new();
+    i = skip_comments(tokens, i + 1);
+    while !matches!(tokens.get(i).map(|t| &t.node), Some(Token::CloseBrace(_))) {
+        if i >= tokens.len() {
+            return Err(unterminated("object", '{', span));
+        }
+        let (key, next_i) = parse_helper(tokens, i, depth + 1)?;
+        let key_name = match key.node {
+            AstNode::Atom(Expr::String(name)) => name,
+            _ => {
+                return Err(Spanned::new(
+                    JreplErr::ParserErr(
+                        "Object keys must be string literals, e.g. {\"name\" 1}.".to_string(),
+                    ),
+                    key.span,
+                ));
             }
+        };
 
-            (Expr::List(exprs), i)
+        // the colon of {"k": v} is optional
+        i = skip_comments(tokens, next_i);
+        if matches!(tokens.get(i).map(|t| &t.node), Some(Token::Colon(_))) {
+            i = skip_comments(tokens, i + 1);
+        }
+        if matches!(
+            tokens.get(i).map(|t| &t.node),
+            None | Some(Token::CloseBrace(_))
+        ) {
+            return Err(Spanned::new(
+                JreplErr::ParserErr(format!("Missing value for key \"{}\".", key_name)),
+                key.span,
+            ));
         }
 
-        // A stray ')' at this position: mirror old behavior by yielding None and consuming it.
-        Some(CloseParen(_)) => (Expr::None, i + 1),
+        let (value, next_i) = parse_helper(tokens, i, depth + 1)?;
+        entries.push((key_name, value));
+        i = skip_comments(tokens, next_i);
+    }
+    let object = Spanned::new(AstNode::Object(entries), span.to(tokens[i].span));
+    Ok((object, i + 1))
+}
 
-        // Skip comments in atom position and continue parsing the next thing.
-        Some(Comment(_)) => parse_helper(tokens, i + 1),
+// Any token that is not a container or a comment.
+fn parse_atom(token: &Token, span: Span) -> Result<Ast, Spanned<JreplErr>> {
+    use Token::*;
+
+    let atom = |expr: Expr| Ok(Spanned::new(AstNode::Atom(expr), span));
+    let invalid = |what: &str, text: &str| {
+        Err(Spanned::new(
+            JreplErr::ParserErr(format!("Invalid {} literal '{}'.", what, text)),
+            span,
+        ))
+    };
+    let unexpected = |msg: &str| Err(Spanned::new(JreplErr::ParserErr(msg.to_string()), span));
+
+    match token {
+        CloseParen(_) => unexpected("Unexpected ')' with no matching '('."),
+        CloseBracket(_) => unexpected("Unexpected ']' with no matching '['."),
+        CloseBrace(_) => unexpected("Unexpected '}' with no matching '{'."),
+        Colon(_) => unexpected("Unexpected ':' outside an object literal."),
 
         // Atom cases
-        Some(StringLiteral(s)) => (Expr::String(s.clone()), i + 1),
-        Some(NumberLiteral(s)) => {
-            let n = s.parse::<f64>().expect("lexer produced invalid number literal");
-            (Expr::Number(n), i + 1)
-        }
-        Some(BoolLiteral(s)) => (Expr::Bool(s == "true"), i + 1),
-        Some(Symbol(s)) => (Expr::Symbol(s.clone()), i + 1),
+        StringLiteral(s) => atom(Expr::String(s.clone())),
+        NumberLiteral(s) => match Number::parse(s) {
+            Some(n) => atom(Expr::Number(n)),
+            None => invalid("number", s),
+        },
+        BoolLiteral(s) => match s.as_str() {
+            "true" => atom(Expr::Bool(true)),
+            "false" => atom(Expr::Bool(false)),
+            _ => invalid("bool", s),
+        },
+        // JSON's null, so pasted documents read as they are
+        Symbol(s) if s == "null" => atom(Expr::None),
+        Symbol(s) => atom(Expr::Symbol(s.clone())),
 
-        // End of input
-        None => (Expr::None, i),
+        OpenParen(_) | OpenBracket(_) | OpenBrace(_) | Comment(_) => {
+            unreachable!("parse_helper handles containers and comments")
+        }
     }
 }
 
+fn unterminated(what: &str, opener: char, span: Span) -> Spanned<JreplErr> {
+    Spanned::new(
+        JreplErr::ParserErr(format!(
+            "Unterminated {}: this '{}' is never closed.",
+            what, opener
+        )),
+        span,
+    )
+}
+
 // pub fn parse(tokens: &[String]) -> Expr {
 //     let (expr, _) = parse_helper(tokens, 0);
 //     expr
@@ -108,11 +347,13 @@ fn parse_helper(tokens: &[Token], mut i: usize) -> (Expr, usize) {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::command_interpreter::types::JsonObject;
+    use proptest::prelude::*;
 
     #[test]
     fn parse_empty_expression() {
         let tokens = vec![Token::OpenParen("(".into()), Token::CloseParen(")".into())];
-        assert_eq!(parse(&tokens), Expr::List(vec![]));
+        assert_eq!(parse(&tokens).unwrap(), Expr::List(vec![]));
     }
 
     #[test]
@@ -123,7 +364,7 @@ mod test {
             Token::CloseParen(")".into()),
         ];
         assert_eq!(
-            parse(&tokens),
+            parse(&tokens).unwrap(),
             Expr::List(vec![Expr::String("i_am_a_string_literal".into())])
         );
     }
@@ -135,7 +376,10 @@ mod test {
             Token::Symbol("help".into()),
             Token::CloseParen(")".into()),
         ];
-        assert_eq!(parse(&tokens), Expr::List(vec![Expr::Symbol("help".into())]));
+        assert_eq!(
+            parse(&tokens).unwrap(),
+            Expr::List(vec![Expr::Symbol("help".into())])
+        );
     }
 
     #[test]
@@ -149,7 +393,7 @@ mod test {
             Token::CloseParen(")".into()),
         ];
         assert_eq!(
-            parse(&tokens),
+            parse(&tokens).unwrap(),
             Expr::List(vec![
                 Expr::Symbol("search".into()),
                 Expr::String("hi".into()),
@@ -177,14 +421,18 @@ mod test {
             Token::CloseParen(")".into()),
         ];
         assert_eq!(
-            parse(&tokens),
+            parse(&tokens).unwrap(),
             Expr::List(vec![
                 Expr::Symbol("+".into()),
-                Expr::Number(1.0),
+                Expr::Number(Number::Int(1)),
                 Expr::List(vec![
                     Expr::Symbol("-".into()),
-                    Expr::Number(10.0),
-                    Expr::List(vec![Expr::Symbol("+".into()), Expr::Number(7.0), Expr::Number(7.0),])
+                    Expr::Number(Number::Int(10)),
+                    Expr::List(vec![
+                        Expr::Symbol("+".into()),
+                        Expr::Number(Number::Int(7)),
+                        Expr::Number(Number::Int(7)),
+                    ])
                 ])
             ])
         );
@@ -200,8 +448,12 @@ mod test {
             Token::CloseParen(")".into()),
         ];
         assert_eq!(
-            parse(&tokens),
-            Expr::List(vec![Expr::Symbol("+".into()), Expr::Number(1.0), Expr::Number(2.0),])
+            parse(&tokens).unwrap(),
+            Expr::List(vec![
+                Expr::Symbol("+".into()),
+                Expr::Number(Number::Int(1)),
+                Expr::Number(Number::Int(2)),
+            ])
         );
     }
 
@@ -212,7 +464,10 @@ mod test {
             Token::StringLiteral("+".into()),
             Token::CloseParen(")".into()),
         ];
-        assert_eq!(parse(&tokens), Expr::List(vec![Expr::String("+".into())]));
+        assert_eq!(
+            parse(&tokens).unwrap(),
+            Expr::List(vec![Expr::String("+".into())])
+        );
     }
 
     #[test]
@@ -222,7 +477,7 @@ mod test {
             Token::BoolLiteral("true".into()),
             Token::CloseParen(")".into()),
         ];
-        assert_eq!(parse(&tokens), Expr::List(vec![Expr::Bool(true)]));
+        assert_eq!(parse(&tokens).unwrap(), Expr::List(vec![Expr::Bool(true)]));
     }
 
     #[test]
@@ -233,7 +488,10 @@ mod test {
             Token::BoolLiteral("false".into()),
             Token::CloseParen(")".into()),
         ];
-        assert_eq!(parse(&tokens), Expr::List(vec![Expr::Bool(true), Expr::Bool(false)]));
+        assert_eq!(
+            parse(&tokens).unwrap(),
+            Expr::List(vec![Expr::Bool(true), Expr::Bool(false)])
+        );
     }
 
     #[test]
@@ -244,19 +502,211 @@ mod test {
             Token::Symbol("a".into()),
             Token::CloseParen(")".into()),
         ];
-        assert_eq!(parse(&tokens), Expr::List(vec![Expr::Symbol("a".into())]));
+        assert_eq!(
+            parse(&tokens).unwrap(),
+            Expr::List(vec![Expr::Symbol("a".into())])
+        );
+    }
+
+    fn parser_err(tokens: &[Token]) -> String {
+        match parse(tokens) {
+            Err(JreplErr::ParserErr(msg)) => msg,
+            other => panic!("expected a ParserErr, got {:?}", other),
+        }
     }
 
     #[test]
-    fn parse_stray_close_paren_yields_none() {
+    fn parse_stray_close_paren_is_err() {
         let tokens = vec![Token::CloseParen(")".into())];
-        assert_eq!(parse(&tokens), Expr::None);
+        assert_eq!(parser_err(&tokens), "Unexpected ')' with no matching '('.");
     }
 
     #[test]
-    #[should_panic] // number parser will panic on invalid numeric literal from lexer
-    fn parse_invalid_number_literal_should_panic() {
+    fn parse_invalid_number_literal_is_err() {
         let tokens = vec![Token::NumberLiteral("3.1.4".into())];
-        let _ = parse(&tokens);
+        assert_eq!(parser_err(&tokens), "Invalid number literal '3.1.4'.");
+    }
+
+    #[test]
+    fn parse_unterminated_list_is_err() {
+        let tokens = vec![Token::OpenParen("(".into()), Token::Symbol("help".into())];
+        assert_eq!(
+            parser_err(&tokens),
+            "Unterminated list: this '(' is never closed."
+        );
+    }
+
+    #[test]
+    fn parse_trailing_tokens_are_err() {
+        let tokens = vec![
+            Token::OpenParen("(".into()),
+            Token::Symbol("help".into()),
+            Token::CloseParen(")".into()),
+            Token::Symbol("extra".into()),
+        ];
+        assert_eq!(
+            parser_err(&tokens),
+            "Unexpected form after the end of the first one. Only one form is read here."
+        );
+    }
+
+    #[test]
+    fn parse_comments_only_is_none() {
+        assert_eq!(parse(&[Token::Comment("`note`".into())]), Ok(Expr::None));
+        assert_eq!(parse(&[]), Ok(Expr::None));
+    }
+
+    #[test]
+    fn parse_error_spans_point_at_offending_token() {
+        let tokens = crate::command_interpreter::lexer::lex("(help) (exit) (help)").unwrap();
+        let err = parse_spanned(&tokens).unwrap_err();
+        assert_eq!((err.span.start.offset, err.span.end.offset), (7, 20));
+    }
+
+    #[test]
+    fn parse_forms_reads_every_form() {
+        let tokens = crate::command_interpreter::lexer::lex("(defn a 1) `note` (+ a 2)").unwrap();
+        let forms: Vec<Expr> = parse_forms(&tokens)
+            .unwrap()
+            .iter()
+            .map(Ast::to_expr)
+            .collect();
+        assert_eq!(
+            forms,
+            vec![
+                Expr::List(vec![
+                    Expr::Symbol("defn".into()),
+                    Expr::Symbol("a".into()),
+                    Expr::Number(Number::Int(1))
+                ]),
+                Expr::List(vec![
+                    Expr::Symbol("+".into()),
+                    Expr::Symbol("a".into()),
+                    Expr::Number(Number::Int(2))
+                ]),
+            ]
+        );
+    }
+
+    fn token() -> impl Strategy<Value = Token> {
+        prop_oneof![
+            Just(Token::OpenParen("(".into())),
+            Just(Token::CloseParen(")".into())),
+            Just(Token::OpenBracket("[".into())),
+            Just(Token::CloseBracket("]".into())),
+            Just(Token::OpenBrace("{".into())),
+            Just(Token::CloseBrace("}".into())),
+            Just(Token::Colon(":".into())),
+            "`[a-z ]{0,4}`".prop_map(Token::Comment),
+            "[a-z+*/-]{1,4}".prop_map(Token::Symbol),
+            ".{0,4}".prop_map(Token::StringLiteral),
+            "[0-9.e+-]{0,5}".prop_map(Token::NumberLiteral),
+            "true|false|[a-z]{1,3}".prop_map(Token::BoolLiteral),
+        ]
+    }
+
+    proptest! {
+        // Whatever the lexer hands over, parsing returns instead of panicking.
+        #[test]
+        fn parse_never_panics(tokens in proptest::collection::vec(token(), 0..40)) {
+            let _ = parse(&tokens);
+        }
+
+        // A balanced stream of atoms and lists always parses.
+        #[test]
+        fn parse_accepts_balanced_lists(depth in 1usize..20, atoms in proptest::collection::vec("[a-z]{1,5}", 0..10)) {
+            let mut tokens: Vec<Token> = (0..depth).map(|_| Token::OpenParen("(".into())).collect();
+            tokens.extend(atoms.into_iter().map(Token::Symbol));
+            tokens.extend((0..depth).map(|_| Token::CloseParen(")".into())));
+            prop_assert!(matches!(parse(&tokens), Ok(Expr::List(_))));
+        }
+    }
+
+    #[test]
+    fn parse_rejects_forms_nested_too_deeply() {
+        let nested = |depth: usize, open: &str, close: &str| {
+            let source = format!("{}1{}", open.repeat(depth), close.repeat(depth));
+            parse_spanned(&crate::command_interpreter::lexer::lex(&source).unwrap())
+        };
+
+        for (open, close) in [("(", ")"), ("[", "]"), ("(list [", "])")] {
+            let err = nested(100_000, open, close).unwrap_err();
+            assert_eq!(
+                err.node,
+                JreplErr::ParserErr(format!("Forms nested deeper than {}.", MAX_NESTING))
+            );
+            assert!(err.span.start.offset > 0 && err.span.start.offset <= MAX_NESTING * open.len());
+        }
+        let err = nested(MAX_NESTING + 1, "(", ")").unwrap_err();
+        assert_eq!(
+            (err.span.start.offset, err.span.end.offset),
+            (MAX_NESTING, MAX_NESTING + 1)
+        );
+        assert!(nested(MAX_NESTING, "(", ")").is_ok());
+    }
+
+    #[test]
+    fn parse_spanned_keeps_node_ranges() {
+        let tokens = crate::command_interpreter::lexer::lex("(+ 1 (* x 3))").unwrap();
+        let ast = parse_spanned(&tokens).unwrap();
+        assert_eq!((ast.span.start.offset, ast.span.end.offset), (0, 13));
+
+        let x = ast.find_symbol("x").unwrap();
+        assert_eq!((x.start.offset, x.end.offset), (8, 9));
+        match &ast.node {
+            AstNode::List(items) => assert_eq!(
+                (items[2].span.start.offset, items[2].span.end.offset),
+                (5, 12)
+            ),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(ast.find_symbol("y"), None);
+    }
+
+    fn parse_source(source: &str) -> Result<Expr, JreplErr> {
+        let tokens = crate::command_interpreter::lexer::lexer(source)?;
+        parse(&tokens)
+    }
+
+    #[test]
+    fn parse_array_and_object_literals() {
+        let expr = parse_source(r#"{"name": "Aaon", "tags": [1 x], "none": null}"#).unwrap();
+        let mut expected = JsonObject::new();
+        expected.insert("name".to_string(), Expr::String("Aaon".into()));
+        expected.insert(
+            "tags".to_string(),
+            Expr::array(vec![Expr::Number(Number::Int(1)), Expr::Symbol("x".into())]),
+        );
+        expected.insert("none".to_string(), Expr::None);
+        assert_eq!(expr, Expr::object(expected));
+
+        // colons and commas are optional
+        assert_eq!(
+            parse_source(r#"{"a" 1 "b" 2}"#).unwrap(),
+            parse_source(r#"{"a": 1, "b": 2}"#).unwrap()
+        );
+        assert_eq!(parse_source("[]").unwrap(), Expr::array(vec![]));
+    }
+
+    #[test]
+    fn parse_object_literal_errors() {
+        let err = |source: &str| match parse_source(source) {
+            Err(JreplErr::ParserErr(msg)) => msg,
+            other => panic!("expected a ParserErr, got {:?}", other),
+        };
+        assert_eq!(err(r#"{"a" 1 "b"}"#), "Missing value for key \"b\".");
+        assert_eq!(err(r#"{"a":}"#), "Missing value for key \"a\".");
+        assert_eq!(
+            err("{1 2}"),
+            "Object keys must be string literals, e.g. {\"name\" 1}."
+        );
+        assert_eq!(
+            parser_err(&[Token::OpenBracket("[".into())]),
+            "Unterminated array: this '[' is never closed."
+        );
+        assert_eq!(
+            parser_err(&[Token::Colon(":".into())]),
+            "Unexpected ':' outside an object literal."
+        );
     }
 }
@@ -0,0 +1,192 @@
+/*
+    Source positions and caret diagnostics.
+
+    Offsets are in bytes, lines and columns count from 1 and columns count chars, so a span can
+    both slice the input and line up a caret under it.
+*/
+
+use crate::errors::errors::JreplErr;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn start() -> Position {
+        Position {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// The position just after `c`, when `c` is at this position.
+    pub fn advance(self, c: char) -> Position {
+        if c == '\n' {
+            Position {
+                offset: self.offset + c.len_utf8(),
+                line: self.line + 1,
+                column: 1,
+            }
+        } else {
+            Position {
+                offset: self.offset + c.len_utf8(),
+                line: self.line,
+                column: self.column + 1,
+            }
+        }
+    }
+}
+
+/// A half-open range of the input, `start` included and `end` excluded.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+
+    /// The smallest span covering both.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Spanned<T> {
+        Spanned { node, span }
+    }
+}
+
+/// Renders `err` in the style of rustc: the message, the location, each source line the span
+/// touches with the range underlined, then the hint if there is one.
+///
+/// ```text
+/// syntax error[E0105]: Found Invalid char in user_input while tokenizing a Number. Char: 'x'.
+///   --> 1:6
+///   |
+/// 1 | (+ 12x 3)
+///   |      ^
+///   = hint: Symbols start with a letter or '_' and may contain letters, digits, '_', '-' and '!'. Keyword options start with ':', e.g. :force.
+/// ```
+pub fn render_diagnostic(source: &str, span: Span, err: &JreplErr) -> String {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let last_line = span.end.line.max(span.start.line).min(lines.len());
+    let gutter = last_line.to_string().len();
+    let pad = " ".repeat(gutter);
+
+    let mut out = format!(
+        "{} error[{}]: {}\n{} --> {}:{}\n{} |\n",
+        err.category(),
+        err.code(),
+        err.message(),
+        pad,
+        span.start.line,
+        span.start.column,
+        pad
+    );
+
+    for line_no in span.start.line..=last_line {
+        let text = lines[line_no - 1].trim_end_matches('\r');
+        let width = text.chars().count();
+        let indent = text.chars().take_while(|c| c.is_whitespace()).count();
+        let from = if line_no == span.start.line {
+            span.start.column
+        } else {
+            indent + 1
+        };
+        let to = if line_no == span.end.line {
+            span.end.column
+        } else {
+            width + 1
+        };
+        // an empty span, e.g. at the end of the input, still gets one caret
+        let marks = to.saturating_sub(from).max(1);
+
+        out.push_str(&format!(
+            "{:>gutter$} | {}\n",
+            line_no,
+            text,
+            gutter = gutter
+        ));
+        out.push_str(&format!(
+            "{} | {}{}\n",
+            pad,
+            " ".repeat(from - 1),
+            "^".repeat(marks)
+        ));
+    }
+
+    if let Some(hint) = err.hint() {
+        out.push_str(&format!("{} = hint: {}\n", pad, hint));
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn span_of(source: &str, from: usize, to: usize) -> Span {
+        let mut pos = Position::start();
+        let mut start = pos;
+        for (i, c) in source.chars().enumerate() {
+            if i == from {
+                start = pos;
+            }
+            if i == to {
+                break;
+            }
+            pos = pos.advance(c);
+        }
+        Span::new(start, pos)
+    }
+
+    #[test]
+    fn position_tracks_lines_and_multibyte_chars() {
+        let end = "ab\ncé".chars().fold(Position::start(), Position::advance);
+        assert_eq!(
+            end,
+            Position {
+                offset: 6,
+                line: 2,
+                column: 3
+            }
+        );
+    }
+
+    #[test]
+    fn render_underlines_range() {
+        let source = "(+ 12x 3)";
+        let err = JreplErr::InvalidSyntax("bad number".to_string());
+        assert_eq!(
+            render_diagnostic(source, span_of(source, 3, 6), &err),
+            "syntax error[E0106]: bad number\n  --> 1:4\n  |\n1 | (+ 12x 3)\n  |    ^^^\n"
+        );
+    }
+
+    #[test]
+    fn render_spans_several_lines() {
+        let source = "(defn x\n  (+ 1\n     2)";
+        let err = JreplErr::MissingKey("nope".to_string());
+        assert_eq!(
+            render_diagnostic(source, span_of(source, 10, 22), &err),
+            "data error[E0304]: nope\n  --> 2:3\n  |\n\
+             2 |   (+ 1\n  |   ^^^^\n\
+             3 |      2)\n  |      ^^\n"
+        );
+    }
+}
@@ -1,6 +1,12 @@
-use std::rc::Rc;
+use std::{collections::HashMap, fmt, rc::Rc};
 
-use crate::{appstate::State, command_interpreter::command::Command, errors::errors::JreplErr};
+use indexmap::IndexMap;
+
+use crate::{
+    appstate::State,
+    command_interpreter::{command::Command, number::Number, span::Span},
+    errors::errors::JreplErr,
+};
 
 #[derive(Clone)]
 pub enum Referent {
@@ -12,40 +18,128 @@ pub enum Referent {
 pub enum Expr {
     // ------------ Literals/Terminals ----------
     String(String),
-    Number(f64),
+    Number(Number),
     Bool(bool),
     None,
     // Operator(String),
     // Command(String),
     File(FileValue),
     // State(AppState)
+    // ---------------- JSON Values ---------------
+    Array(Rc<Vec<Expr>>),
+    Object(Rc<JsonObject>),
+    // ----------------- Functions ----------------
+    Lambda(Rc<Lambda>),
     // --------------- Expandables ---------------
     Symbol(String),
     List(Vec<Expr>), // e.g. (add 1 2)
 }
 
+/// Keys keep the order they were read or inserted in, like the source document.
+pub type JsonObject = IndexMap<String, Expr>;
+
+/// A user function: `(fn (x y) body...)`, or `(defn name (x y) body...)`.
+pub struct Lambda {
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: Vec<Expr>,
+    /// The local bindings in effect where the function was defined.
+    pub scope: Option<Rc<Scope>>,
+}
+
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<fn {}({})>",
+            self.name
+                .as_deref()
+                .map(|n| format!("{} ", n))
+                .unwrap_or_default(),
+            self.params.join(" ")
+        )
+    }
+}
+
+// Functions are equal only to themselves.
+impl PartialEq for Lambda {
+    fn eq(&self, other: &Lambda) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// One frame of local bindings, e.g. a function call's arguments. Lookups that miss walk up to
+/// the parent frame and finally to the global symbol table.
+#[derive(Debug)]
+pub struct Scope {
+    pub bindings: HashMap<String, Expr>,
+    pub parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    pub fn lookup(&self, symbol: &str) -> Option<&Expr> {
+        match self.bindings.get(symbol) {
+            Some(expr) => Some(expr),
+            None => self
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.lookup(symbol)),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct FileValue {
     /// Original path or identifier (useful for debugging / provenance).
     pub path: String,
-    /// Raw bytes of the file (works for both text and binary).
-    pub bytes: Vec<u8>,
+    /// Raw bytes of the file (works for both text and binary), shared between copies.
+    pub bytes: Rc<[u8]>,
     /// Optional MIME type if you detect/attach one (e.g., "text/plain", "application/pdf").
     pub mime: Option<String>,
 }
 
 impl Expr {
     pub fn is_terminal(&self) -> bool {
-        matches!(self, Expr::String(_) | Expr::Number(_) | Expr::Bool(_) | Expr::None)
+        matches!(
+            self,
+            Expr::String(_) | Expr::Number(_) | Expr::Bool(_) | Expr::None
+        )
     }
 
     pub fn is_literal(&self) -> bool {
         match self {
-            Expr::String(_) | Expr::Number(_) | Expr::Bool(_) | Expr::None | Expr::File(_) => true,
+            Expr::String(_)
+            | Expr::Number(_)
+            | Expr::Bool(_)
+            | Expr::None
+            | Expr::File(_)
+            | Expr::Lambda(_) => true,
             Expr::Symbol(_) => false,
             Expr::List(xs) => xs.iter().all(|e| e.is_literal()),
+            Expr::Array(xs) => xs.iter().all(|e| e.is_literal()),
+            Expr::Object(map) => map.values().all(|e| e.is_literal()),
         }
     }
+
+    pub fn is_json(&self) -> bool {
+        matches!(
+            self,
+            Expr::String(_)
+                | Expr::Number(_)
+                | Expr::Bool(_)
+                | Expr::None
+                | Expr::Array(_)
+                | Expr::Object(_)
+        )
+    }
+
+    pub fn array(items: Vec<Expr>) -> Expr {
+        Expr::Array(Rc::new(items))
+    }
+
+    pub fn object(map: JsonObject) -> Expr {
+        Expr::Object(Rc::new(map))
+    }
 }
 
 // #[derive(Debug, PartialEq)]
@@ -54,7 +148,8 @@ pub struct Effect {
     pub next_state: Option<State>,
     pub user_feedback: Option<String>, // If a file is bound to a symbol with (load <file_path>) it might not have an eval_value, but the user should get feedback.
     pub err: Option<JreplErr>,
-    //     pub side_effect: Option<SideEffect>,   // IO, print, etc.
+    pub err_span: Option<Span>, // Where in the input `err` was raised, when known.
+                                //     pub side_effect: Option<SideEffect>,   // IO, print, etc.
 }
 
 impl Effect {
@@ -64,6 +159,7 @@ impl Effect {
             next_state: None,
             user_feedback: None,
             err: None,
+            err_span: None,
         }
     }
 
@@ -73,6 +169,7 @@ impl Effect {
             next_state: None,
             user_feedback: None,
             err: Some(err),
+            err_span: None,
         }
     }
 }
@@ -80,8 +177,3 @@ impl Effect {
 // impl Effect {
 //     pub fn exe(&self) {}
 // }
-
-#[derive(Debug, PartialEq)]
-pub struct AST {
-    pub expr: Expr,
-}
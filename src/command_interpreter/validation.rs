@@ -49,7 +49,7 @@ impl Accumulator {
         if self.context_stack.is_empty() {
             return Context::Init;
         }
-        self.context_stack.get(self.context_stack.len() - 1).unwrap().clone()
+        self.context_stack.last().unwrap().clone()
     }
 }
 
@@ -61,11 +61,12 @@ fn transition_table(mut acc: Accumulator, token: &str) -> Result<Accumulator, Jr
                 acc.context_stack.push(Context::List);
                 acc.token_history.push(token.to_string());
                 acc.delimiter_stack.push(token.to_string());
-                return Ok(acc);
+                Ok(acc)
             }
 
             _ => Err(JreplErr::InvalidSyntax(
-                "Context::Init - found token at start of expr which isnt a '(', token: {symbol}".to_string(),
+                "Context::Init - found token at start of expr which isnt a '(', token: {symbol}"
+                    .to_string(),
             )),
         },
         // For the moment assume its a symbol if its not a Delimiter, a literal would be acceptable and not a symbol and not a Deliminiter.
@@ -75,20 +76,20 @@ fn transition_table(mut acc: Accumulator, token: &str) -> Result<Accumulator, Jr
                 acc.context_stack.push(Context::List);
                 acc.token_history.push(token.to_string());
                 acc.delimiter_stack.push(token.to_string());
-                return Ok(acc);
+                Ok(acc)
             }
 
             ")" => {
                 acc.token_history.push(token.to_string());
                 acc.context_stack.pop();
                 acc.delimiter_stack.pop();
-                return Ok(acc);
+                Ok(acc)
             }
 
             // this needs to exclude comments
             symbol if !Delimiter::is(token) => {
                 acc.token_history.push(symbol.to_string());
-                return Ok(acc);
+                Ok(acc)
             }
 
             // comment if isComment(token) => {
@@ -97,30 +98,29 @@ fn transition_table(mut acc: Accumulator, token: &str) -> Result<Accumulator, Jr
             //     return Ok(acc);
             // }
             _ => Err(JreplErr::InvalidSymbol(
-                "Context::List - found token in list which isnt a '(', ')' or a symbol: {token}".to_string(),
+                "Context::List - found token in list which isnt a '(', ')' or a symbol: {token}"
+                    .to_string(),
             )),
         },
-
-        _ => Ok(acc),
     }
 }
 
 pub fn verify_syntax(tokens: &[String]) -> Result<(), JreplErr> {
-    let acc = tokens
-        .iter()
-        .try_fold(Accumulator::new(), |acc, token| transition_table(acc, token));
+    let acc = tokens.iter().try_fold(Accumulator::new(), |acc, token| {
+        transition_table(acc, token)
+    });
 
     if let Err(err) = acc {
         return Err(err);
     }
 
-    if let Ok(acc) = acc {
-        if !acc.delimiter_stack.is_empty() {
-            println!("acc.delimiter_stack: {:?}", acc.delimiter_stack);
-            return Err(JreplErr::InvalidSyntax(
-                "Unbalanced delimiter somewhere in user input".to_string(),
-            ));
-        }
+    if let Ok(acc) = acc
+        && !acc.delimiter_stack.is_empty()
+    {
+        println!("acc.delimiter_stack: {:?}", acc.delimiter_stack);
+        return Err(JreplErr::InvalidSyntax(
+            "Unbalanced delimiter somewhere in user input".to_string(),
+        ));
     }
 
     Ok(())
@@ -132,19 +132,19 @@ mod test {
 
     #[test]
     fn verify_exit_command() {
-        let result = verify_syntax(&vec!["(".to_string(), "exit".to_string(), ")".to_string()]);
+        let result = verify_syntax(&["(".to_string(), "exit".to_string(), ")".to_string()]);
         assert_eq!(Ok(()), result);
     }
 
     #[test]
     fn verify_empty_expr() {
-        let result = verify_syntax(&vec!["(".to_string(), ")".to_string()]);
+        let result = verify_syntax(&["(".to_string(), ")".to_string()]);
         assert_eq!(Ok(()), result);
     }
 
     #[test]
     fn verify_with_string_literal() {
-        let result = verify_syntax(&vec![
+        let result = verify_syntax(&[
             "(".to_string(),
             "search".to_string(),
             "\"symbol_name\"".to_string(),
@@ -155,7 +155,7 @@ mod test {
 
     #[test]
     fn verify_nested_expr() {
-        let result = verify_syntax(&vec![
+        let result = verify_syntax(&[
             "(".to_string(),
             "outer".to_string(),
             "(".to_string(),
@@ -170,19 +170,19 @@ mod test {
 
     #[test]
     fn verify_unbalanced_open() {
-        let result = verify_syntax(&vec!["(".to_string(), "unbalanced".to_string()]);
+        let result = verify_syntax(&["(".to_string(), "unbalanced".to_string()]);
         assert!(result.is_err());
     }
 
     #[test]
     fn verify_unbalanced_close() {
-        let result = verify_syntax(&vec!["unbalanced".to_string(), ")".to_string()]);
+        let result = verify_syntax(&["unbalanced".to_string(), ")".to_string()]);
         assert!(result.is_err());
     }
 
     #[test]
     fn verify_extra_close() {
-        let result = verify_syntax(&vec![
+        let result = verify_syntax(&[
             "(".to_string(),
             "foo".to_string(),
             ")".to_string(),
@@ -193,7 +193,7 @@ mod test {
 
     #[test]
     fn verify_unbalanced_nested() {
-        let result = verify_syntax(&vec![
+        let result = verify_syntax(&[
             "(".to_string(),
             "outer".to_string(),
             "(".to_string(),
@@ -206,7 +206,7 @@ mod test {
 
     #[test]
     fn verify_invalid_symbol() {
-        let result = verify_syntax(&vec!["$not_a_symbol".to_string()]);
+        let result = verify_syntax(&["$not_a_symbol".to_string()]);
         assert!(result.is_err());
     }
 
@@ -214,7 +214,7 @@ mod test {
 
     #[test]
     fn verify_backtick_comment_inside_expr() {
-        let result = verify_syntax(&vec![
+        let result = verify_syntax(&[
             "(".to_string(),
             "foo".to_string(),
             "`this is a comment`".to_string(),
@@ -226,7 +226,7 @@ mod test {
 
     #[test]
     fn verify_backtick_multiline_comment_inside_expr() {
-        let result = verify_syntax(&vec![
+        let result = verify_syntax(&[
             "(".to_string(),
             "foo".to_string(),
             "`this is\na multiline\ncomment`".to_string(),
@@ -238,13 +238,17 @@ mod test {
 
     #[test]
     fn verify_comment_only_expr() {
-        let result = verify_syntax(&vec!["(".to_string(), "`just a comment`".to_string(), ")".to_string()]);
+        let result = verify_syntax(&[
+            "(".to_string(),
+            "`just a comment`".to_string(),
+            ")".to_string(),
+        ]);
         assert_eq!(Ok(()), result);
     }
 
     #[test]
     fn verify_comment_at_start_middle_end() {
-        let result = verify_syntax(&vec![
+        let result = verify_syntax(&[
             "(".to_string(),
             "`start`".to_string(),
             "foo".to_string(),
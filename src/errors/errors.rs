@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Debug, PartialEq)]
 pub enum JreplErr {
     // -------------------- Interpreter Errors --------------------//
@@ -9,7 +11,327 @@ pub enum JreplErr {
     InvalidSyntax(String),
     ParserErr(String),
     EvalErr(String),
-    UndefinedSymbol(String),
+    /// `name` is the symbol that failed to resolve, when there is one, so it can be pointed at.
+    UndefinedSymbol {
+        name: Option<String>,
+        msg: String,
+    },
     ArithmeticErr(String),
     OperatorFormatErr(String),
+    TypeErr(String),
+    // ----------------------- Data Errors ------------------------//
+    JsonErr(String),
+    IoErr(String),
+    WriteErr(String),
+    /// Saving without :force to a file that already exists.
+    FileExists(String),
+    InvalidPath(String),
+    MissingKey(String),
+    IndexOutOfBounds(String),
+    PathTypeErr(String),
+    /// A JSON Patch operation failed; carries the index of the operation in the patch.
+    PatchErr(usize, String),
+}
+
+/// What is known about an error kind regardless of where it was raised. Codes are stable: they
+/// may be linked to from documentation, so never renumber or reuse one.
+#[derive(Debug, PartialEq)]
+pub struct ErrorInfo {
+    pub code: &'static str,
+    pub category: &'static str,
+    pub title: &'static str,
+    pub hint: Option<&'static str>,
+    pub explanation: &'static str,
+}
+
+pub const ERROR_INFO: &[ErrorInfo] = &[
+    // E01xx: reading the input
+    ErrorInfo {
+        code: "E0101",
+        category: "syntax",
+        title: "unbalanced delimiter",
+        hint: Some("Every '(', '[' and '{' needs a matching ')', ']' or '}'."),
+        explanation: "The input has more opening than closing delimiters, a closer with nothing to \
+            close, or a closer of the wrong kind such as (+ [1 2). Count the delimiters of each \
+            form; an unterminated string or comment can also swallow a closer.",
+    },
+    ErrorInfo {
+        code: "E0102",
+        category: "syntax",
+        title: "cannot tokenize input",
+        hint: None,
+        explanation: "The lexer met a character it cannot start or continue a token with. Symbols \
+            start with a letter or '_', strings are wrapped in double quotes and comments in \
+            backticks.",
+    },
+    ErrorInfo {
+        code: "E0103",
+        category: "syntax",
+        title: "malformed token",
+        hint: Some("Check number literals and string escapes."),
+        explanation: "A token started correctly but its contents are invalid, for example a number \
+            with two decimal points or a string with an escape JSON does not have. Strings take \
+            \\\" \\\\ \\/ \\b \\f \\n \\r \\t and \\uXXXX.",
+    },
+    ErrorInfo {
+        code: "E0104",
+        category: "syntax",
+        title: "syntax check failed",
+        hint: None,
+        explanation: "The token stream did not pass the syntax check that runs before parsing.",
+    },
+    ErrorInfo {
+        code: "E0105",
+        category: "syntax",
+        title: "invalid symbol",
+        hint: Some(
+            "Symbols start with a letter or '_' and may contain letters, digits, '_', '-' and '!'. Keyword options start with ':', e.g. :force.",
+        ),
+        explanation: "A name was used where it is not allowed, either because it contains \
+            characters a symbol cannot hold or because it is already taken by a command or \
+            another binding.",
+    },
+    ErrorInfo {
+        code: "E0106",
+        category: "syntax",
+        title: "invalid syntax",
+        hint: None,
+        explanation: "The input is made of valid tokens that do not form a valid expression.",
+    },
+    ErrorInfo {
+        code: "E0107",
+        category: "syntax",
+        title: "cannot parse",
+        hint: Some("Wrap each command in parentheses, e.g. (help)."),
+        explanation: "The parser could not build an expression from the tokens, for example \
+            because of a stray closing parenthesis or tokens left over after a complete form.",
+    },
+    // E02xx: evaluating
+    ErrorInfo {
+        code: "E0201",
+        category: "eval",
+        title: "evaluation failed",
+        hint: None,
+        explanation: "An expression was well formed but could not be evaluated in the current \
+            state, for example undo with nothing left to undo.",
+    },
+    ErrorInfo {
+        code: "E0202",
+        category: "eval",
+        title: "undefined symbol",
+        hint: Some("Bind a value with (defn <name> <expr>), or run (help) to list the commands."),
+        explanation: "A symbol has no binding in the local scope or the symbol table.",
+    },
+    ErrorInfo {
+        code: "E0203",
+        category: "eval",
+        title: "arithmetic error",
+        hint: None,
+        explanation: "An arithmetic command could not produce a number, for example a division by \
+            zero.",
+    },
+    ErrorInfo {
+        code: "E0204",
+        category: "eval",
+        title: "wrong arguments",
+        hint: Some("Run (help) to see the usage of every command."),
+        explanation: "A command or function was called with the wrong number or kind of \
+            arguments, or something that is not a function was called.",
+    },
+    ErrorInfo {
+        code: "E0205",
+        category: "eval",
+        title: "type mismatch",
+        hint: None,
+        explanation: "A value of the wrong type was given, for example a string where a number \
+            was expected, a condition that is not a Bool, or two values that cannot be ordered \
+            against each other.",
+    },
+    // E03xx: data
+    ErrorInfo {
+        code: "E0301",
+        category: "data",
+        title: "invalid JSON",
+        hint: None,
+        explanation: "A file or string could not be parsed as JSON, or a value could not be \
+            represented as JSON. The message includes the parser's line and column.",
+    },
+    ErrorInfo {
+        code: "E0302",
+        category: "io",
+        title: "I/O error",
+        hint: Some("Check that the path exists and is readable."),
+        explanation: "A file could not be opened or read. Paths are relative to the directory \
+            jrepl was started in.",
+    },
+    ErrorInfo {
+        code: "E0303",
+        category: "data",
+        title: "invalid path",
+        hint: Some(
+            "Paths are JSON Pointers (/a/0), dotted paths (a[0].b) or JSONPath queries ($.a[*]).",
+        ),
+        explanation: "A path or query string could not be parsed, or asks for something \
+            impossible such as removing the root of a document.",
+    },
+    ErrorInfo {
+        code: "E0304",
+        category: "data",
+        title: "missing key",
+        hint: None,
+        explanation: "A path names an object key that is not there. The message gives the \
+            location of the object that was searched.",
+    },
+    ErrorInfo {
+        code: "E0305",
+        category: "data",
+        title: "index out of bounds",
+        hint: None,
+        explanation: "A path indexes past the end of an array. Array indices start at 0.",
+    },
+    ErrorInfo {
+        code: "E0306",
+        category: "data",
+        title: "path through a non-container",
+        hint: None,
+        explanation: "A path tries to step into a value that is not an object or array, or uses \
+            a key on an array.",
+    },
+    ErrorInfo {
+        code: "E0307",
+        category: "data",
+        title: "patch operation failed",
+        hint: Some("Operations apply in order, each to the result of the ones before it."),
+        explanation: "A JSON Patch operation could not be applied, so the whole patch was \
+            rejected and the document is unchanged. The message gives the index of the failing \
+            operation: a 'test' that did not match, a path that does not exist (only 'add' may \
+            name a missing last key, or '-' to append to an array), or a malformed operation.",
+    },
+    ErrorInfo {
+        code: "E0308",
+        category: "io",
+        title: "cannot write file",
+        hint: Some("Check that the directory exists and is writable."),
+        explanation: "A file could not be written. Saves go to a temporary file next to the \
+            target first, so the directory must allow creating files; the target is left as it \
+            was.",
+    },
+    ErrorInfo {
+        code: "E0309",
+        category: "io",
+        title: "file already exists",
+        hint: Some("Add :force to overwrite it, e.g. (save doc \"out.json\" :force)."),
+        explanation: "save does not replace an existing file unless asked to with :force. This \
+            includes saving a value back to the file it was loaded from.",
+    },
+];
+
+impl JreplErr {
+    fn parts(&self) -> (&'static str, &str) {
+        match self {
+            JreplErr::UnbalancedDelimiter(m) => ("E0101", m),
+            JreplErr::LexificationErr(m) => ("E0102", m),
+            JreplErr::LexErr(m) => ("E0103", m),
+            JreplErr::VerifySyntaxErr(m) => ("E0104", m),
+            JreplErr::InvalidSymbol(m) => ("E0105", m),
+            JreplErr::InvalidSyntax(m) => ("E0106", m),
+            JreplErr::ParserErr(m) => ("E0107", m),
+            JreplErr::EvalErr(m) => ("E0201", m),
+            JreplErr::UndefinedSymbol { msg, .. } => ("E0202", msg),
+            JreplErr::ArithmeticErr(m) => ("E0203", m),
+            JreplErr::OperatorFormatErr(m) => ("E0204", m),
+            JreplErr::TypeErr(m) => ("E0205", m),
+            JreplErr::JsonErr(m) => ("E0301", m),
+            JreplErr::IoErr(m) => ("E0302", m),
+            JreplErr::InvalidPath(m) => ("E0303", m),
+            JreplErr::MissingKey(m) => ("E0304", m),
+            JreplErr::IndexOutOfBounds(m) => ("E0305", m),
+            JreplErr::PathTypeErr(m) => ("E0306", m),
+            JreplErr::PatchErr(_, m) => ("E0307", m),
+            JreplErr::WriteErr(m) => ("E0308", m),
+            JreplErr::FileExists(m) => ("E0309", m),
+        }
+    }
+
+    pub fn info(&self) -> &'static ErrorInfo {
+        explain(self.parts().0).expect("every variant has an entry in ERROR_INFO")
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.info().code
+    }
+
+    pub fn category(&self) -> &'static str {
+        self.info().category
+    }
+
+    pub fn hint(&self) -> Option<&'static str> {
+        self.info().hint
+    }
+
+    pub fn message(&self) -> &str {
+        self.parts().1
+    }
+}
+
+/// The entry for `code`, e.g. "E0204". Case-insensitive.
+pub fn explain(code: &str) -> Option<&'static ErrorInfo> {
+    ERROR_INFO
+        .iter()
+        .find(|info| info.code.eq_ignore_ascii_case(code))
+}
+
+impl fmt::Display for JreplErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} error[{}]: {}",
+            self.category(),
+            self.code(),
+            self.message()
+        )?;
+        if let Some(hint) = self.hint() {
+            write!(f, "\n  = hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JreplErr {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_shows_code_message_and_hint() {
+        let err = JreplErr::OperatorFormatErr("'get' expects at least 2 arguments".to_string());
+        assert_eq!(
+            err.to_string(),
+            "eval error[E0204]: 'get' expects at least 2 arguments\n  = hint: Run (help) to see the usage of every command."
+        );
+        assert_eq!(
+            JreplErr::MissingKey("No key 'x' in the object at /a.".to_string()).to_string(),
+            "data error[E0304]: No key 'x' in the object at /a."
+        );
+        assert_eq!(
+            JreplErr::TypeErr("Type error: expected Number, got Bool(true)".to_string())
+                .to_string(),
+            "eval error[E0205]: Type error: expected Number, got Bool(true)"
+        );
+        assert_eq!(
+            JreplErr::FileExists("'out.json' already exists".to_string()).hint(),
+            Some("Add :force to overwrite it, e.g. (save doc \"out.json\" :force).")
+        );
+    }
+
+    #[test]
+    fn codes_are_unique_and_explainable() {
+        let mut codes: Vec<&str> = ERROR_INFO.iter().map(|info| info.code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), ERROR_INFO.len());
+        assert_eq!(explain("e0302").map(|info| info.title), Some("I/O error"));
+        assert_eq!(explain("E9999"), None);
+    }
 }
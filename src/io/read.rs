@@ -1,30 +1,206 @@
-use serde_json::Value;
+use std::collections::HashSet;
 use std::fs::File;
-use std::io::BufReader;
-use std::process;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{command_interpreter::types::Expr, errors::errors::JreplErr};
+
+/// A JSON file given on the command line, parsed and ready to be bound to `symbol`.
+#[derive(Debug)]
+pub struct JsonInput {
+    pub symbol: String,
+    pub path: String,
+    pub value: Expr,
+}
+
+/// Parses every `path` or `name=path` argument. Files that fail to open or parse are reported
+/// as errors in place, the rest still load. Names derived from file stems never shadow `reserved`
+/// symbols or each other, explicit names must be free.
+pub fn extract_json(file_args: &[String], reserved: &[String]) -> Vec<Result<JsonInput, JreplErr>> {
+    let mut taken: HashSet<String> = reserved.iter().cloned().collect();
 
-pub fn extract_json(file_paths: &[String]) -> Vec<Value> {
-    file_paths
+    file_args
         .iter()
-        .map(|file_path| {
-            let file = File::open(file_path).unwrap_or_else(|err| {
-                eprintln!("Error opening file: '{}': {}", file_path, err);
-                process::exit(1);
-            });
-
-            let rdr = BufReader::new(file);
-            serde_json::from_reader(rdr).unwrap_or_else(|err| {
-                eprintln!("Failed to parse as JSON '{}': {}", file_path, err);
-                process::exit(1);
+        .map(|arg| {
+            let (explicit_name, file_path) = split_binding(arg);
+
+            let symbol = match explicit_name {
+                Some(name) if taken.contains(name) => {
+                    return Err(JreplErr::InvalidSymbol(format!(
+                        "Cannot bind '{}' to '{}': the symbol is already in use.",
+                        file_path, name
+                    )));
+                }
+                Some(name) => name.to_string(),
+                None => unique_symbol(&symbol_from_stem(file_path), &taken),
+            };
+
+            let value = read_json_file(file_path)?;
+            taken.insert(symbol.clone());
+
+            Ok(JsonInput {
+                symbol,
+                path: file_path.to_string(),
+                value,
             })
         })
         .collect()
 }
 
-pub fn user_input() -> String {
-    let mut input = String::new();
-    std::io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read line");
-    input.trim().to_owned()
+/// Reads all of `reader` as one JSON document, e.g. stdin in a pipeline. Input that is empty or
+/// only whitespace is `None`. `name` identifies the source in error messages.
+pub fn read_json_from<R: Read>(mut reader: R, name: &str) -> Result<Option<Expr>, JreplErr> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|err| JreplErr::IoErr(format!("Error reading {}: {}", name, err)))?;
+
+    if bytes.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    let value: Value = serde_json::from_slice(&bytes)
+        .map_err(|err| JreplErr::JsonErr(format!("Failed to parse {} as JSON: {}", name, err)))?;
+    Ok(Some(Expr::from(value)))
+}
+
+fn read_json_file(file_path: &str) -> Result<Expr, JreplErr> {
+    let file = File::open(file_path)
+        .map_err(|err| JreplErr::IoErr(format!("Error opening file: '{}': {}", file_path, err)))?;
+
+    let rdr = BufReader::new(file);
+    let value: Value = serde_json::from_reader(rdr).map_err(|err| {
+        JreplErr::JsonErr(format!("Failed to parse as JSON '{}': {}", file_path, err))
+    })?;
+
+    Ok(Expr::from(value))
+}
+
+/// `name=path` binds to `name`, anything else is a plain path. The name must be a valid symbol,
+/// so paths that merely contain a '=' are left alone.
+fn split_binding(arg: &str) -> (Option<&str>, &str) {
+    match arg.split_once('=') {
+        Some((name, path)) if is_valid_symbol(name) && !path.is_empty() => (Some(name), path),
+        _ => (None, arg),
+    }
+}
+
+fn is_valid_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        }
+        _ => false,
+    }
+}
+
+fn symbol_from_stem(file_path: &str) -> String {
+    let stem = Path::new(file_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut symbol: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    // symbols cannot start with a digit or a dash
+    if !symbol.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        symbol.insert(0, '_');
+    }
+    symbol
+}
+
+fn unique_symbol(base: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}_{}", base, n))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(xs: &[&str]) -> Vec<String> {
+        xs.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn extract_json_binds_file_stem() {
+        let results = extract_json(&args(&["dev/simple_object.json"]), &[]);
+        let input = results[0].as_ref().unwrap();
+        assert_eq!(input.symbol, "simple_object");
+        assert_eq!(input.path, "dev/simple_object.json");
+        assert!(matches!(input.value, Expr::Object(_)));
+    }
+
+    #[test]
+    fn extract_json_explicit_name() {
+        let results = extract_json(&args(&["doc=dev/deeply_nested.json"]), &[]);
+        assert_eq!(results[0].as_ref().unwrap().symbol, "doc");
+    }
+
+    #[test]
+    fn extract_json_stem_collisions_get_suffix() {
+        let results = extract_json(
+            &args(&[
+                "dev/simple_object.json",
+                "./dev/simple_object.json",
+                "help=dev/mixed_types.json",
+            ]),
+            &args(&["ld"]),
+        );
+        assert_eq!(results[0].as_ref().unwrap().symbol, "simple_object");
+        assert_eq!(results[1].as_ref().unwrap().symbol, "simple_object_2");
+        assert_eq!(results[2].as_ref().unwrap().symbol, "help");
+
+        let results = extract_json(&args(&["dev/mixed_types.json"]), &args(&["mixed_types"]));
+        assert_eq!(results[0].as_ref().unwrap().symbol, "mixed_types_2");
+    }
+
+    #[test]
+    fn extract_json_explicit_name_collision_is_err() {
+        let results = extract_json(&args(&["help=dev/mixed_types.json"]), &args(&["help"]));
+        assert!(matches!(results[0], Err(JreplErr::InvalidSymbol(_))));
+    }
+
+    #[test]
+    fn extract_json_bad_file_does_not_stop_the_rest() {
+        let results = extract_json(
+            &args(&["dev/does_not_exist.json", "dev/mixed_types.json"]),
+            &[],
+        );
+        assert!(matches!(results[0], Err(JreplErr::IoErr(_))));
+        assert_eq!(results[1].as_ref().unwrap().symbol, "mixed_types");
+    }
+
+    #[test]
+    fn symbol_from_stem_is_lexable() {
+        assert_eq!(symbol_from_stem("data/2024 report.json"), "_2024_report");
+        assert_eq!(symbol_from_stem("a.b.json"), "a_b");
+    }
+
+    #[test]
+    fn read_json_from_parses_or_skips_empty_input() {
+        let value = read_json_from(&b"{\"a\": [1, 2]}\n"[..], "stdin").unwrap();
+        assert!(matches!(value, Some(Expr::Object(_))));
+        assert_eq!(read_json_from(&b" \n"[..], "stdin"), Ok(None));
+        assert!(matches!(
+            read_json_from(&b"{oops"[..], "stdin"),
+            Err(JreplErr::JsonErr(_))
+        ));
+    }
 }
@@ -0,0 +1,234 @@
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::{command_interpreter::types::Expr, errors::errors::JreplErr};
+
+/// How `save` writes a value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveOptions {
+    /// One line with no spaces instead of indented JSON.
+    pub compact: bool,
+    pub sort_keys: bool,
+    /// End the file with a newline, as most editors and tools expect.
+    pub trailing_newline: bool,
+    /// Replace the file if it already exists.
+    pub force: bool,
+}
+
+impl Default for SaveOptions {
+    fn default() -> SaveOptions {
+        SaveOptions {
+            compact: false,
+            sort_keys: false,
+            trailing_newline: true,
+            force: false,
+        }
+    }
+}
+
+/// The bytes `save` writes for `value`. Files are written back exactly as they were loaded,
+/// anything else must have a JSON representation.
+pub fn serialize(value: &Expr, options: &SaveOptions) -> Result<Vec<u8>, JreplErr> {
+    if let Expr::File(file) = value {
+        return Ok(file.bytes.to_vec());
+    }
+
+    let mut json = Value::try_from(value)?;
+    if options.sort_keys {
+        json = sorted(json);
+    }
+    let mut text = if options.compact {
+        serde_json::to_string(&json)
+    } else {
+        serde_json::to_string_pretty(&json)
+    }
+    .map_err(|err| JreplErr::JsonErr(err.to_string()))?;
+    if options.trailing_newline {
+        text.push('\n');
+    }
+    Ok(text.into_bytes())
+}
+
+fn sorted(json: Value) -> Value {
+    match json {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, sorted(v)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sorted).collect()),
+        other => other,
+    }
+}
+
+/// Writes `bytes` to `path` so that readers see either the old file or the complete new one,
+/// never a partial write: the bytes go to a temporary file in the same directory, which then
+/// takes the place of `path`. An existing file is only replaced with `force`. Without it the
+/// temporary file is linked in under the new name, which fails if the name is taken, so a file
+/// created in the meantime is not overwritten either.
+pub fn write_atomic(path: &str, bytes: &[u8], force: bool) -> Result<(), JreplErr> {
+    let target = Path::new(path);
+    if target.file_name().is_none() {
+        return Err(JreplErr::WriteErr(format!(
+            "'{}' does not name a file to save to",
+            path
+        )));
+    }
+
+    let temp = temp_path(target);
+    let written = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .and_then(|_| {
+            if force {
+                fs::rename(&temp, target)
+            } else {
+                link_new(&temp, target)
+            }
+        });
+    let _ = fs::remove_file(&temp);
+    match written {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => Err(JreplErr::FileExists(format!(
+            "'{}' already exists, save with :force to overwrite it",
+            path
+        ))),
+        Err(err) => Err(JreplErr::WriteErr(format!(
+            "Error writing '{}': {}",
+            path, err
+        ))),
+    }
+}
+
+// Gives `temp` the name `target`, failing with AlreadyExists if that name is taken.
+fn link_new(temp: &Path, target: &Path) -> io::Result<()> {
+    match fs::hard_link(temp, target) {
+        Err(err) if err.kind() != ErrorKind::AlreadyExists => {
+            // no hard links on this filesystem: claim the name first, then replace the claim
+            File::create_new(target)?;
+            fs::rename(temp, target)
+        }
+        linked => linked,
+    }
+}
+
+// A sibling of `target`, so the rename stays on one filesystem.
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    target.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{command_interpreter::types::FileValue, json::convert::parse_json};
+
+    fn scratch(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("jrepl-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn serialize_pretty_compact_and_sorted() {
+        let value = parse_json(br#"{"b": [1, 2.50], "a": {"d": null, "c": "x"}}"#).unwrap();
+        let text =
+            |options: SaveOptions| String::from_utf8(serialize(&value, &options).unwrap()).unwrap();
+
+        assert_eq!(
+            text(SaveOptions::default()),
+            "{\n  \"b\": [\n    1,\n    2.50\n  ],\n  \"a\": {\n    \"d\": null,\n    \"c\": \"x\"\n  }\n}\n"
+        );
+        assert_eq!(
+            text(SaveOptions {
+                compact: true,
+                sort_keys: true,
+                trailing_newline: false,
+                ..SaveOptions::default()
+            }),
+            r#"{"a":{"c":"x","d":null},"b":[1,2.50]}"#
+        );
+    }
+
+    #[test]
+    fn serialize_writes_file_bytes_raw() {
+        let bytes: &[u8] = &[0x89, b'P', b'N', b'G', 0, 0xff];
+        let file = Expr::File(FileValue {
+            path: "image.png".to_string(),
+            bytes: Rc::from(bytes),
+            mime: None,
+        });
+        assert_eq!(serialize(&file, &SaveOptions::default()).unwrap(), bytes);
+        assert!(matches!(
+            serialize(&Expr::Symbol("x".to_string()), &SaveOptions::default()),
+            Err(JreplErr::JsonErr(_))
+        ));
+    }
+
+    #[test]
+    fn write_atomic_refuses_to_overwrite_without_force() {
+        let path = scratch("out.json");
+        write_atomic(&path, b"first", false).unwrap();
+        assert!(matches!(
+            write_atomic(&path, b"second", false),
+            Err(JreplErr::FileExists(_))
+        ));
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+
+        write_atomic(&path, b"second", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert!(!temp_path(Path::new(&path)).exists());
+    }
+
+    #[test]
+    fn write_atomic_never_replaces_a_file_created_meanwhile() {
+        let path = scratch("raced.json");
+        let temp = temp_path(Path::new(&path));
+        fs::write(&temp, b"ours").unwrap();
+        fs::write(&path, b"theirs").unwrap();
+        // the state a save without :force is in if the file appears after it started
+        assert_eq!(
+            link_new(&temp, Path::new(&path)).unwrap_err().kind(),
+            ErrorKind::AlreadyExists
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"theirs");
+        fs::remove_file(&temp).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_rejects_paths_without_a_file_name() {
+        for path in ["", "..", "dir/..", "/"] {
+            assert!(
+                matches!(write_atomic(path, b"{}", true), Err(JreplErr::WriteErr(_))),
+                "{:?}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn write_atomic_reports_a_missing_directory() {
+        let path = scratch("missing/out.json");
+        assert!(matches!(
+            write_atomic(&path, b"{}", false),
+            Err(JreplErr::WriteErr(_))
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use std::rc::Rc;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    command_interpreter::{
+        number::Number,
+        types::{Expr, JsonObject},
+    },
+    errors::errors::JreplErr,
+};
+
+pub fn parse_json(bytes: &[u8]) -> Result<Expr, JreplErr> {
+    let value: Value = serde_json::from_slice(bytes)
+        .map_err(|e| JreplErr::JsonErr(format!("Failed to parse JSON: {}", e)))?;
+    Ok(Expr::from(value))
+}
+
+impl From<Value> for Expr {
+    fn from(value: Value) -> Expr {
+        match value {
+            Value::Null => Expr::None,
+            Value::Bool(b) => Expr::Bool(b),
+            Value::Number(n) => Expr::Number(Number::from_json(&n)),
+            Value::String(s) => Expr::String(s),
+            Value::Array(items) => {
+                Expr::Array(Rc::new(items.into_iter().map(Expr::from).collect()))
+            }
+            Value::Object(map) => Expr::Object(Rc::new(
+                map.into_iter()
+                    .map(|(k, v)| (k, Expr::from(v)))
+                    .collect::<JsonObject>(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<&Expr> for Value {
+    type Error = JreplErr;
+
+    fn try_from(expr: &Expr) -> Result<Value, JreplErr> {
+        match expr {
+            Expr::None => Ok(Value::Null),
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
+            Expr::Number(n) => n.to_json().map(Value::Number),
+            Expr::String(s) => Ok(Value::String(s.clone())),
+            // an evaluated list (e.g. query results) is plain data
+            Expr::Array(items) => items
+                .iter()
+                .map(Value::try_from)
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            Expr::List(items) => items
+                .iter()
+                .map(Value::try_from)
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            Expr::Object(map) => map
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), Value::try_from(v)?)))
+                .collect::<Result<Map<_, _>, JreplErr>>()
+                .map(Value::Object),
+            other => Err(JreplErr::JsonErr(format!(
+                "Value has no JSON representation: {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn convert_scalars() {
+        assert_eq!(Expr::from(json!(null)), Expr::None);
+        assert_eq!(Expr::from(json!(true)), Expr::Bool(true));
+        assert_eq!(Expr::from(json!(2.5)), Expr::Number(Number::float(2.5)));
+        assert_eq!(Expr::from(json!("hi")), Expr::String("hi".into()));
+    }
+
+    #[test]
+    fn convert_round_trip_nested_document() {
+        let value = json!({
+            "name": "Aaon",
+            "tags": ["a", "b"],
+            "config": { "window": { "width": 800, "ratio": 1.5, "visible": false } },
+            "missing": null
+        });
+        let expr = Expr::from(value.clone());
+        assert_eq!(Value::try_from(&expr), Ok(value));
+    }
+
+    #[test]
+    fn convert_preserves_key_order() {
+        let expr = parse_json(br#"{"z": 1, "a": 2, "m": 3}"#).unwrap();
+        let keys: Vec<&String> = match &expr {
+            Expr::Object(map) => map.keys().collect(),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(keys, vec!["z", "a", "m"]);
+    }
+
+    #[test]
+    fn convert_whole_numbers_stay_integers() {
+        assert_eq!(Value::try_from(&Expr::Number(Number::Int(3))), Ok(json!(3)));
+    }
+
+    #[test]
+    fn convert_keeps_numbers_as_written() {
+        let text =
+            r#"{"id":9007199254740993,"big":123456789012345678901234567890,"price":1.50,"n":1e10}"#;
+        let expr = parse_json(text.as_bytes()).unwrap();
+        assert_eq!(
+            serde_json::to_string(&Value::try_from(&expr).unwrap()).unwrap(),
+            text
+        );
+    }
+
+    #[test]
+    fn convert_non_json_expr_is_err() {
+        assert!(Value::try_from(&Expr::Symbol("x".into())).is_err());
+        assert!(Value::try_from(&Expr::Number(Number::float(f64::NAN))).is_err());
+    }
+
+    #[test]
+    fn parse_invalid_json_is_err() {
+        assert!(parse_json(b"{ not json").is_err());
+    }
+}
@@ -0,0 +1,434 @@
+/*
+    Structural diff of two JSON values.
+
+    Objects are compared key by key, whatever their key order, and numbers by value, so
+    reformatting a document changes nothing. Arrays are compared by index, or, given a key field,
+    by matching the objects that share a value for it, so an inserted element does not show up
+    as a change to everything after it.
+
+    The changes come out in an order they can be applied in, one after the other, with every path
+    referring to the document as the earlier changes left it: that is what makes them a valid
+    RFC 6902 patch. Removals from an array go from the back, and elements matched by key are
+    moved into place before their own changes.
+*/
+
+use std::collections::HashMap;
+
+use crate::{
+    command_interpreter::types::{Expr, JsonObject},
+    json::{
+        path::{PathSegment, to_pointer},
+        pretty::{PrettyOptions, render},
+    },
+    statics::logic_commands::values_equal,
+};
+
+const ADDED: &str = "\x1b[32m";
+const REMOVED: &str = "\x1b[31m";
+const MOVED: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        path: Vec<PathSegment>,
+        value: Expr,
+    },
+    Removed {
+        path: Vec<PathSegment>,
+        value: Expr,
+    },
+    Changed {
+        path: Vec<PathSegment>,
+        old: Expr,
+        new: Expr,
+    },
+    Moved {
+        from: Vec<PathSegment>,
+        path: Vec<PathSegment>,
+    },
+}
+
+impl Change {
+    /// The change as data: {"change": "changed", "path": "/a", "old": 1, "new": 2}.
+    pub fn to_expr(&self) -> Expr {
+        let mut object = JsonObject::new();
+        let mut field = |key: &str, value: Expr| {
+            object.insert(key.to_string(), value);
+        };
+        match self {
+            Change::Added { path, value } => {
+                field("change", Expr::String("added".to_string()));
+                field("path", pointer(path));
+                field("value", value.clone());
+            }
+            Change::Removed { path, value } => {
+                field("change", Expr::String("removed".to_string()));
+                field("path", pointer(path));
+                field("value", value.clone());
+            }
+            Change::Changed { path, old, new } => {
+                field("change", Expr::String("changed".to_string()));
+                field("path", pointer(path));
+                field("old", old.clone());
+                field("new", new.clone());
+            }
+            Change::Moved { from, path } => {
+                field("change", Expr::String("moved".to_string()));
+                field("from", pointer(from));
+                field("path", pointer(path));
+            }
+        }
+        Expr::object(object)
+    }
+
+    /// The RFC 6902 operation making this change.
+    pub fn to_patch_op(&self) -> Expr {
+        let mut object = JsonObject::new();
+        let mut field = |key: &str, value: Expr| {
+            object.insert(key.to_string(), value);
+        };
+        match self {
+            Change::Added { path, value } => {
+                field("op", Expr::String("add".to_string()));
+                field("path", pointer(path));
+                field("value", value.clone());
+            }
+            Change::Removed { path, .. } => {
+                field("op", Expr::String("remove".to_string()));
+                field("path", pointer(path));
+            }
+            Change::Changed { path, new, .. } => {
+                field("op", Expr::String("replace".to_string()));
+                field("path", pointer(path));
+                field("value", new.clone());
+            }
+            Change::Moved { from, path } => {
+                field("op", Expr::String("move".to_string()));
+                field("from", pointer(from));
+                field("path", pointer(path));
+            }
+        }
+        Expr::object(object)
+    }
+}
+
+fn pointer(path: &[PathSegment]) -> Expr {
+    Expr::String(to_pointer(path))
+}
+
+/// The changes that turn `old` into `new`. With `array_key`, arrays whose elements are all
+/// objects with a distinct value for that key are matched by it instead of by index.
+pub fn diff(old: &Expr, new: &Expr, array_key: Option<&str>) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at(old, new, &mut Vec::new(), array_key, &mut changes);
+    changes
+}
+
+fn diff_at(
+    old: &Expr,
+    new: &Expr,
+    path: &mut Vec<PathSegment>,
+    array_key: Option<&str>,
+    out: &mut Vec<Change>,
+) {
+    if values_equal(old, new) {
+        return;
+    }
+    match (old, new, items(old), items(new)) {
+        (Expr::Object(a), Expr::Object(b), _, _) => {
+            for (key, value) in a.iter() {
+                path.push(PathSegment::Key(key.clone()));
+                match b.get(key) {
+                    Some(other) => diff_at(value, other, path, array_key, out),
+                    None => out.push(Change::Removed {
+                        path: path.clone(),
+                        value: value.clone(),
+                    }),
+                }
+                path.pop();
+            }
+            for (key, value) in b.iter().filter(|(key, _)| !a.contains_key(*key)) {
+                out.push(Change::Added {
+                    path: child(path, PathSegment::Key(key.clone())),
+                    value: value.clone(),
+                });
+            }
+        }
+        (_, _, Some(a), Some(b)) => {
+            let keyed = array_key.and_then(|key| Some((keys_of(a, key)?, keys_of(b, key)?)));
+            match keyed {
+                Some((a_keys, b_keys)) => diff_keyed(a, b, &a_keys, &b_keys, path, array_key, out),
+                None => diff_indexed(a, b, path, array_key, out),
+            }
+        }
+        _ => out.push(Change::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+    }
+}
+
+fn diff_indexed(
+    a: &[Expr],
+    b: &[Expr],
+    path: &mut Vec<PathSegment>,
+    array_key: Option<&str>,
+    out: &mut Vec<Change>,
+) {
+    let common = a.len().min(b.len());
+    for i in 0..common {
+        path.push(PathSegment::Index(i));
+        diff_at(&a[i], &b[i], path, array_key, out);
+        path.pop();
+    }
+    for (i, value) in a.iter().enumerate().skip(common).rev() {
+        out.push(Change::Removed {
+            path: child(path, PathSegment::Index(i)),
+            value: value.clone(),
+        });
+    }
+    for (i, value) in b.iter().enumerate().skip(common) {
+        out.push(Change::Added {
+            path: child(path, PathSegment::Index(i)),
+            value: value.clone(),
+        });
+    }
+}
+
+fn diff_keyed(
+    a: &[Expr],
+    b: &[Expr],
+    a_keys: &[String],
+    b_keys: &[String],
+    path: &mut Vec<PathSegment>,
+    array_key: Option<&str>,
+    out: &mut Vec<Change>,
+) {
+    let old_by_key: HashMap<&String, &Expr> = a_keys.iter().zip(a).collect();
+
+    // the elements whose key is gone, from the back so the indices stay valid
+    for (i, key) in a_keys.iter().enumerate().rev() {
+        if !b_keys.contains(key) {
+            out.push(Change::Removed {
+                path: child(path, PathSegment::Index(i)),
+                value: a[i].clone(),
+            });
+        }
+    }
+
+    // then build the new order front to back, `current` tracking the array as it is so far
+    let mut current: Vec<&String> = a_keys.iter().filter(|key| b_keys.contains(key)).collect();
+    for (i, (key, value)) in b_keys.iter().zip(b).enumerate() {
+        match old_by_key.get(key) {
+            None => {
+                out.push(Change::Added {
+                    path: child(path, PathSegment::Index(i)),
+                    value: value.clone(),
+                });
+                current.insert(i, key);
+            }
+            Some(old) => {
+                let at = current.iter().position(|k| *k == key).unwrap_or(i);
+                if at != i {
+                    out.push(Change::Moved {
+                        from: child(path, PathSegment::Index(at)),
+                        path: child(path, PathSegment::Index(i)),
+                    });
+                    let moved = current.remove(at);
+                    current.insert(i, moved);
+                }
+                path.push(PathSegment::Index(i));
+                diff_at(old, value, path, array_key, out);
+                path.pop();
+            }
+        }
+    }
+}
+
+fn items(value: &Expr) -> Option<&[Expr]> {
+    match value {
+        Expr::Array(items) => Some(items),
+        Expr::List(items) => Some(items),
+        _ => None,
+    }
+}
+
+// The key of every element, or None if some element is not an object with a unique key.
+fn keys_of(items: &[Expr], key: &str) -> Option<Vec<String>> {
+    let keys: Vec<String> = items
+        .iter()
+        .map(|item| match item {
+            Expr::Object(object) => object.get(key).map(compact),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    let unique = keys.iter().collect::<std::collections::HashSet<_>>().len() == keys.len();
+    unique.then_some(keys)
+}
+
+fn child(path: &[PathSegment], segment: PathSegment) -> Vec<PathSegment> {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
+
+fn compact(value: &Expr) -> String {
+    render(
+        value,
+        &PrettyOptions {
+            compact: true,
+            ..PrettyOptions::default()
+        },
+    )
+}
+
+/// One line per change, like a unified diff: "- path: old" and "+ path: new", with a change
+/// shown as both. Removals are red and additions green when `color` is set.
+pub fn render_unified(changes: &[Change], color: bool) -> String {
+    let paint = |code: &str, line: String| {
+        if color {
+            format!("{}{}{}\n", code, line, RESET)
+        } else {
+            format!("{}\n", line)
+        }
+    };
+    let mut out = String::new();
+    for change in changes {
+        match change {
+            Change::Added { path, value } => {
+                out += &paint(ADDED, format!("+ {}: {}", to_pointer(path), compact(value)));
+            }
+            Change::Removed { path, value } => {
+                out += &paint(
+                    REMOVED,
+                    format!("- {}: {}", to_pointer(path), compact(value)),
+                );
+            }
+            Change::Changed { path, old, new } => {
+                out += &paint(REMOVED, format!("- {}: {}", to_pointer(path), compact(old)));
+                out += &paint(ADDED, format!("+ {}: {}", to_pointer(path), compact(new)));
+            }
+            Change::Moved { from, path } => {
+                out += &paint(
+                    MOVED,
+                    format!("~ {} -> {}", to_pointer(from), to_pointer(path)),
+                );
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json::convert::parse_json;
+
+    fn doc(text: &str) -> Expr {
+        parse_json(text.as_bytes()).unwrap()
+    }
+
+    fn ops(old: &str, new: &str, array_key: Option<&str>) -> serde_json::Value {
+        let patch: Vec<Expr> = diff(&doc(old), &doc(new), array_key)
+            .iter()
+            .map(Change::to_patch_op)
+            .collect();
+        serde_json::Value::try_from(&Expr::array(patch)).unwrap()
+    }
+
+    #[test]
+    fn equal_documents_have_no_changes() {
+        assert!(
+            diff(
+                &doc(r#"{"a": 1, "b": [1.0]}"#),
+                &doc(r#"{"b": [1], "a": 1.00}"#),
+                None
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn objects_report_added_removed_and_changed_keys() {
+        let changes = diff(
+            &doc(r#"{"name": "app", "debug": true, "port": 80}"#),
+            &doc(r#"{"name": "app", "port": 8080, "host": "x"}"#),
+            None,
+        );
+        let records: Vec<serde_json::Value> = changes
+            .iter()
+            .map(|c| serde_json::Value::try_from(&c.to_expr()).unwrap())
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                serde_json::json!({"change": "removed", "path": "/debug", "value": true}),
+                serde_json::json!({"change": "changed", "path": "/port", "old": 80, "new": 8080}),
+                serde_json::json!({"change": "added", "path": "/host", "value": "x"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays_by_index_remove_from_the_back() {
+        assert_eq!(
+            ops(r#"[1, 2, 3, 4]"#, r#"[1, 5]"#, None),
+            serde_json::json!([
+                {"op": "replace", "path": "/1", "value": 5},
+                {"op": "remove", "path": "/3"},
+                {"op": "remove", "path": "/2"}
+            ])
+        );
+        assert_eq!(
+            ops(r#"{"a": []}"#, r#"{"a": [{"b/c": 1}]}"#, None),
+            serde_json::json!([{"op": "add", "path": "/a/0", "value": {"b/c": 1}}])
+        );
+        assert_eq!(
+            ops("1", r#""one""#, None),
+            serde_json::json!([{"op": "replace", "path": "", "value": "one"}])
+        );
+    }
+
+    #[test]
+    fn arrays_by_key_match_moved_elements() {
+        let old = r#"[{"id": 1, "v": "a"}, {"id": 2, "v": "b"}, {"id": 3, "v": "c"}]"#;
+        let new = r#"[{"id": 3, "v": "c"}, {"id": 4, "v": "d"}, {"id": 1, "v": "A"}]"#;
+        assert_eq!(
+            ops(old, new, Some("id")),
+            serde_json::json!([
+                {"op": "remove", "path": "/1"},
+                {"op": "move", "from": "/1", "path": "/0"},
+                {"op": "add", "path": "/1", "value": {"id": 4, "v": "d"}},
+                {"op": "replace", "path": "/2/v", "value": "A"}
+            ])
+        );
+        // a missing key falls back to matching by index
+        assert_eq!(
+            ops(r#"[{"id": 1}, {"x": 2}]"#, r#"[{"x": 2}]"#, Some("id")),
+            serde_json::json!([
+                {"op": "remove", "path": "/0/id"},
+                {"op": "add", "path": "/0/x", "value": 2},
+                {"op": "remove", "path": "/1"}
+            ])
+        );
+    }
+
+    #[test]
+    fn unified_rendering() {
+        let changes = diff(
+            &doc(r#"{"a": 1, "b": [true]}"#),
+            &doc(r#"{"a": 2, "c": null}"#),
+            None,
+        );
+        assert_eq!(
+            render_unified(&changes, false),
+            "- /a: 1\n+ /a: 2\n- /b: [true]\n+ /c: null\n"
+        );
+        assert_eq!(
+            render_unified(&changes[..1], true),
+            "\x1b[31m- /a: 1\x1b[0m\n\x1b[32m+ /a: 2\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+/*
+    Immutable edits on JSON values.
+
+    Every edit returns a new value and leaves the input untouched. Only the containers along the
+    edited path are copied, and those copies are shallow: every sibling subtree is an Rc that the
+    old and new documents share.
+*/
+
+use crate::{
+    command_interpreter::types::{Expr, JsonObject},
+    errors::errors::JreplErr,
+    json::path::{PathSegment, lookup_err},
+};
+
+/// Sets the value at `path`. Missing object keys are added, along with any missing intermediate
+/// objects, and an index one past the end of an array appends.
+pub fn assoc_in(value: &Expr, path: &[PathSegment], new_value: Expr) -> Result<Expr, JreplErr> {
+    assoc_at(value, path, 0, new_value)
+}
+
+fn assoc_at(
+    value: &Expr,
+    path: &[PathSegment],
+    depth: usize,
+    new_value: Expr,
+) -> Result<Expr, JreplErr> {
+    if depth == path.len() {
+        return Ok(new_value);
+    }
+    with_child(value, path, depth, |child| {
+        let child = match child {
+            Some(child) => child.clone(),
+            None if depth + 1 < path.len() => Expr::object(JsonObject::new()),
+            None => Expr::None,
+        };
+        assoc_at(&child, path, depth + 1, new_value).map(Some)
+    })
+}
+
+/// Replaces the value at `path` with `f(old)`. The path must already exist.
+pub fn update_in<F>(value: &Expr, path: &[PathSegment], f: F) -> Result<Expr, JreplErr>
+where
+    F: FnOnce(&Expr) -> Result<Expr, JreplErr>,
+{
+    update_at(value, path, 0, f)
+}
+
+fn update_at<F>(value: &Expr, path: &[PathSegment], depth: usize, f: F) -> Result<Expr, JreplErr>
+where
+    F: FnOnce(&Expr) -> Result<Expr, JreplErr>,
+{
+    if depth == path.len() {
+        return f(value);
+    }
+    with_child(value, path, depth, |child| match child {
+        Some(child) => update_at(child, path, depth + 1, f).map(Some),
+        None => Err(lookup_err(value, &path[..depth], &path[depth])),
+    })
+}
+
+/// Removes the key or array element at `path`. The path must exist and must not be empty.
+pub fn dissoc_in(value: &Expr, path: &[PathSegment]) -> Result<Expr, JreplErr> {
+    if path.is_empty() {
+        return Err(JreplErr::InvalidPath(
+            "Cannot remove the root of a document.".to_string(),
+        ));
+    }
+    dissoc_at(value, path, 0)
+}
+
+fn dissoc_at(value: &Expr, path: &[PathSegment], depth: usize) -> Result<Expr, JreplErr> {
+    with_child(value, path, depth, |child| match child {
+        None => Err(lookup_err(value, &path[..depth], &path[depth])),
+        Some(_) if depth + 1 == path.len() => Ok(None),
+        Some(child) => dissoc_at(child, path, depth + 1).map(Some),
+    })
+}
+
+/// Rebuilds `container` with the child at `path[depth]` replaced by `f(child)`, or removed when
+/// `f` returns `None`. Only this one container is copied.
+fn with_child<F>(
+    container: &Expr,
+    path: &[PathSegment],
+    depth: usize,
+    f: F,
+) -> Result<Expr, JreplErr>
+where
+    F: FnOnce(Option<&Expr>) -> Result<Option<Expr>, JreplErr>,
+{
+    let segment = &path[depth];
+    match container {
+        Expr::Object(map) => {
+            let key = segment.as_key();
+            let replacement = f(map.get(&key))?;
+            let mut map = JsonObject::clone(map);
+            match replacement {
+                Some(v) => {
+                    map.insert(key, v);
+                }
+                None => {
+                    map.shift_remove(&key);
+                }
+            }
+            Ok(Expr::object(map))
+        }
+        Expr::Array(items) => {
+            let index = match segment.as_index() {
+                Some(i) if i <= items.len() => i,
+                _ => return Err(lookup_err(container, &path[..depth], segment)),
+            };
+            let replacement = f(items.get(index))?;
+            let mut items = Vec::clone(items);
+            match replacement {
+                Some(v) if index == items.len() => items.push(v),
+                Some(v) => items[index] = v,
+                None => {
+                    items.remove(index);
+                }
+            }
+            Ok(Expr::array(items))
+        }
+        _ => Err(lookup_err(container, &path[..depth], segment)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::command_interpreter::number::Number;
+    use crate::json::{
+        convert::parse_json,
+        path::{get_in, parse_pointer},
+    };
+
+    fn doc() -> Expr {
+        parse_json(
+            br#"{
+                "config": { "window": { "width": 800, "height": 600 } },
+                "users": [ { "name": "Bob" }, { "name": "Carol" } ],
+                "big": { "blob": [1, 2, 3] }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn ptr(p: &str) -> Vec<PathSegment> {
+        parse_pointer(p).unwrap()
+    }
+
+    fn json(expr: &Expr) -> serde_json::Value {
+        serde_json::Value::try_from(expr).unwrap()
+    }
+
+    fn child<'a>(expr: &'a Expr, key: &str) -> &'a Expr {
+        get_in(expr, &ptr(&format!("/{}", key))).unwrap()
+    }
+
+    #[test]
+    fn assoc_in_sets_and_leaves_original_unchanged() {
+        let original = doc();
+        let before = json(&original);
+        let edited = assoc_in(
+            &original,
+            &ptr("/config/window/width"),
+            Expr::Number(Number::Int(1024)),
+        )
+        .unwrap();
+
+        assert_eq!(json(&original), before);
+        assert_eq!(
+            get_in(&edited, &ptr("/config/window/width")),
+            Ok(&Expr::Number(Number::Int(1024)))
+        );
+        assert_eq!(
+            get_in(&edited, &ptr("/config/window/height")),
+            Ok(&Expr::Number(Number::Int(600)))
+        );
+    }
+
+    #[test]
+    fn assoc_in_shares_untouched_subtrees() {
+        let original = doc();
+        let edited = assoc_in(
+            &original,
+            &ptr("/config/window/width"),
+            Expr::Number(Number::Int(1)),
+        )
+        .unwrap();
+
+        match (child(&original, "big"), child(&edited, "big")) {
+            (Expr::Object(a), Expr::Object(b)) => assert!(Rc::ptr_eq(a, b)),
+            other => panic!("{:?}", other),
+        }
+        match (child(&original, "users"), child(&edited, "users")) {
+            (Expr::Array(a), Expr::Array(b)) => assert!(Rc::ptr_eq(a, b)),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn assoc_in_creates_missing_keys_and_appends() {
+        let edited = assoc_in(
+            &doc(),
+            &ptr("/config/theme/background"),
+            Expr::String("#fff".into()),
+        )
+        .unwrap();
+        assert_eq!(
+            json(child(&edited, "config")),
+            serde_json::json!({
+                "window": { "width": 800, "height": 600 },
+                "theme": { "background": "#fff" }
+            })
+        );
+
+        let edited = assoc_in(&doc(), &ptr("/users/2"), Expr::String("Dan".into())).unwrap();
+        assert_eq!(
+            get_in(&edited, &ptr("/users/2")),
+            Ok(&Expr::String("Dan".into()))
+        );
+        assert!(matches!(
+            assoc_in(&doc(), &ptr("/users/5"), Expr::None),
+            Err(JreplErr::IndexOutOfBounds(_))
+        ));
+    }
+
+    #[test]
+    fn assoc_in_keeps_key_position() {
+        let edited = assoc_in(
+            &doc(),
+            &ptr("/config/window/width"),
+            Expr::Number(Number::Int(1)),
+        )
+        .unwrap();
+        match get_in(&edited, &ptr("/config/window")).unwrap() {
+            Expr::Object(map) => {
+                assert_eq!(map.keys().collect::<Vec<_>>(), vec!["width", "height"])
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn assoc_in_empty_path_replaces_root() {
+        assert_eq!(
+            assoc_in(&doc(), &[], Expr::Bool(true)),
+            Ok(Expr::Bool(true))
+        );
+    }
+
+    #[test]
+    fn update_in_applies_function() {
+        let edited = update_in(&doc(), &ptr("/config/window/height"), |old| match old {
+            Expr::Number(n) => Ok(Expr::Number(n.mul(&Number::Int(2)))),
+            other => panic!("{:?}", other),
+        })
+        .unwrap();
+        assert_eq!(
+            get_in(&edited, &ptr("/config/window/height")),
+            Ok(&Expr::Number(Number::Int(1200)))
+        );
+    }
+
+    #[test]
+    fn update_in_missing_path_is_err() {
+        let result = update_in(&doc(), &ptr("/config/door/width"), |old| Ok(old.clone()));
+        assert_eq!(
+            result,
+            Err(JreplErr::MissingKey(
+                "No key 'door' in the object at /config.".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn dissoc_in_removes_keys_and_elements() {
+        let original = doc();
+        let edited = dissoc_in(&original, &ptr("/users/0")).unwrap();
+        assert_eq!(
+            json(child(&edited, "users")),
+            serde_json::json!([{ "name": "Carol" }])
+        );
+        assert_eq!(json(child(&original, "users")).as_array().unwrap().len(), 2);
+
+        let edited = dissoc_in(&original, &ptr("/config/window/width")).unwrap();
+        assert_eq!(
+            json(&edited)["config"]["window"],
+            serde_json::json!({ "height": 600 })
+        );
+    }
+
+    #[test]
+    fn dissoc_in_missing_or_root_is_err() {
+        assert!(matches!(
+            dissoc_in(&doc(), &ptr("/nope")),
+            Err(JreplErr::MissingKey(_))
+        ));
+        assert!(matches!(
+            dissoc_in(&doc(), &ptr("/users/7")),
+            Err(JreplErr::IndexOutOfBounds(_))
+        ));
+        assert!(matches!(
+            dissoc_in(&doc(), &[]),
+            Err(JreplErr::InvalidPath(_))
+        ));
+    }
+}
@@ -0,0 +1,438 @@
+/*
+    Inferring a JSON Schema from sample values.
+
+    Every sample is folded into one shape per position in the documents: which types were seen
+    there, how often each object key was present, the merged shape of all array items, and for
+    strings their distinct values and whether they all share a format. The shape is then written
+    out as a draft 2020-12 schema that every sample validates against:
+
+    - a position holding several types gets a list of types, integers merged into number
+    - object keys missing from some samples are left out of "required"
+    - strings that all look like a date, date-time, email or UUID get a "format"
+    - a few distinct strings repeated across many samples become an "enum", where no other
+      type was seen
+*/
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::command_interpreter::types::{Expr, JsonObject};
+
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+// strings become an enum when there are at most this many distinct values...
+const ENUM_MAX_VALUES: usize = 5;
+// ...and each value was seen at least this many times on average
+const ENUM_MIN_REPEATS: usize = 2;
+
+/// A schema that accepts every sample.
+pub fn infer_schema(samples: &[Expr]) -> Expr {
+    let mut shape = Shape::default();
+    for sample in samples {
+        shape.add(sample);
+    }
+    let mut schema = JsonObject::new();
+    schema.insert(
+        "$schema".to_string(),
+        Expr::String(SCHEMA_DIALECT.to_string()),
+    );
+    schema.extend(shape.to_schema());
+    Expr::object(schema)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Date,
+    DateTime,
+    Email,
+    Uuid,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Date => "date",
+            Format::DateTime => "date-time",
+            Format::Email => "email",
+            Format::Uuid => "uuid",
+        }
+    }
+
+    fn of(text: &str) -> Option<Format> {
+        [Format::Date, Format::DateTime, Format::Email, Format::Uuid]
+            .into_iter()
+            .find(|format| format.matches(text))
+    }
+
+    fn matches(self, text: &str) -> bool {
+        match self {
+            Format::Date => is_date(text),
+            Format::DateTime => is_date_time(text),
+            Format::Email => is_email(text),
+            Format::Uuid => is_uuid(text),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shape {
+    null: bool,
+    boolean: bool,
+    integer: bool,
+    // numbers with a fraction
+    number: bool,
+    strings: Option<Strings>,
+    items: Option<Box<Shape>>,
+    object: Option<Properties>,
+}
+
+struct Strings {
+    count: usize,
+    // up to one more than ENUM_MAX_VALUES, which is enough to rule an enum out
+    distinct: IndexSet<String>,
+    // the format every string so far has, None once one didn't match
+    format: Option<Format>,
+}
+
+#[derive(Default)]
+struct Properties {
+    count: usize,
+    // each key's shape, and in how many of the objects it was present
+    keys: IndexMap<String, (Shape, usize)>,
+}
+
+impl Shape {
+    fn add(&mut self, value: &Expr) {
+        match value {
+            Expr::None => self.null = true,
+            Expr::Bool(_) => self.boolean = true,
+            Expr::Number(n) if n.is_integer() => self.integer = true,
+            Expr::Number(_) => self.number = true,
+            Expr::String(s) => self.add_string(s),
+            Expr::Array(items) => self.add_items(items),
+            Expr::List(items) => self.add_items(items),
+            Expr::Object(object) => {
+                let properties = self.object.get_or_insert_with(Properties::default);
+                properties.count += 1;
+                for (key, value) in object.iter() {
+                    let (shape, seen) = properties.keys.entry(key.clone()).or_default();
+                    shape.add(value);
+                    *seen += 1;
+                }
+            }
+            // not JSON, nothing to describe
+            Expr::File(_) | Expr::Lambda(_) | Expr::Symbol(_) => {}
+        }
+    }
+
+    fn add_string(&mut self, s: &str) {
+        match &mut self.strings {
+            None => {
+                self.strings = Some(Strings {
+                    count: 1,
+                    distinct: IndexSet::from([s.to_string()]),
+                    format: Format::of(s),
+                })
+            }
+            Some(strings) => {
+                strings.count += 1;
+                if strings.distinct.len() <= ENUM_MAX_VALUES {
+                    strings.distinct.insert(s.to_string());
+                }
+                strings.format = strings.format.filter(|format| format.matches(s));
+            }
+        }
+    }
+
+    fn add_items(&mut self, items: &[Expr]) {
+        let shape = self.items.get_or_insert_with(Box::default);
+        for item in items {
+            shape.add(item);
+        }
+    }
+
+    // "enum" restricts every type, so it is only written where nothing but strings were seen
+    fn only_strings(&self) -> bool {
+        !(self.null || self.boolean || self.integer || self.number)
+            && self.items.is_none()
+            && self.object.is_none()
+    }
+
+    fn to_schema(&self) -> JsonObject {
+        let mut types = Vec::new();
+        let mut keywords = JsonObject::new();
+        let mut keyword = |key: &str, value: Expr| {
+            keywords.insert(key.to_string(), value);
+        };
+
+        if self.null {
+            types.push("null");
+        }
+        if self.boolean {
+            types.push("boolean");
+        }
+        match (self.integer, self.number) {
+            (true, false) => types.push("integer"),
+            (_, true) => types.push("number"),
+            _ => {}
+        }
+        if let Some(strings) = &self.strings {
+            types.push("string");
+            if let Some(format) = strings.format {
+                keyword("format", Expr::String(format.name().to_string()));
+            } else if self.only_strings()
+                && strings.distinct.len() <= ENUM_MAX_VALUES
+                && strings.count >= strings.distinct.len() * ENUM_MIN_REPEATS
+            {
+                let mut values: Vec<&String> = strings.distinct.iter().collect();
+                values.sort();
+                let values = values
+                    .into_iter()
+                    .map(|v| Expr::String(v.clone()))
+                    .collect();
+                keyword("enum", Expr::array(values));
+            }
+        }
+        if let Some(items) = &self.items {
+            types.push("array");
+            let items = items.to_schema();
+            // arrays that were always empty say nothing about their items
+            if !items.is_empty() {
+                keyword("items", Expr::object(items));
+            }
+        }
+        if let Some(object) = &self.object {
+            types.push("object");
+            let properties = object
+                .keys
+                .iter()
+                .map(|(key, (shape, _))| (key.clone(), Expr::object(shape.to_schema())))
+                .collect();
+            keyword("properties", Expr::object(properties));
+            let required: Vec<Expr> = object
+                .keys
+                .iter()
+                .filter(|(_, (_, seen))| *seen == object.count)
+                .map(|(key, _)| Expr::String(key.clone()))
+                .collect();
+            if !required.is_empty() {
+                keyword("required", Expr::array(required));
+            }
+        }
+
+        // "type" first, the way schemas are usually written
+        let mut schema = JsonObject::new();
+        let mut types: Vec<Expr> = types
+            .into_iter()
+            .map(|t| Expr::String(t.to_string()))
+            .collect();
+        match types.len() {
+            0 => {}
+            1 => {
+                schema.insert("type".to_string(), types.remove(0));
+            }
+            _ => {
+                schema.insert("type".to_string(), Expr::array(types));
+            }
+        }
+        schema.extend(keywords);
+        schema
+    }
+}
+
+// --------------------------------- Formats --------------------------------- //
+
+fn digits(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn in_range(text: &str, min: u32, max: u32) -> bool {
+    digits(text) && text.parse::<u32>().is_ok_and(|n| (min..=max).contains(&n))
+}
+
+// 2024-02-29
+fn is_date(text: &str) -> bool {
+    let parts: Vec<&str> = text.split('-').collect();
+    matches!(parts.as_slice(), [year, month, day]
+        if year.len() == 4 && digits(year)
+            && month.len() == 2 && in_range(month, 1, 12)
+            && day.len() == 2 && in_range(day, 1, 31))
+}
+
+// 2024-02-29T13:45:00Z, 2024-02-29T13:45:00.123+01:00
+fn is_date_time(text: &str) -> bool {
+    let Some((date, time)) = text.split_once(['T', 't']) else {
+        return false;
+    };
+    let (clock, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(at) => time.split_at(at),
+        None => return false,
+    };
+    let clock = clock.split_once('.').map_or(
+        clock,
+        |(whole, fraction)| {
+            if digits(fraction) { whole } else { "" }
+        },
+    );
+    let hms = |text: &str, parts: usize| {
+        let fields: Vec<&str> = text.split(':').collect();
+        fields.len() == parts
+            && fields
+                .iter()
+                .zip([23, 59, 60])
+                .all(|(field, max)| field.len() == 2 && in_range(field, 0, max))
+    };
+    let offset_ok =
+        matches!(offset, "Z" | "z") || offset.strip_prefix(['+', '-']).is_some_and(|o| hms(o, 2));
+    is_date(date) && hms(clock, 3) && offset_ok
+}
+
+// 123e4567-e89b-12d3-a456-426614174000
+fn is_uuid(text: &str) -> bool {
+    let groups: Vec<&str> = text.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+// local@example.com, deliberately loose
+fn is_email(text: &str) -> bool {
+    let Some((local, domain)) = text.split_once('@') else {
+        return false;
+    };
+    let labels: Vec<&str> = domain.split('.').collect();
+    !local.is_empty()
+        && !text.contains(char::is_whitespace)
+        && !domain.contains('@')
+        && labels.len() >= 2
+        && labels.iter().all(|label| !label.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json::{convert::parse_json, schema::validate};
+    use proptest::prelude::*;
+
+    fn doc(text: &str) -> Expr {
+        parse_json(text.as_bytes()).unwrap()
+    }
+
+    fn samples(text: &str) -> Vec<Expr> {
+        match doc(text) {
+            Expr::Array(items) => items.to_vec(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn schema_of(text: &str) -> serde_json::Value {
+        serde_json::Value::try_from(&infer_schema(&samples(text))).unwrap()
+    }
+
+    #[test]
+    fn merges_objects_and_marks_optional_keys() {
+        let schema = schema_of(
+            r#"[
+                {"name": "Bob", "age": 25, "tags": ["a"]},
+                {"name": "Carol", "age": 28.5, "nick": null},
+                {"name": "Dan", "age": 31, "tags": [], "nick": "D"}
+            ]"#,
+        );
+        assert_eq!(
+            schema,
+            serde_json::json!({
+                "$schema": SCHEMA_DIALECT,
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string"},
+                    "age": {"type": "number"},
+                    "tags": {"type": "array", "items": {"type": "string"}},
+                    "nick": {"type": ["null", "string"]}
+                },
+                "required": ["name", "age"]
+            })
+        );
+    }
+
+    #[test]
+    fn detects_enums_and_formats() {
+        let schema = schema_of(
+            r#"[
+                {"status": "open", "id": "123e4567-e89b-12d3-a456-426614174000", "due": "2024-02-29", "by": "ann@example.com", "at": "2024-02-29T13:45:00.5+01:00"},
+                {"status": "closed", "id": "00000000-0000-0000-0000-000000000000", "due": "2024-12-01", "by": "bob@example.org", "at": "2024-02-29T13:45:00Z"},
+                {"status": "open", "id": "ffffffff-ffff-ffff-ffff-ffffffffffff", "due": "1999-01-31", "by": "c@d.io", "at": "2000-01-01T00:00:00Z"},
+                {"status": "open", "id": "123e4567-e89b-12d3-a456-426614174001", "due": "2000-01-01", "by": "e@f.io", "at": "2000-01-01T00:00:00Z"}
+            ]"#,
+        );
+        let property = |name: &str| &schema["properties"][name];
+        assert_eq!(
+            property("status")["enum"],
+            serde_json::json!(["closed", "open"])
+        );
+        assert_eq!(property("id")["format"], "uuid");
+        assert_eq!(property("due")["format"], "date");
+        assert_eq!(property("by")["format"], "email");
+        assert_eq!(property("at")["format"], "date-time");
+        assert!(property("id").get("enum").is_none());
+    }
+
+    #[test]
+    fn no_enum_or_format_without_evidence() {
+        let schema = schema_of(r#"["a", "b", "c"]"#);
+        assert_eq!(
+            schema,
+            serde_json::json!({"$schema": SCHEMA_DIALECT, "type": "string"})
+        );
+        let schema = schema_of(r#"["2024-01-01", "soon"]"#);
+        assert!(schema.get("format").is_none());
+        assert_eq!(
+            schema_of("[]"),
+            serde_json::json!({"$schema": SCHEMA_DIALECT})
+        );
+    }
+
+    #[test]
+    fn format_checks() {
+        assert!(is_date("2024-02-29"));
+        assert!(!is_date("2024-13-01"));
+        assert!(!is_date("24-01-01"));
+        assert!(is_date_time("2024-02-29T23:59:60.123Z"));
+        assert!(!is_date_time("2024-02-29 23:59:00Z"));
+        assert!(!is_date_time("2024-02-29T23:59Z"));
+        assert!(is_uuid("123E4567-E89B-12D3-A456-426614174000"));
+        assert!(!is_uuid("123e4567e89b12d3a456426614174000"));
+        assert!(is_email("first.last+tag@mail.example.com"));
+        assert!(!is_email("no at sign.com"));
+        assert!(!is_email("a@localhost"));
+    }
+
+    fn json() -> impl Strategy<Value = serde_json::Value> {
+        let leaf = prop_oneof![
+            Just(serde_json::Value::Null),
+            any::<bool>().prop_map(serde_json::Value::Bool),
+            any::<i64>().prop_map(|n| serde_json::json!(n)),
+            (-1e6f64..1e6).prop_map(|n| serde_json::json!(n)),
+            "[a-c]{0,3}|2024-0[1-9]-1[0-9]".prop_map(serde_json::Value::String),
+        ];
+        leaf.prop_recursive(3, 24, 4, |inner| {
+            prop_oneof![
+                proptest::collection::vec(inner.clone(), 0..4).prop_map(serde_json::Value::Array),
+                proptest::collection::btree_map("[a-d]", inner, 0..4)
+                    .prop_map(|map| serde_json::Value::Object(map.into_iter().collect())),
+            ]
+        })
+    }
+
+    proptest! {
+        // The inferred schema accepts every sample it was inferred from.
+        #[test]
+        fn samples_validate_against_their_schema(values in proptest::collection::vec(json(), 1..6)) {
+            let samples: Vec<Expr> = values.into_iter().map(Expr::from).collect();
+            let schema = infer_schema(&samples);
+            for sample in &samples {
+                prop_assert_eq!(validate(sample, &schema, None), Ok(vec![]));
+            }
+        }
+    }
+}
@@ -0,0 +1,1189 @@
+/*
+    JSONPath (RFC 9535) over Expr values.
+
+    query     := '$' segment*
+    segment   := '.' name | '.*' | '..' (name | '*' | bracket) | bracket
+    bracket   := '[' selector (',' selector)* ']'
+    selector  := 'name' | "name" | '*' | index | start:end:step | '?' filter
+
+    Filters support ==, !=, <, <=, >, >=, &&, ||, !, parentheses, existence tests on @ / $ paths,
+    and the functions length(), count(), match(), search() and value(). The Goessner form
+    [?(@.price > 10)] is just a parenthesised filter.
+*/
+
+use regex::Regex;
+
+use crate::{
+    command_interpreter::{number::Number, types::Expr},
+    errors::errors::JreplErr,
+    json::path::PathSegment,
+};
+
+/// Indices and slice bounds must lie in the I-JSON range of RFC 9535 §2.1.
+const MAX_SAFE_INT: i64 = (1 << 53) - 1;
+
+/// A match and the location it was found at.
+pub type Node<'a> = (Vec<PathSegment>, &'a Expr);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Or(Vec<Filter>),
+    And(Vec<Filter>),
+    Not(Box<Filter>),
+    Compare(Operand, CmpOp, Operand),
+    Test(Operand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Literal(Expr),
+    Current(Vec<Segment>),
+    Root(Vec<Segment>),
+    Function(String, Vec<Operand>),
+}
+
+impl Operand {
+    // RFC 9535 2.3.5.1: a query compared with something must select at most one node, so it may
+    // only use name and index selectors, one per segment. Functions must return a value (2.4.3),
+    // which rules out the logical match() and search().
+    fn is_comparable(&self) -> bool {
+        match self {
+            Operand::Current(segments) | Operand::Root(segments) => {
+                segments.iter().all(|segment| match segment {
+                    Segment::Child(selectors) => matches!(
+                        selectors.as_slice(),
+                        [Selector::Name(_) | Selector::Index(_)]
+                    ),
+                    Segment::Descendant(_) => false,
+                })
+            }
+            Operand::Function(name, _) => matches!(name.as_str(), "length" | "count" | "value"),
+            Operand::Literal(_) => true,
+        }
+    }
+}
+
+// ----------------------------------- Parser ----------------------------------- //
+
+pub fn parse_jsonpath(input: &str) -> Result<JsonPath, JreplErr> {
+    let mut parser = Parser {
+        input,
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+
+    parser.skip_ws();
+    parser.expect('$')?;
+    let segments = parser.segments()?;
+    parser.skip_ws();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.err("Unexpected trailing characters"));
+    }
+    Ok(JsonPath { segments })
+}
+
+struct Parser<'s> {
+    input: &'s str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn err(&self, msg: &str) -> JreplErr {
+        JreplErr::InvalidPath(format!(
+            "JSONPath \"{}\": {} at column {}.",
+            self.input,
+            msg,
+            self.pos + 1
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        let len = s.chars().count();
+        if self.chars[self.pos..]
+            .iter()
+            .take(len)
+            .copied()
+            .eq(s.chars())
+        {
+            self.pos += len;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), JreplErr> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.err(&format!("Expected '{}'", c)))
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn segments(&mut self) -> Result<Vec<Segment>, JreplErr> {
+        let mut segments = Vec::new();
+        loop {
+            let save = self.pos;
+            self.skip_ws();
+            match self.peek() {
+                Some('.') if self.peek_at(1) == Some('.') => {
+                    self.pos += 2;
+                    let selectors = match self.peek() {
+                        Some('[') => self.bracket()?,
+                        Some('*') => {
+                            self.pos += 1;
+                            vec![Selector::Wildcard]
+                        }
+                        _ => vec![Selector::Name(self.member_name()?)],
+                    };
+                    segments.push(Segment::Descendant(selectors));
+                }
+                Some('.') => {
+                    self.pos += 1;
+                    if self.eat('*') {
+                        segments.push(Segment::Child(vec![Selector::Wildcard]));
+                    } else {
+                        segments.push(Segment::Child(vec![Selector::Name(self.member_name()?)]));
+                    }
+                }
+                Some('[') => segments.push(Segment::Child(self.bracket()?)),
+                _ => {
+                    self.pos = save;
+                    return Ok(segments);
+                }
+            }
+        }
+    }
+
+    fn member_name(&mut self) -> Result<String, JreplErr> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' || c == '-' || !c.is_ascii() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        if self.pos == start || self.chars[start].is_ascii_digit() || self.chars[start] == '-' {
+            self.pos = start;
+            return Err(self.err("Expected a member name"));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn bracket(&mut self) -> Result<Vec<Selector>, JreplErr> {
+        self.expect('[')?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_ws();
+            selectors.push(self.selector()?);
+            self.skip_ws();
+            if self.eat(']') {
+                return Ok(selectors);
+            }
+            if !self.eat(',') {
+                return Err(self.err("Expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, JreplErr> {
+        match self.peek() {
+            Some('\'' | '"') => Ok(Selector::Name(self.string_literal()?)),
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.pos += 1;
+                self.skip_ws();
+                Ok(Selector::Filter(self.logical_or()?))
+            }
+            Some(c) if c == ':' || c == '-' || c.is_ascii_digit() => self.index_or_slice(),
+            _ => Err(self.err("Expected a selector")),
+        }
+    }
+
+    fn index_or_slice(&mut self) -> Result<Selector, JreplErr> {
+        let start = self.optional_int()?;
+        self.skip_ws();
+        if !self.eat(':') {
+            return match start {
+                Some(i) => Ok(Selector::Index(i)),
+                None => Err(self.err("Expected an index")),
+            };
+        }
+        self.skip_ws();
+        let end = self.optional_int()?;
+        self.skip_ws();
+        let step = if self.eat(':') {
+            self.skip_ws();
+            self.optional_int()?
+        } else {
+            None
+        };
+        Ok(Selector::Slice(start, end, step))
+    }
+
+    fn optional_int(&mut self) -> Result<Option<i64>, JreplErr> {
+        let start = self.pos;
+        self.eat('-');
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Ok(None);
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match text.parse::<i64>() {
+            Ok(i) if (-MAX_SAFE_INT..=MAX_SAFE_INT).contains(&i) => Ok(Some(i)),
+            _ => {
+                self.pos = start;
+                Err(self.err("Invalid integer"))
+            }
+        }
+    }
+
+    fn string_literal(&mut self) -> Result<String, JreplErr> {
+        let quote = self.peek().ok_or_else(|| self.err("Expected a string"))?;
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.err("Unterminated string")),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let escaped = self.peek().ok_or_else(|| self.err("Unterminated escape"))?;
+                    self.pos += 1;
+                    match escaped {
+                        'b' => out.push('\u{08}'),
+                        'f' => out.push('\u{0C}'),
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'u' => out.push(self.unicode_escape()?),
+                        '\\' | '/' | '\'' | '"' => out.push(escaped),
+                        _ => return Err(self.err("Invalid escape")),
+                    }
+                }
+                Some(c) => {
+                    out.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, JreplErr> {
+        let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+        if hex.len() != 4 {
+            return Err(self.err("Expected 4 hex digits"));
+        }
+        let code = u32::from_str_radix(&hex, 16).map_err(|_| self.err("Expected 4 hex digits"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, JreplErr> {
+        let high = self.hex4()?;
+        if (0xD800..0xDC00).contains(&high) {
+            if !self.eat_str("\\u") {
+                return Err(self.err("Expected a low surrogate"));
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.err("Invalid low surrogate"));
+            }
+            let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+            return char::from_u32(code).ok_or_else(|| self.err("Invalid unicode escape"));
+        }
+        char::from_u32(high).ok_or_else(|| self.err("Invalid unicode escape"))
+    }
+
+    fn logical_or(&mut self) -> Result<Filter, JreplErr> {
+        let mut terms = vec![self.logical_and()?];
+        loop {
+            self.skip_ws();
+            if !self.eat_str("||") {
+                break;
+            }
+            self.skip_ws();
+            terms.push(self.logical_and()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Filter::Or(terms)
+        })
+    }
+
+    fn logical_and(&mut self) -> Result<Filter, JreplErr> {
+        let mut terms = vec![self.basic()?];
+        loop {
+            self.skip_ws();
+            if !self.eat_str("&&") {
+                break;
+            }
+            self.skip_ws();
+            terms.push(self.basic()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Filter::And(terms)
+        })
+    }
+
+    fn basic(&mut self) -> Result<Filter, JreplErr> {
+        self.skip_ws();
+        if self.peek() == Some('!') && self.peek_at(1) != Some('=') {
+            self.pos += 1;
+            self.skip_ws();
+            return Ok(Filter::Not(Box::new(self.basic()?)));
+        }
+        if self.eat('(') {
+            let inner = self.logical_or()?;
+            self.skip_ws();
+            self.expect(')')?;
+            return Ok(inner);
+        }
+
+        let left_start = self.pos;
+        let left = self.operand()?;
+        self.skip_ws();
+        let op = if self.eat_str("==") {
+            CmpOp::Eq
+        } else if self.eat_str("!=") {
+            CmpOp::Ne
+        } else if self.eat_str("<=") {
+            CmpOp::Le
+        } else if self.eat_str(">=") {
+            CmpOp::Ge
+        } else if self.eat('<') {
+            CmpOp::Lt
+        } else if self.eat('>') {
+            CmpOp::Gt
+        } else {
+            return match left {
+                Operand::Literal(_) => Err(self.err("A literal cannot be used as a test")),
+                test => Ok(Filter::Test(test)),
+            };
+        };
+        self.skip_ws();
+        let right_start = self.pos;
+        let right = self.operand()?;
+        for (start, operand) in [(left_start, &left), (right_start, &right)] {
+            if !operand.is_comparable() {
+                self.pos = start;
+                return Err(match operand {
+                    Operand::Function(name, _) => self.err(&format!(
+                        "{}() returns a logical value and cannot be compared",
+                        name
+                    )),
+                    _ => self.err("Comparison operands must be singular queries"),
+                });
+            }
+        }
+        Ok(Filter::Compare(left, op, right))
+    }
+
+    fn operand(&mut self) -> Result<Operand, JreplErr> {
+        match self.peek() {
+            Some('@') => {
+                self.pos += 1;
+                Ok(Operand::Current(self.segments()?))
+            }
+            Some('$') => {
+                self.pos += 1;
+                Ok(Operand::Root(self.segments()?))
+            }
+            Some('\'' | '"') => Ok(Operand::Literal(Expr::String(self.string_literal()?))),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number_literal(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_') {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                match word.as_str() {
+                    "true" => Ok(Operand::Literal(Expr::Bool(true))),
+                    "false" => Ok(Operand::Literal(Expr::Bool(false))),
+                    "null" => Ok(Operand::Literal(Expr::None)),
+                    _ => self.function_call(word),
+                }
+            }
+            _ => Err(self.err("Expected a value, path or function")),
+        }
+    }
+
+    fn function_call(&mut self, name: String) -> Result<Operand, JreplErr> {
+        let arity = match name.as_str() {
+            "length" | "count" | "value" => 1,
+            "match" | "search" => 2,
+            _ => return Err(self.err(&format!("Unknown function '{}'", name))),
+        };
+        self.skip_ws();
+        self.expect('(')?;
+        let mut args = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.eat(')') {
+                break;
+            }
+            if !args.is_empty() {
+                self.expect(',')?;
+                self.skip_ws();
+            }
+            args.push(self.operand()?);
+        }
+        if args.len() != arity {
+            return Err(self.err(&format!(
+                "{}() expects {} argument(s), got {}",
+                name,
+                arity,
+                args.len()
+            )));
+        }
+        Ok(Operand::Function(name, args))
+    }
+
+    fn number_literal(&mut self) -> Result<Operand, JreplErr> {
+        let start = self.pos;
+        self.eat('-');
+        let digits = |p: &mut Self| {
+            while matches!(p.peek(), Some(c) if c.is_ascii_digit()) {
+                p.pos += 1;
+            }
+        };
+        digits(self);
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            digits(self);
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            digits(self);
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match Number::parse(&text) {
+            Some(n) => Ok(Operand::Literal(Expr::Number(n))),
+            None => {
+                self.pos = start;
+                Err(self.err("Invalid number"))
+            }
+        }
+    }
+}
+
+// --------------------------------- Evaluation --------------------------------- //
+
+impl JsonPath {
+    /// All nodes selected by the path, in document order, with their locations.
+    pub fn select<'a>(&self, root: &'a Expr) -> Result<Vec<Node<'a>>, JreplErr> {
+        select_segments(&self.segments, root, vec![(Vec::new(), root)])
+    }
+}
+
+fn select_segments<'a>(
+    segments: &[Segment],
+    root: &'a Expr,
+    start: Vec<Node<'a>>,
+) -> Result<Vec<Node<'a>>, JreplErr> {
+    segments.iter().try_fold(start, |nodes, segment| {
+        let mut out = Vec::new();
+        for (path, value) in nodes {
+            match segment {
+                Segment::Child(selectors) => {
+                    apply_selectors(selectors, root, &path, value, &mut out)?
+                }
+                Segment::Descendant(selectors) => {
+                    for (desc_path, desc) in descendants(path, value) {
+                        apply_selectors(selectors, root, &desc_path, desc, &mut out)?;
+                    }
+                }
+            }
+        }
+        Ok(out)
+    })
+}
+
+// The node itself followed by everything below it, depth first in document order.
+fn descendants(path: Vec<PathSegment>, value: &Expr) -> Vec<Node<'_>> {
+    let mut out = Vec::new();
+    let mut stack = vec![(path, value)];
+    while let Some((path, value)) = stack.pop() {
+        let children = children(&path, value);
+        out.push((path, value));
+        stack.extend(children.into_iter().rev());
+    }
+    out
+}
+
+fn children<'a>(path: &[PathSegment], value: &'a Expr) -> Vec<Node<'a>> {
+    match value {
+        Expr::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (child_path(path, PathSegment::Index(i)), item))
+            .collect(),
+        Expr::Object(map) => map
+            .iter()
+            .map(|(k, v)| (child_path(path, PathSegment::Key(k.clone())), v))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn child_path(path: &[PathSegment], segment: PathSegment) -> Vec<PathSegment> {
+    let mut out = path.to_vec();
+    out.push(segment);
+    out
+}
+
+fn apply_selectors<'a>(
+    selectors: &[Selector],
+    root: &'a Expr,
+    path: &[PathSegment],
+    value: &'a Expr,
+    out: &mut Vec<Node<'a>>,
+) -> Result<(), JreplErr> {
+    for selector in selectors {
+        match (selector, value) {
+            (Selector::Name(name), Expr::Object(map)) => {
+                if let Some(v) = map.get(name) {
+                    out.push((child_path(path, PathSegment::Key(name.clone())), v));
+                }
+            }
+            (Selector::Wildcard, _) => out.extend(children(path, value)),
+            (Selector::Index(i), Expr::Array(items)) => {
+                if let Some(idx) = normalize_index(*i, items.len()) {
+                    out.push((child_path(path, PathSegment::Index(idx)), &items[idx]));
+                }
+            }
+            (Selector::Slice(start, end, step), Expr::Array(items)) => {
+                for idx in slice_indices(*start, *end, *step, items.len()) {
+                    out.push((child_path(path, PathSegment::Index(idx)), &items[idx]));
+                }
+            }
+            (Selector::Filter(filter), Expr::Array(_) | Expr::Object(_)) => {
+                for (child_path, child) in children(path, value) {
+                    if test_filter(filter, root, child)? {
+                        out.push((child_path, child));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn normalize_index(i: i64, len: usize) -> Option<usize> {
+    let idx = if i < 0 { len as i64 + i } else { i };
+    (0..len as i64).contains(&idx).then_some(idx as usize)
+}
+
+// RFC 9535 section 2.3.4.2.2
+fn slice_indices(
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    len: usize,
+) -> Vec<usize> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    if step == 0 {
+        return Vec::new();
+    }
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+
+    let mut out = Vec::new();
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+        let mut i = lower;
+        while i < upper {
+            out.push(i as usize);
+            i = match i.checked_add(step) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    } else {
+        let upper = normalize(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+        let lower = end.map(normalize).unwrap_or(-len - 1).clamp(-1, len - 1);
+        let mut i = upper;
+        while lower < i {
+            out.push(i as usize);
+            i = match i.checked_add(step) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+    out
+}
+
+/// The result of evaluating an operand: a list of nodes, or a single computed value.
+enum Evaluated<'a> {
+    Nodes(Vec<&'a Expr>),
+    Value(Option<Expr>),
+    Logical(bool),
+}
+
+impl Evaluated<'_> {
+    // A single value, or Nothing, for comparisons and function arguments.
+    fn singular(&self) -> Option<Expr> {
+        match self {
+            Evaluated::Nodes(nodes) if nodes.len() == 1 => Some(nodes[0].clone()),
+            Evaluated::Nodes(_) => None,
+            Evaluated::Value(v) => v.clone(),
+            Evaluated::Logical(b) => Some(Expr::Bool(*b)),
+        }
+    }
+}
+
+fn test_filter(filter: &Filter, root: &Expr, current: &Expr) -> Result<bool, JreplErr> {
+    match filter {
+        Filter::Or(terms) => {
+            for t in terms {
+                if test_filter(t, root, current)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        Filter::And(terms) => {
+            for t in terms {
+                if !test_filter(t, root, current)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        Filter::Not(inner) => Ok(!test_filter(inner, root, current)?),
+        Filter::Test(operand) => Ok(match eval_operand(operand, root, current)? {
+            Evaluated::Nodes(nodes) => !nodes.is_empty(),
+            Evaluated::Logical(b) => b,
+            Evaluated::Value(v) => v.is_some(),
+        }),
+        Filter::Compare(left, op, right) => {
+            let l = eval_operand(left, root, current)?.singular();
+            let r = eval_operand(right, root, current)?.singular();
+            Ok(compare(l.as_ref(), *op, r.as_ref()))
+        }
+    }
+}
+
+fn eval_operand<'a>(
+    operand: &Operand,
+    root: &'a Expr,
+    current: &'a Expr,
+) -> Result<Evaluated<'a>, JreplErr> {
+    match operand {
+        Operand::Literal(v) => Ok(Evaluated::Value(Some(v.clone()))),
+        Operand::Current(segments) => {
+            let nodes = select_segments(segments, root, vec![(Vec::new(), current)])?;
+            Ok(Evaluated::Nodes(
+                nodes.into_iter().map(|(_, v)| v).collect(),
+            ))
+        }
+        Operand::Root(segments) => {
+            let nodes = select_segments(segments, root, vec![(Vec::new(), root)])?;
+            Ok(Evaluated::Nodes(
+                nodes.into_iter().map(|(_, v)| v).collect(),
+            ))
+        }
+        Operand::Function(name, args) => {
+            let args = args
+                .iter()
+                .map(|a| eval_operand(a, root, current))
+                .collect::<Result<Vec<_>, _>>()?;
+            call_function(name, &args)
+        }
+    }
+}
+
+fn call_function<'a>(name: &str, args: &[Evaluated<'a>]) -> Result<Evaluated<'a>, JreplErr> {
+    match name {
+        "length" => Ok(Evaluated::Value(match args[0].singular() {
+            Some(Expr::String(s)) => Some(Expr::Number(s.chars().count().into())),
+            Some(Expr::Array(items)) => Some(Expr::Number(items.len().into())),
+            Some(Expr::Object(map)) => Some(Expr::Number(map.len().into())),
+            _ => None,
+        })),
+        "count" => match &args[0] {
+            Evaluated::Nodes(nodes) => Ok(Evaluated::Value(Some(Expr::Number(nodes.len().into())))),
+            _ => Err(JreplErr::InvalidPath(
+                "count() expects a path argument.".to_string(),
+            )),
+        },
+        "value" => match &args[0] {
+            Evaluated::Nodes(_) => Ok(Evaluated::Value(args[0].singular())),
+            _ => Err(JreplErr::InvalidPath(
+                "value() expects a path argument.".to_string(),
+            )),
+        },
+        "match" | "search" => {
+            let (Some(Expr::String(text)), Some(Expr::String(pattern))) =
+                (args[0].singular(), args[1].singular())
+            else {
+                return Ok(Evaluated::Logical(false));
+            };
+            let pattern = if name == "match" {
+                format!("^(?:{})$", pattern)
+            } else {
+                pattern
+            };
+            let re = Regex::new(&pattern).map_err(|e| {
+                JreplErr::InvalidPath(format!("Invalid regex in {}(): {}", name, e))
+            })?;
+            Ok(Evaluated::Logical(re.is_match(&text)))
+        }
+        _ => unreachable!("functions are checked by the parser"),
+    }
+}
+
+fn compare(left: Option<&Expr>, op: CmpOp, right: Option<&Expr>) -> bool {
+    match op {
+        CmpOp::Eq => equal(left, right),
+        CmpOp::Ne => !equal(left, right),
+        CmpOp::Lt => less(left, right),
+        CmpOp::Gt => less(right, left),
+        CmpOp::Le => less(left, right) || equal(left, right),
+        CmpOp::Ge => less(right, left) || equal(left, right),
+    }
+}
+
+fn equal(left: Option<&Expr>, right: Option<&Expr>) -> bool {
+    match (left, right) {
+        (None, None) => true,
+        (Some(l), Some(r)) => l == r,
+        _ => false,
+    }
+}
+
+fn less(left: Option<&Expr>, right: Option<&Expr>) -> bool {
+    match (left, right) {
+        (Some(Expr::Number(l)), Some(Expr::Number(r))) => l < r,
+        (Some(Expr::String(l)), Some(Expr::String(r))) => l < r,
+        _ => false,
+    }
+}
+
+/// Renders a location as an RFC 9535 normalized path, e.g. `$['store']['book'][0]`.
+pub fn to_normalized_path(path: &[PathSegment]) -> String {
+    let mut out = String::from("$");
+    for segment in path {
+        match segment {
+            PathSegment::Index(i) => out.push_str(&format!("[{}]", i)),
+            PathSegment::Key(k) => {
+                out.push_str("['");
+                for c in k.chars() {
+                    match c {
+                        '\'' => out.push_str("\\'"),
+                        '\\' => out.push_str("\\\\"),
+                        '\u{08}' => out.push_str("\\b"),
+                        '\u{0C}' => out.push_str("\\f"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => out.push(c),
+                    }
+                }
+                out.push_str("']");
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json::convert::parse_json;
+
+    fn fixture(name: &str) -> Expr {
+        parse_json(&std::fs::read(format!("dev/{}", name)).unwrap()).unwrap()
+    }
+
+    fn values(doc: &Expr, path: &str) -> Vec<serde_json::Value> {
+        parse_jsonpath(path)
+            .unwrap_or_else(|e| panic!("{}: {:?}", path, e))
+            .select(doc)
+            .unwrap()
+            .into_iter()
+            .map(|(_, v)| serde_json::Value::try_from(v).unwrap())
+            .collect()
+    }
+
+    fn paths(doc: &Expr, path: &str) -> Vec<String> {
+        parse_jsonpath(path)
+            .unwrap()
+            .select(doc)
+            .unwrap()
+            .into_iter()
+            .map(|(p, _)| to_normalized_path(&p))
+            .collect()
+    }
+
+    // (fixture, query, expected values)
+    fn conformance_cases() -> Vec<(&'static str, &'static str, serde_json::Value)> {
+        use serde_json::json;
+        vec![
+            // root and child segments
+            (
+                "simple_object.json",
+                "$",
+                json!([{"name": "Alice", "age": 30, "active": true}]),
+            ),
+            ("simple_object.json", "$.name", json!(["Alice"])),
+            ("simple_object.json", "$['age']", json!([30])),
+            ("simple_object.json", "$[\"active\"]", json!([true])),
+            ("simple_object.json", "$.missing", json!([])),
+            (
+                "simple_object.json",
+                "$['name','age']",
+                json!(["Alice", 30]),
+            ),
+            (
+                "nested_object.json",
+                "$.user.profile.username",
+                json!(["alice42"]),
+            ),
+            (
+                "deeply_nested.json",
+                "$.config.window.theme.*",
+                json!(["#ffffff", "#222222"]),
+            ),
+            (
+                "deeply_nested.json",
+                "$.config.window[*]",
+                json!([800, 600, {"background": "#ffffff", "foreground": "#222222"}]),
+            ),
+            // descendants
+            (
+                "deeply_nested.json",
+                "$..theme.*",
+                json!(["#ffffff", "#222222"]),
+            ),
+            ("deeply_nested.json", "$..background", json!(["#ffffff"])),
+            ("nested_object.json", "$..id", json!([42])),
+            ("nested_object.json", "$..[0]", json!(["admin"])),
+            ("array_of_objects.json", "$..name", json!(["Bob", "Carol"])),
+            (
+                "mixed_types.json",
+                "$..*",
+                json!([
+                    1,
+                    ["rust", "json", "cli"],
+                    null,
+                    99.5,
+                    "rust",
+                    "json",
+                    "cli"
+                ]),
+            ),
+            // indices and slices
+            ("mixed_types.json", "$.tags[0]", json!(["rust"])),
+            ("mixed_types.json", "$.tags[-1]", json!(["cli"])),
+            ("mixed_types.json", "$.tags[5]", json!([])),
+            ("mixed_types.json", "$.tags[0,2]", json!(["rust", "cli"])),
+            ("mixed_types.json", "$.tags[0:2]", json!(["rust", "json"])),
+            ("mixed_types.json", "$.tags[1:]", json!(["json", "cli"])),
+            ("mixed_types.json", "$.tags[:-1]", json!(["rust", "json"])),
+            ("mixed_types.json", "$.tags[::2]", json!(["rust", "cli"])),
+            (
+                "mixed_types.json",
+                "$.tags[::-1]",
+                json!(["cli", "json", "rust"]),
+            ),
+            ("mixed_types.json", "$.tags[0:3:0]", json!([])),
+            ("mixed_types.json", "$.tags[-2:]", json!(["json", "cli"])),
+            // filters
+            (
+                "array_of_objects.json",
+                "$.users[?(@.age > 26)].name",
+                json!(["Carol"]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?@.age <= 25].name",
+                json!(["Bob"]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?(@.name == 'Bob')].age",
+                json!([25]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?(@.name != \"Bob\")].age",
+                json!([28]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?(@.age > 20 && @.age < 27)].name",
+                json!(["Bob"]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?(@.age < 20 || @.name == 'Carol')].name",
+                json!(["Carol"]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?!(@.age > 26)].name",
+                json!(["Bob"]),
+            ),
+            ("array_of_objects.json", "$.users[?@.email].name", json!([])),
+            (
+                "array_of_objects.json",
+                "$.users[?@.name].age",
+                json!([25, 28]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?match(@.name, 'C.*')].name",
+                json!(["Carol"]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?search(@.name, 'o')].name",
+                json!(["Bob", "Carol"]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?length(@.name) == 3].name",
+                json!(["Bob"]),
+            ),
+            (
+                "array_of_objects.json",
+                "$[?count(@[*]) == 2]",
+                json!([[{"name": "Bob", "age": 25}, {"name": "Carol", "age": 28}]]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?@.age == $.users[0].age].name",
+                json!(["Bob"]),
+            ),
+            (
+                "array_of_objects.json",
+                "$.users[?@['age'] < $['users'][-1].age].name",
+                json!(["Bob"]),
+            ),
+            ("mixed_types.json", "$[?@ == null]", json!([null])),
+            (
+                "mixed_types.json",
+                "$.tags[?@ >= 'json']",
+                json!(["rust", "json"]),
+            ),
+            ("mixed_types.json", "$[?@ > 50]", json!([99.5])),
+            ("mixed_types.json", "$[?value(@..json) == 1]", json!([])),
+            (
+                "deeply_nested.json",
+                "$..[?@.width > 100].height",
+                json!([600]),
+            ),
+        ]
+    }
+
+    #[test]
+    fn jsonpath_conformance_over_dev_fixtures() {
+        for (file, query, expected) in conformance_cases() {
+            let doc = fixture(file);
+            assert_eq!(
+                serde_json::Value::Array(values(&doc, query)),
+                expected,
+                "{} on {}",
+                query,
+                file
+            );
+        }
+    }
+
+    #[test]
+    fn jsonpath_normalized_paths() {
+        let doc = fixture("array_of_objects.json");
+        assert_eq!(
+            paths(&doc, "$..name"),
+            vec!["$['users'][0]['name']", "$['users'][1]['name']"]
+        );
+        assert_eq!(paths(&doc, "$"), vec!["$"]);
+        assert_eq!(
+            to_normalized_path(&[PathSegment::Key("it's\n".into())]),
+            "$['it\\'s\\n']"
+        );
+    }
+
+    #[test]
+    fn jsonpath_string_escapes_and_unicode() {
+        let doc = parse_json("{\"a'b\": 1, \"\u{1F600}\": 2, \"é\": 3}".as_bytes()).unwrap();
+        assert_eq!(values(&doc, r"$['a\'b']"), vec![serde_json::json!(1)]);
+        assert_eq!(values(&doc, r"$['😀']"), vec![serde_json::json!(2)]);
+        assert_eq!(values(&doc, "$.é"), vec![serde_json::json!(3)]);
+    }
+
+    #[test]
+    fn jsonpath_rejects_malformed_queries() {
+        for bad in [
+            "",
+            "name",
+            "$.",
+            "$[",
+            "$[1",
+            "$['a'",
+            "$[?@.a ==]",
+            "$[?foo(@)]",
+            "$[?length(@, @)]",
+            "$.a b",
+            "$[?(@.a]",
+            "$[?'x']",
+            "$..",
+            "$[1.5]",
+            "$[?@.* == @.*]",
+            "$[?@..x == 1]",
+            "$[?@[0:2] == 1]",
+            "$[?1 < @['a','b']]",
+            "$[?@[*].a == $.b]",
+            "$[?@.a == $..b]",
+        ] {
+            assert!(
+                matches!(parse_jsonpath(bad), Err(JreplErr::InvalidPath(_))),
+                "expected an error for {:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn jsonpath_slices_with_extreme_bounds() {
+        let doc = parse_json(b"[1, 2, 3]").unwrap();
+        assert_eq!(
+            values(&doc, "$[1::9007199254740991]"),
+            vec![serde_json::json!(2)]
+        );
+        assert_eq!(
+            values(&doc, "$[::-9007199254740991]"),
+            vec![serde_json::json!(3)]
+        );
+        assert_eq!(
+            values(&doc, "$[-9007199254740991:9007199254740991]"),
+            vec![
+                serde_json::json!(1),
+                serde_json::json!(2),
+                serde_json::json!(3)
+            ]
+        );
+        for bad in [
+            "$[1::9223372036854775807]",
+            "$[9007199254740992:]",
+            "$[:-9007199254740992]",
+            "$[9007199254740992]",
+        ] {
+            assert!(
+                matches!(parse_jsonpath(bad), Err(JreplErr::InvalidPath(_))),
+                "expected an error for {:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn jsonpath_error_reports_column() {
+        match parse_jsonpath("$.a[?@.b >]") {
+            Err(JreplErr::InvalidPath(msg)) => assert!(msg.contains("column 11"), "{}", msg),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn jsonpath_comparisons_need_singular_queries() {
+        match parse_jsonpath("$[?@.a == @.b[*]]") {
+            Err(JreplErr::InvalidPath(msg)) => assert_eq!(
+                msg,
+                "JSONPath \"$[?@.a == @.b[*]]\": Comparison operands must be singular queries at column 11."
+            ),
+            other => panic!("{:?}", other),
+        }
+        match parse_jsonpath("$[?match(@.a, \"x\") == true]") {
+            Err(JreplErr::InvalidPath(msg)) => assert_eq!(
+                msg,
+                "JSONPath \"$[?match(@.a, \"x\") == true]\": match() returns a logical value and cannot be compared at column 4."
+            ),
+            other => panic!("{:?}", other),
+        }
+        assert!(parse_jsonpath("$[?true != search(@, 'a')]").is_err());
+        for ok in [
+            "$[?@.a == @['b'][0]]",
+            "$[?$[-1].x != @]",
+            "$[?@.* && @.a > 1]",
+            "$[?count(@.*) == 2]",
+            "$[?length(@.a) > value(@.b)]",
+        ] {
+            assert!(parse_jsonpath(ok).is_ok(), "expected {:?} to parse", ok);
+        }
+    }
+}
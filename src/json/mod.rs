@@ -0,0 +1,9 @@
+pub mod convert;
+pub mod diff;
+pub mod edit;
+pub mod infer;
+pub mod jsonpath;
+pub mod patch;
+pub mod path;
+pub mod pretty;
+pub mod schema;
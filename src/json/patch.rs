@@ -0,0 +1,366 @@
+/*
+    JSON Patch (RFC 6902) and JSON Merge Patch (RFC 7396).
+
+    Both are built on the immutable edits in `edit`, so applying a patch returns a new document
+    and shares every subtree the patch does not touch with the input.
+
+    A JSON Patch is all or nothing: the operations apply one after the other, each to the
+    document the previous ones left, and if one fails the whole patch fails with a `PatchErr`
+    that names the operation by its index. Members of an operation the RFC does not define are
+    ignored.
+*/
+
+use crate::{
+    command_interpreter::types::{Expr, JsonObject},
+    errors::errors::JreplErr,
+    json::{
+        edit::{dissoc_in, update_in},
+        path::{PathSegment, describe_location, get_in, lookup_err, parse_pointer, type_name},
+        pretty::{PrettyOptions, render},
+    },
+    statics::logic_commands::values_equal,
+};
+
+/// Applies the RFC 6902 operations in `ops` to `doc`, in order.
+pub fn apply_patch(doc: &Expr, ops: &[Expr]) -> Result<Expr, JreplErr> {
+    ops.iter()
+        .enumerate()
+        .try_fold(doc.clone(), |doc, (index, op)| {
+            apply_op(&doc, op).map_err(|err| {
+                JreplErr::PatchErr(
+                    index,
+                    format!(
+                        "Patch operation {} ({}) failed: {}",
+                        index,
+                        describe(op),
+                        err.message()
+                    ),
+                )
+            })
+        })
+}
+
+fn apply_op(doc: &Expr, op: &Expr) -> Result<Expr, JreplErr> {
+    let Expr::Object(members) = op else {
+        return Err(JreplErr::JsonErr(format!(
+            "An operation must be an object, got {}.",
+            type_name(op)
+        )));
+    };
+    let path = pointer_member(members, "path")?;
+    match string_member(members, "op")? {
+        "add" => add(doc, &path, value_member(members)?.clone()),
+        "remove" => dissoc_in(doc, &path),
+        "replace" => {
+            get_in(doc, &path)?;
+            let value = value_member(members)?.clone();
+            update_in(doc, &path, |_| Ok(value))
+        }
+        "move" => {
+            let from = pointer_member(members, "from")?;
+            let value = get_in(doc, &from)?.clone();
+            if from == path {
+                return Ok(doc.clone());
+            }
+            if path.starts_with(&from) {
+                return Err(JreplErr::InvalidPath(format!(
+                    "Cannot move {} into its own child {}.",
+                    describe_location(&from),
+                    describe_location(&path)
+                )));
+            }
+            add(&dissoc_in(doc, &from)?, &path, value)
+        }
+        "copy" => {
+            let from = pointer_member(members, "from")?;
+            add(doc, &path, get_in(doc, &from)?.clone())
+        }
+        "test" => {
+            let actual = get_in(doc, &path)?;
+            let expected = value_member(members)?;
+            if values_equal(actual, expected) {
+                Ok(doc.clone())
+            } else {
+                Err(JreplErr::JsonErr(format!(
+                    "Test failed, the value at {} is {}, not {}.",
+                    describe_location(&path),
+                    compact(actual),
+                    compact(expected)
+                )))
+            }
+        }
+        other => Err(JreplErr::JsonErr(format!(
+            "Unknown op \"{}\", expected add, remove, replace, move, copy or test.",
+            other
+        ))),
+    }
+}
+
+/// Adds `value` at `path`: an object member is set, replacing any existing one, and an array
+/// element is inserted before the given index, or appended for "-". The parent must exist.
+fn add(doc: &Expr, path: &[PathSegment], value: Expr) -> Result<Expr, JreplErr> {
+    let Some((last, parent)) = path.split_last() else {
+        return Ok(value);
+    };
+    update_in(doc, parent, |container| match container {
+        Expr::Object(map) => {
+            let mut map = JsonObject::clone(map);
+            map.insert(last.as_key(), value);
+            Ok(Expr::object(map))
+        }
+        Expr::Array(items) => {
+            let index = match last.as_key().as_str() {
+                "-" => items.len(),
+                _ => match last.as_index() {
+                    Some(i) if i <= items.len() => i,
+                    _ => return Err(lookup_err(container, parent, last)),
+                },
+            };
+            let mut items = Vec::clone(items);
+            items.insert(index, value);
+            Ok(Expr::array(items))
+        }
+        other => Err(lookup_err(other, parent, last)),
+    })
+}
+
+fn string_member<'a>(members: &'a JsonObject, name: &str) -> Result<&'a str, JreplErr> {
+    match members.get(name) {
+        Some(Expr::String(s)) => Ok(s),
+        Some(other) => Err(JreplErr::JsonErr(format!(
+            "The \"{}\" member must be a string, got {}.",
+            name,
+            type_name(other)
+        ))),
+        None => Err(JreplErr::JsonErr(format!(
+            "The operation has no \"{}\" member.",
+            name
+        ))),
+    }
+}
+
+fn pointer_member(members: &JsonObject, name: &str) -> Result<Vec<PathSegment>, JreplErr> {
+    parse_pointer(string_member(members, name)?)
+}
+
+fn value_member(members: &JsonObject) -> Result<&Expr, JreplErr> {
+    members
+        .get("value")
+        .ok_or_else(|| JreplErr::JsonErr("The operation has no \"value\" member.".to_string()))
+}
+
+// "replace /a/b" or "move /a -> /b", for naming an operation in errors.
+fn describe(op: &Expr) -> String {
+    let Expr::Object(members) = op else {
+        return compact(op);
+    };
+    let member = |name: &str| match members.get(name) {
+        Some(Expr::String(s)) => Some(s.as_str()),
+        _ => None,
+    };
+    match (member("op"), member("from"), member("path")) {
+        (Some(op), Some(from), Some(path)) if op == "move" || op == "copy" => {
+            format!("{} {} -> {}", op, from, path)
+        }
+        (Some(op), _, Some(path)) => format!("{} {}", op, path),
+        _ => compact(op),
+    }
+}
+
+fn compact(value: &Expr) -> String {
+    render(
+        value,
+        &PrettyOptions {
+            compact: true,
+            ..PrettyOptions::default()
+        },
+    )
+}
+
+/// Applies an RFC 7396 merge patch: each member of an object patch is merged into the target's
+/// member of the same name, a null member removes it, and any patch that is not an object
+/// replaces the target outright. Merging never fails.
+pub fn merge_patch(target: &Expr, patch: &Expr) -> Expr {
+    let Expr::Object(patch) = patch else {
+        return patch.clone();
+    };
+    let mut merged = match target {
+        Expr::Object(map) => JsonObject::clone(map),
+        _ => JsonObject::new(),
+    };
+    for (key, value) in patch.iter() {
+        match value {
+            Expr::None => {
+                merged.shift_remove(key);
+            }
+            value => {
+                let current = merged.get(key).cloned().unwrap_or(Expr::None);
+                merged.insert(key.clone(), merge_patch(&current, value));
+            }
+        }
+    }
+    Expr::object(merged)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::json::{convert::parse_json, diff::Change, diff::diff};
+
+    fn doc(text: &str) -> Expr {
+        parse_json(text.as_bytes()).unwrap()
+    }
+
+    fn json(value: &Expr) -> serde_json::Value {
+        serde_json::Value::try_from(value).unwrap()
+    }
+
+    // The cases of an RFC appendix, in the format of the json-patch-tests suite.
+    fn cases(file: &str) -> Vec<JsonObject> {
+        let Expr::Array(cases) = parse_json(&fs::read(file).unwrap()).unwrap() else {
+            panic!("{} is not an array of cases", file);
+        };
+        cases
+            .iter()
+            .map(|case| match case {
+                Expr::Object(case) => JsonObject::clone(case),
+                other => panic!("case is not an object: {:?}", other),
+            })
+            .filter(|case| case.get("disabled") != Some(&Expr::Bool(true)))
+            .collect()
+    }
+
+    fn comment(case: &JsonObject) -> String {
+        compact(case.get("comment").unwrap())
+    }
+
+    #[test]
+    fn rfc6902_appendix_a() {
+        let cases = cases("dev/fixtures/rfc6902_appendix_a.json");
+        assert_eq!(cases.len(), 15);
+        for case in cases {
+            let Some(Expr::Array(ops)) = case.get("patch") else {
+                panic!("{} has no patch", comment(&case));
+            };
+            let result = apply_patch(case.get("doc").unwrap(), ops);
+            match (case.get("expected"), case.get("error_op")) {
+                (Some(expected), _) => {
+                    assert_eq!(json(&result.unwrap()), json(expected), "{}", comment(&case))
+                }
+                (None, Some(Expr::Number(op))) => {
+                    let op = op.to_string().parse::<usize>().unwrap();
+                    assert!(
+                        matches!(result, Err(JreplErr::PatchErr(i, _)) if i == op),
+                        "{}: {:?}",
+                        comment(&case),
+                        result
+                    )
+                }
+                _ => panic!("{} has neither expected nor error_op", comment(&case)),
+            }
+        }
+    }
+
+    #[test]
+    fn rfc7396_appendix_a() {
+        let cases = cases("dev/fixtures/rfc7396_appendix_a.json");
+        assert_eq!(cases.len(), 16);
+        for case in cases {
+            let merged = merge_patch(case.get("doc").unwrap(), case.get("patch").unwrap());
+            assert_eq!(
+                json(&merged),
+                json(case.get("expected").unwrap()),
+                "{}",
+                comment(&case)
+            );
+        }
+    }
+
+    #[test]
+    fn errors_name_the_failing_operation() {
+        let target = doc(r#"{"a": [1, 2], "b": {"c": true}}"#);
+        let patch = |ops: &str| match doc(ops) {
+            Expr::Array(ops) => apply_patch(&target, &ops),
+            _ => unreachable!(),
+        };
+
+        let err = patch(
+            r#"[{"op": "add", "path": "/a/-", "value": 3},
+                {"op": "test", "path": "/a/2", "value": 3},
+                {"op": "remove", "path": "/a/5"}]"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, JreplErr::PatchErr(2, _)));
+        assert_eq!(
+            err.message(),
+            "Patch operation 2 (remove /a/5) failed: Index 5 is out of bounds for the array of length 3 at /a."
+        );
+
+        let err = patch(r#"[{"op": "test", "path": "/b", "value": {"c": false}}]"#).unwrap_err();
+        assert_eq!(
+            err.message(),
+            r#"Patch operation 0 (test /b) failed: Test failed, the value at /b is {"c":true}, not {"c":false}."#
+        );
+
+        for (ops, index) in [
+            (r#"[{"op": "frobnicate", "path": "/a"}]"#, 0),
+            (
+                r#"[{"op": "test", "path": "/a", "value": [1, 2]}, {"op": "add"}]"#,
+                1,
+            ),
+            (r#"[{"op": "add", "path": "a", "value": 1}]"#, 0),
+            (r#"[{"op": "add", "path": "/a/01", "value": 1}]"#, 0),
+            (r#"[{"op": "add", "path": "/a/3", "value": 1}]"#, 0),
+            (r#"[{"op": "replace", "path": "/x", "value": 1}]"#, 0),
+            (r#"[{"op": "move", "from": "/b", "path": "/b/c/d"}]"#, 0),
+            (r#"[{"op": "copy", "from": "/missing", "path": "/x"}]"#, 0),
+            (r#"[1]"#, 0),
+        ] {
+            assert!(
+                matches!(patch(ops), Err(JreplErr::PatchErr(i, _)) if i == index),
+                "{}",
+                ops
+            );
+        }
+    }
+
+    #[test]
+    fn moves_copies_and_the_root() {
+        let target = doc(r#"{"a": {"b": 1}, "list": [1, 2, 3]}"#);
+        let ops = doc(r#"[{"op": "copy", "from": "/a", "path": "/list/0"},
+                {"op": "move", "from": "/a/b", "path": "/a/b"},
+                {"op": "move", "from": "/list/3", "path": "/list/1"},
+                {"op": "replace", "path": "/a/b", "value": null}]"#);
+        let Expr::Array(ops) = ops else {
+            unreachable!()
+        };
+        assert_eq!(
+            json(&apply_patch(&target, &ops).unwrap()),
+            json(&doc(r#"{"a": {"b": null}, "list": [{"b": 1}, 3, 1, 2]}"#))
+        );
+
+        let ops = doc(r#"[{"op": "replace", "path": "", "value": [true]}]"#);
+        let Expr::Array(ops) = ops else {
+            unreachable!()
+        };
+        assert_eq!(
+            json(&apply_patch(&target, &ops).unwrap()),
+            json(&doc("[true]"))
+        );
+    }
+
+    #[test]
+    fn applying_a_diff_reproduces_the_new_document() {
+        let old = doc(r#"{"users": [{"id": 1, "n": "a"}, {"id": 2}, {"id": 3}], "x": {"y": 1}}"#);
+        let new = doc(r#"{"users": [{"id": 3}, {"id": 1, "n": "b"}, {"id": 4}], "z": [1]}"#);
+        for key in [None, Some("id")] {
+            let ops: Vec<Expr> = diff(&old, &new, key)
+                .iter()
+                .map(Change::to_patch_op)
+                .collect();
+            assert_eq!(json(&apply_patch(&old, &ops).unwrap()), json(&new));
+        }
+    }
+}
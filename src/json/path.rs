@@ -0,0 +1,403 @@
+use std::fmt;
+
+use crate::{command_interpreter::types::Expr, errors::errors::JreplErr};
+
+/// One step into a JSON value. Keys that look like indices still work on arrays, and the other
+/// way round, so `"/items/0"` and `items[0]` resolve the same.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Key(k) => write!(f, "{}", k),
+            PathSegment::Index(i) => write!(f, "{}", i),
+        }
+    }
+}
+
+impl PathSegment {
+    pub fn as_index(&self) -> Option<usize> {
+        match self {
+            PathSegment::Index(i) => Some(*i),
+            PathSegment::Key(k) => parse_array_index(k),
+        }
+    }
+
+    pub fn as_key(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Renders a path as an RFC 6901 JSON Pointer.
+pub fn to_pointer(path: &[PathSegment]) -> String {
+    path.iter()
+        .map(|seg| format!("/{}", seg.as_key().replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// Parses an RFC 6901 JSON Pointer. The empty string is the whole document.
+pub fn parse_pointer(pointer: &str) -> Result<Vec<PathSegment>, JreplErr> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rest = pointer.strip_prefix('/').ok_or_else(|| {
+        JreplErr::InvalidPath(format!(
+            "JSON Pointer must be empty or start with '/', got \"{}\".",
+            pointer
+        ))
+    })?;
+
+    rest.split('/')
+        .map(|token| {
+            let bad_escape = token
+                .match_indices('~')
+                .any(|(pos, _)| !matches!(token.as_bytes().get(pos + 1), Some(b'0') | Some(b'1')));
+            if bad_escape {
+                return Err(JreplErr::InvalidPath(format!(
+                    "Invalid escape in JSON Pointer token \"{}\", '~' must be followed by 0 or 1.",
+                    token
+                )));
+            }
+            Ok(PathSegment::Key(
+                token.replace("~1", "/").replace("~0", "~"),
+            ))
+        })
+        .collect()
+}
+
+/// Parses a dotted path with optional brackets: `config.window.theme["background"]`, `items[0].name`.
+/// A leading `$` or `.` is accepted and ignored.
+pub fn parse_dotted(path: &str) -> Result<Vec<PathSegment>, JreplErr> {
+    let chars: Vec<char> = path.chars().collect();
+    let mut segments = Vec::new();
+    let mut i = 0;
+
+    if chars.first() == Some(&'$') {
+        i += 1;
+    }
+
+    let invalid = |msg: String| JreplErr::InvalidPath(format!("{} in path \"{}\".", msg, path));
+
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                i += 1;
+                if i >= chars.len() || chars[i] == '.' || chars[i] == '[' {
+                    return Err(invalid(format!("Expected a key after '.' at column {}", i)));
+                }
+            }
+            '[' => {
+                i += 1;
+                match chars.get(i) {
+                    Some(&quote) if quote == '"' || quote == '\'' => {
+                        i += 1;
+                        let mut key = String::new();
+                        loop {
+                            match chars.get(i) {
+                                Some('\\') if chars.get(i + 1).is_some() => {
+                                    key.push(chars[i + 1]);
+                                    i += 2;
+                                }
+                                Some(&c) if c == quote => {
+                                    i += 1;
+                                    break;
+                                }
+                                Some(&c) => {
+                                    key.push(c);
+                                    i += 1;
+                                }
+                                None => return Err(invalid("Unterminated quoted key".to_string())),
+                            }
+                        }
+                        segments.push(PathSegment::Key(key));
+                    }
+                    _ => {
+                        let start = i;
+                        while i < chars.len() && chars[i] != ']' {
+                            i += 1;
+                        }
+                        let digits: String = chars[start..i].iter().collect();
+                        let index = digits.trim().parse::<usize>().map_err(|_| {
+                            invalid(format!(
+                                "Expected an index or quoted key inside [], got \"{}\"",
+                                digits
+                            ))
+                        })?;
+                        segments.push(PathSegment::Index(index));
+                    }
+                }
+                if chars.get(i) != Some(&']') {
+                    return Err(invalid(format!("Expected ']' at column {}", i)));
+                }
+                i += 1;
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                    i += 1;
+                }
+                segments.push(PathSegment::Key(chars[start..i].iter().collect()));
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Turns evaluated `get`-style arguments into a path. A single string is parsed as a JSON
+/// Pointer when it starts with '/' (or is empty) and as a dotted path otherwise. Several
+/// arguments are taken as literal segments: strings are keys, whole numbers are indices.
+pub fn path_from_args(args: &[Expr]) -> Result<Vec<PathSegment>, JreplErr> {
+    match args {
+        [Expr::String(s)] => parse_path_string(s),
+        _ => literal_segments(args),
+    }
+}
+
+/// A path given as one value: a pointer or dotted string, an index, or a list of segments.
+pub fn path_from_value(value: &Expr) -> Result<Vec<PathSegment>, JreplErr> {
+    match value {
+        Expr::String(s) => parse_path_string(s),
+        Expr::List(items) => literal_segments(items),
+        Expr::Array(items) => literal_segments(items),
+        other => literal_segments(std::slice::from_ref(other)),
+    }
+}
+
+fn parse_path_string(s: &str) -> Result<Vec<PathSegment>, JreplErr> {
+    if s.is_empty() || s.starts_with('/') {
+        parse_pointer(s)
+    } else {
+        parse_dotted(s)
+    }
+}
+
+fn literal_segments(args: &[Expr]) -> Result<Vec<PathSegment>, JreplErr> {
+    args.iter()
+        .map(|arg| match arg {
+            Expr::String(s) => Ok(PathSegment::Key(s.clone())),
+            Expr::Number(n) if n.to_usize().is_some() => {
+                Ok(PathSegment::Index(n.to_usize().unwrap()))
+            }
+            other => Err(JreplErr::InvalidPath(format!(
+                "Path segments must be strings or non-negative whole numbers, got {:?}.",
+                other
+            ))),
+        })
+        .collect()
+}
+
+/// Walks `path` into `value`. Errors name the segment that failed and where it was looked up.
+pub fn get_in<'a>(value: &'a Expr, path: &[PathSegment]) -> Result<&'a Expr, JreplErr> {
+    path.iter()
+        .enumerate()
+        .try_fold(value, |current, (depth, segment)| {
+            step(current, segment).ok_or_else(|| lookup_err(current, &path[..depth], segment))
+        })
+}
+
+fn step<'a>(value: &'a Expr, segment: &PathSegment) -> Option<&'a Expr> {
+    match value {
+        Expr::Object(map) => map.get(&segment.as_key()),
+        Expr::Array(items) => segment.as_index().and_then(|i| items.get(i)),
+        _ => None,
+    }
+}
+
+pub fn lookup_err(container: &Expr, parent: &[PathSegment], segment: &PathSegment) -> JreplErr {
+    let at = describe_location(parent);
+    match container {
+        Expr::Object(_) => {
+            JreplErr::MissingKey(format!("No key '{}' in the object at {}.", segment, at))
+        }
+        Expr::Array(items) => match segment.as_index() {
+            Some(i) => JreplErr::IndexOutOfBounds(format!(
+                "Index {} is out of bounds for the array of length {} at {}.",
+                i,
+                items.len(),
+                at
+            )),
+            None => JreplErr::PathTypeErr(format!(
+                "Segment '{}' is not an array index, but the value at {} is an array.",
+                segment, at
+            )),
+        },
+        other => JreplErr::PathTypeErr(format!(
+            "Cannot look up '{}' at {}: the value there is {}, not an object or array.",
+            segment,
+            at,
+            type_name(other)
+        )),
+    }
+}
+
+/// "the root" or the JSON Pointer of `path`, for messages.
+pub fn describe_location(path: &[PathSegment]) -> String {
+    if path.is_empty() {
+        "the root".to_string()
+    } else {
+        to_pointer(path)
+    }
+}
+
+pub fn type_name(expr: &Expr) -> &'static str {
+    match expr {
+        Expr::String(_) => "a string",
+        Expr::Number(_) => "a number",
+        Expr::Bool(_) => "a bool",
+        Expr::None => "null",
+        Expr::File(_) => "a file",
+        Expr::Array(_) => "an array",
+        Expr::Object(_) => "an object",
+        Expr::Lambda(_) => "a function",
+        Expr::Symbol(_) => "a symbol",
+        Expr::List(_) => "a list",
+    }
+}
+
+// RFC 6901 array indices: "0" or digits without a leading zero.
+fn parse_array_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{command_interpreter::number::Number, json::convert::parse_json};
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    fn doc() -> Expr {
+        parse_json(&std::fs::read("dev/deeply_nested.json").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn pointer_parses_and_unescapes() {
+        assert_eq!(parse_pointer(""), Ok(vec![]));
+        assert_eq!(
+            parse_pointer("/a~1b/m~0n/0"),
+            Ok(vec![key("a/b"), key("m~n"), key("0")])
+        );
+        assert_eq!(parse_pointer("/"), Ok(vec![key("")]));
+        assert!(parse_pointer("a/b").is_err());
+        assert!(parse_pointer("/a~2").is_err());
+        assert!(parse_pointer("/a~0b~").is_err());
+        assert_eq!(parse_pointer("/~01"), Ok(vec![key("~1")]));
+    }
+
+    #[test]
+    fn dotted_parses_keys_indices_and_quoted_keys() {
+        assert_eq!(
+            parse_dotted(r#"config.window.theme["background"]"#),
+            Ok(vec![
+                key("config"),
+                key("window"),
+                key("theme"),
+                key("background")
+            ])
+        );
+        assert_eq!(
+            parse_dotted("$.items[2].name"),
+            Ok(vec![key("items"), PathSegment::Index(2), key("name")])
+        );
+        assert_eq!(parse_dotted("['a.b']"), Ok(vec![key("a.b")]));
+        assert!(parse_dotted("a..b").is_err());
+        assert!(parse_dotted("a[x]").is_err());
+        assert!(parse_dotted("a[\"b").is_err());
+    }
+
+    #[test]
+    fn get_in_pointer_and_dotted_agree() {
+        let doc = doc();
+        let by_pointer = get_in(&doc, &parse_pointer("/config/window/width").unwrap()).unwrap();
+        let by_dotted = get_in(&doc, &parse_dotted("config.window.width").unwrap()).unwrap();
+        assert_eq!(by_pointer, &Expr::Number(Number::Int(800)));
+        assert_eq!(by_pointer, by_dotted);
+    }
+
+    #[test]
+    fn get_in_indexes_arrays() {
+        let doc = parse_json(br#"{"tags": ["rust", "json"]}"#).unwrap();
+        assert_eq!(
+            get_in(&doc, &parse_pointer("/tags/1").unwrap()),
+            Ok(&Expr::String("json".into()))
+        );
+        assert_eq!(
+            get_in(&doc, &parse_dotted("tags[0]").unwrap()),
+            Ok(&Expr::String("rust".into()))
+        );
+    }
+
+    #[test]
+    fn get_in_missing_key_names_segment() {
+        let err = get_in(&doc(), &parse_pointer("/config/window/depth").unwrap()).unwrap_err();
+        assert_eq!(
+            err,
+            JreplErr::MissingKey("No key 'depth' in the object at /config/window.".to_string())
+        );
+    }
+
+    #[test]
+    fn get_in_out_of_bounds_and_type_errors() {
+        let doc = parse_json(br#"{"tags": ["rust"], "n": 1}"#).unwrap();
+        assert!(matches!(
+            get_in(&doc, &parse_pointer("/tags/3").unwrap()),
+            Err(JreplErr::IndexOutOfBounds(_))
+        ));
+        assert!(matches!(
+            get_in(&doc, &parse_pointer("/tags/x").unwrap()),
+            Err(JreplErr::PathTypeErr(_))
+        ));
+        assert!(matches!(
+            get_in(&doc, &parse_pointer("/n/x").unwrap()),
+            Err(JreplErr::PathTypeErr(_))
+        ));
+    }
+
+    #[test]
+    fn path_from_args_variadic_segments() {
+        let args = vec![Expr::String("config".into()), Expr::Number(Number::Int(0))];
+        assert_eq!(
+            path_from_args(&args),
+            Ok(vec![key("config"), PathSegment::Index(0)])
+        );
+        assert!(path_from_args(&[Expr::Number(Number::float(1.5))]).is_err());
+    }
+
+    #[test]
+    fn path_from_value_accepts_strings_and_lists() {
+        assert_eq!(
+            path_from_value(&Expr::String("/a/0".into())),
+            Ok(vec![key("a"), key("0")])
+        );
+        assert_eq!(
+            path_from_value(&Expr::List(vec![
+                Expr::String("a.b".into()),
+                Expr::Number(Number::Int(0))
+            ])),
+            Ok(vec![key("a.b"), PathSegment::Index(0)])
+        );
+        assert_eq!(
+            path_from_value(&Expr::Number(Number::Int(3))),
+            Ok(vec![PathSegment::Index(3)])
+        );
+    }
+
+    #[test]
+    fn to_pointer_escapes() {
+        assert_eq!(to_pointer(&[key("a/b"), PathSegment::Index(0)]), "/a~1b/0");
+        assert_eq!(to_pointer(&[]), "");
+    }
+}
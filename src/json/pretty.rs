@@ -0,0 +1,340 @@
+/*
+    Rendering values as JSON text for people to read.
+
+    Objects and arrays are indented, or written on one line in compact mode, and scalars are
+    written the way JSON writes them: strings quoted and escaped, numbers as their literal. Past
+    `max_depth` a container is shown as [...] or {...}, and past `max_width` elements the rest of a
+    container is replaced by a single ... marker, both with the Unicode ellipsis. Values that are
+    not JSON (functions, files, unevaluated lists) are shown in a readable form of their own.
+*/
+
+use crate::{
+    command_interpreter::types::{Expr, JsonObject},
+    errors::errors::JreplErr,
+};
+
+const KEY: &str = "\x1b[34m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[36m";
+const BOOL: &str = "\x1b[33m";
+const NULL: &str = "\x1b[35m";
+const MARKER: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+const ELLIPSIS: &str = "…";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrettyOptions {
+    /// Spaces per nesting level, ignored when compact.
+    pub indent: usize,
+    pub sort_keys: bool,
+    /// Everything on one line with no spaces.
+    pub compact: bool,
+    /// Containers nested deeper than this are collapsed, the top level being depth 0.
+    pub max_depth: Option<usize>,
+    /// At most this many elements of each container are shown.
+    pub max_width: Option<usize>,
+    /// ANSI colors for keys and scalars.
+    pub color: bool,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            indent: 2,
+            sort_keys: false,
+            compact: false,
+            max_depth: None,
+            max_width: None,
+            color: false,
+        }
+    }
+}
+
+impl PrettyOptions {
+    /// Reads options from an object such as {"indent" 4 "sort-keys" true}. Keys not given keep
+    /// their defaults. `what` names the command for error messages.
+    pub fn from_object(what: &str, options: &JsonObject) -> Result<PrettyOptions, JreplErr> {
+        let mut result = PrettyOptions::default();
+        for (key, value) in options {
+            match key.as_str() {
+                "indent" => result.indent = count_option(what, key, value)?,
+                "sort-keys" => result.sort_keys = bool_option(what, key, value)?,
+                "compact" => result.compact = bool_option(what, key, value)?,
+                "color" => result.color = bool_option(what, key, value)?,
+                "depth" => result.max_depth = Some(count_option(what, key, value)?),
+                "width" => result.max_width = Some(count_option(what, key, value)?),
+                _ => {
+                    return Err(JreplErr::OperatorFormatErr(format!(
+                        "'{}' has no option \"{}\", expected one of indent, sort-keys, compact, color, depth, width",
+                        what, key
+                    )));
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn bool_option(what: &str, key: &str, value: &Expr) -> Result<bool, JreplErr> {
+    match value {
+        Expr::Bool(b) => Ok(*b),
+        other => Err(JreplErr::OperatorFormatErr(format!(
+            "'{}' option \"{}\" expects a Bool, got {:?}",
+            what, key, other
+        ))),
+    }
+}
+
+fn count_option(what: &str, key: &str, value: &Expr) -> Result<usize, JreplErr> {
+    match value {
+        Expr::Number(n) if n.to_usize().is_some() => Ok(n.to_usize().unwrap()),
+        other => Err(JreplErr::OperatorFormatErr(format!(
+            "'{}' option \"{}\" expects a whole number, got {:?}",
+            what, key, other
+        ))),
+    }
+}
+
+/// Renders `value` as JSON text, without a trailing newline.
+pub fn render(value: &Expr, options: &PrettyOptions) -> String {
+    let mut out = String::new();
+    Renderer {
+        options,
+        out: &mut out,
+    }
+    .value(value, 0);
+    out
+}
+
+struct Renderer<'a> {
+    options: &'a PrettyOptions,
+    out: &'a mut String,
+}
+
+impl Renderer<'_> {
+    fn value(&mut self, value: &Expr, depth: usize) {
+        match value {
+            Expr::String(s) => self.paint(STRING, &quoted(s)),
+            Expr::Number(n) => self.paint(NUMBER, &n.to_string()),
+            Expr::Bool(b) => self.paint(BOOL, &b.to_string()),
+            Expr::None => self.paint(NULL, "null"),
+            Expr::Array(items) => self.items('[', ']', items, depth),
+            Expr::List(items) => self.items('[', ']', items, depth),
+            Expr::Object(object) => {
+                let mut entries: Vec<_> = object.iter().collect();
+                if self.options.sort_keys {
+                    entries.sort_by(|a, b| a.0.cmp(b.0));
+                }
+                self.container('{', '}', &entries, depth, |r, (key, value)| {
+                    r.paint(KEY, &quoted(key));
+                    r.out.push(':');
+                    if !r.options.compact {
+                        r.out.push(' ');
+                    }
+                    r.value(value, depth + 1);
+                });
+            }
+            Expr::File(file) => self.out.push_str(&format!(
+                "<file {} ({} bytes)>",
+                file.path,
+                file.bytes.len()
+            )),
+            Expr::Lambda(lambda) => self.out.push_str(&format!("{:?}", lambda)),
+            Expr::Symbol(name) => self.out.push_str(name),
+        }
+    }
+
+    fn items(&mut self, open: char, close: char, items: &[Expr], depth: usize) {
+        let items: Vec<_> = items.iter().collect();
+        self.container(open, close, &items, depth, |r, item| {
+            r.value(item, depth + 1)
+        });
+    }
+
+    fn container<T>(
+        &mut self,
+        open: char,
+        close: char,
+        elements: &[T],
+        depth: usize,
+        mut element: impl FnMut(&mut Self, &T),
+    ) {
+        self.out.push(open);
+        if elements.is_empty() {
+            self.out.push(close);
+            return;
+        }
+        if self.options.max_depth.is_some_and(|max| depth >= max) {
+            self.paint(MARKER, ELLIPSIS);
+            self.out.push(close);
+            return;
+        }
+
+        let shown = self
+            .options
+            .max_width
+            .map_or(elements.len(), |w| w.min(elements.len()));
+        for (i, e) in elements[..shown].iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.newline(depth + 1);
+            element(self, e);
+        }
+        if shown < elements.len() {
+            if shown > 0 {
+                self.out.push(',');
+            }
+            self.newline(depth + 1);
+            self.paint(MARKER, ELLIPSIS);
+        }
+        self.newline(depth);
+        self.out.push(close);
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if !self.options.compact {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(self.options.indent * depth));
+        }
+    }
+
+    fn paint(&mut self, color: &str, text: &str) {
+        if self.options.color {
+            self.out.push_str(color);
+            self.out.push_str(text);
+            self.out.push_str(RESET);
+        } else {
+            self.out.push_str(text);
+        }
+    }
+}
+
+fn quoted(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_else(|_| format!("\"{}\"", s))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{command_interpreter::number::Number, json::convert::parse_json};
+
+    fn doc(text: &str) -> Expr {
+        parse_json(text.as_bytes()).unwrap()
+    }
+
+    fn with(f: impl FnOnce(&mut PrettyOptions)) -> PrettyOptions {
+        let mut options = PrettyOptions::default();
+        f(&mut options);
+        options
+    }
+
+    #[test]
+    fn pretty_output_is_indented_json() {
+        let value =
+            doc(r#"{"name": "Ann \"A\"", "tags": ["a", 1, 2.50, true, null], "empty": {}}"#);
+        assert_eq!(
+            render(&value, &PrettyOptions::default()),
+            "{\n  \"name\": \"Ann \\\"A\\\"\",\n  \"tags\": [\n    \"a\",\n    1,\n    2.50,\n    true,\n    null\n  ],\n  \"empty\": {}\n}"
+        );
+        assert_eq!(
+            render(&value, &with(|o| o.indent = 4)).lines().nth(1),
+            Some("    \"name\": \"Ann \\\"A\\\"\",")
+        );
+    }
+
+    #[test]
+    fn compact_output_matches_serde() {
+        let text = r#"{"b":[1,{"c":null}],"a":"x"}"#;
+        assert_eq!(render(&doc(text), &with(|o| o.compact = true)), text);
+    }
+
+    #[test]
+    fn sort_keys_orders_every_object() {
+        let value = doc(r#"{"b": {"z": 1, "y": 2}, "a": 0}"#);
+        let options = with(|o| {
+            o.compact = true;
+            o.sort_keys = true;
+        });
+        assert_eq!(render(&value, &options), r#"{"a":0,"b":{"y":2,"z":1}}"#);
+    }
+
+    #[test]
+    fn numbers_print_as_written() {
+        let options = PrettyOptions::default();
+        assert_eq!(render(&Expr::Number(Number::Int(3)), &options), "3");
+        assert_eq!(render(&Expr::Number(Number::float(0.5)), &options), "0.5");
+        assert_eq!(render(&doc("1e10"), &options), "1e10");
+    }
+
+    #[test]
+    fn depth_and_width_truncate_with_markers() {
+        let value = doc(r#"{"a": [1, 2, 3, 4], "b": {"c": {"d": 1}}, "e": []}"#);
+        let compact = |f: fn(&mut PrettyOptions)| {
+            render(
+                &value,
+                &with(|o| {
+                    o.compact = true;
+                    f(o)
+                }),
+            )
+        };
+        assert_eq!(
+            compact(|o| o.max_depth = Some(1)),
+            r#"{"a":[…],"b":{…},"e":[]}"#
+        );
+        assert_eq!(compact(|o| o.max_depth = Some(0)), "{…}");
+        assert_eq!(
+            compact(|o| o.max_width = Some(2)),
+            r#"{"a":[1,2,…],"b":{"c":{"d":1}},…}"#
+        );
+        assert_eq!(compact(|o| o.max_width = Some(0)), "{…}");
+        assert_eq!(
+            render(&doc("[1, 2, 3]"), &with(|o| o.max_width = Some(1))),
+            "[\n  1,\n  …\n]"
+        );
+    }
+
+    #[test]
+    fn color_wraps_keys_and_scalars() {
+        let rendered = render(
+            &doc(r#"{"a": "x"}"#),
+            &with(|o| {
+                o.compact = true;
+                o.color = true;
+            }),
+        );
+        assert_eq!(rendered, "{\x1b[34m\"a\"\x1b[0m:\x1b[32m\"x\"\x1b[0m}");
+    }
+
+    #[test]
+    fn options_are_read_from_an_object() {
+        let Expr::Object(object) = doc(r#"{"indent": 4, "sort-keys": true, "depth": 2}"#) else {
+            unreachable!()
+        };
+        let options = PrettyOptions::from_object("pp", &object).unwrap();
+        assert_eq!(
+            options,
+            with(|o| {
+                o.indent = 4;
+                o.sort_keys = true;
+                o.max_depth = Some(2);
+            })
+        );
+
+        for bad in [
+            r#"{"indent": -1}"#,
+            r#"{"compact": 1}"#,
+            r#"{"colour": true}"#,
+        ] {
+            let Expr::Object(object) = doc(bad) else {
+                unreachable!()
+            };
+            assert!(matches!(
+                PrettyOptions::from_object("pp", &object),
+                Err(JreplErr::OperatorFormatErr(_))
+            ));
+        }
+    }
+}
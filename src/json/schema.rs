@@ -0,0 +1,1745 @@
+/*
+    JSON Schema validation, drafts 2020-12 and 07.
+
+    A schema's draft is read from its "$schema", anything that is not draft-07 or older is
+    validated as 2020-12. Every violation is collected rather than stopping at the first, each
+    with the JSON Pointer of the offending value, the pointer of the keyword that rejected it
+    and a message. A violation inside anyOf, oneOf or not is reported once, at that keyword.
+
+    "$ref" resolves against the "$id" of the enclosing schemas the way URIs do. A schema read
+    from a file has that file as its base, so refs to sibling files such as
+    "address.json#/$defs/street" are loaded from disk on first use. When a schema with an http
+    "$id" refers to a neighbour by a relative URI that was never loaded, the file of the same
+    name next to the referring schema is used. A "$dynamicRef" to a "$dynamicAnchor" starts
+    from the outermost schema resource being evaluated that declares the same anchor, so an
+    extending schema can take the place of the one it extends; any other "$dynamicRef" is
+    resolved like "$ref". "format" is an annotation only, as both drafts specify by default.
+*/
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use regex::Regex;
+
+use crate::{
+    command_interpreter::{
+        number::Number,
+        types::{Expr, JsonObject},
+    },
+    errors::errors::JreplErr,
+    json::{
+        convert::parse_json,
+        path::{PathSegment, get_in, parse_pointer, to_pointer},
+        pretty::{PrettyOptions, render},
+    },
+    statics::logic_commands::values_equal,
+};
+
+// deeper than this, a chain of refs is taken to be a cycle that never consumes the instance
+const MAX_REF_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Draft {
+    Draft7,
+    Draft2020_12,
+}
+
+impl Draft {
+    fn of(schema: &Expr, default: Draft) -> Draft {
+        match schema {
+            Expr::Object(o) => match o.get("$schema") {
+                Some(Expr::String(uri)) if uri.contains("draft-0") => Draft::Draft7,
+                Some(Expr::String(_)) => Draft::Draft2020_12,
+                _ => default,
+            },
+            _ => default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// JSON Pointer of the value that failed.
+    pub instance_path: String,
+    /// JSON Pointer of the failing keyword, through any refs followed to reach it.
+    pub schema_path: String,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn to_expr(&self) -> Expr {
+        let mut object = JsonObject::new();
+        object.insert(
+            "instancePath".to_string(),
+            Expr::String(self.instance_path.clone()),
+        );
+        object.insert(
+            "schemaPath".to_string(),
+            Expr::String(self.schema_path.clone()),
+        );
+        object.insert("message".to_string(), Expr::String(self.message.clone()));
+        Expr::object(object)
+    }
+}
+
+/// Validates `instance` against `schema`. `source` is the file the schema was read from, if any,
+/// which relative refs are resolved against; without it they resolve against the working
+/// directory. An error means the schema itself is broken, e.g. a ref that resolves to nothing.
+pub fn validate(
+    instance: &Expr,
+    schema: &Expr,
+    source: Option<&Path>,
+) -> Result<Vec<Violation>, JreplErr> {
+    let (uri, dir) = match source {
+        Some(path) => {
+            let path = std::path::absolute(path).map_err(|err| {
+                JreplErr::IoErr(format!("Cannot resolve '{}': {}", path.display(), err))
+            })?;
+            let dir = path.parent().map(Path::to_path_buf);
+            (file_uri(&path), dir)
+        }
+        None => {
+            let cwd = std::env::current_dir().ok();
+            let uri = cwd
+                .as_deref()
+                .map_or("file:///".to_string(), |d| file_uri(&d.join("schema.json")));
+            (uri, cwd)
+        }
+    };
+
+    let validator = Validator::default();
+    let document = validator.register(&uri, schema.clone(), dir, Draft::Draft2020_12);
+    let base = Base {
+        uri,
+        dir: document.dir.clone(),
+        draft: document.draft,
+        scope: None,
+    };
+    let mut violations = Vec::new();
+    validator.check(instance, schema, &base, &[], "", 0, &mut violations)?;
+    Ok(violations)
+}
+
+/// Reads a schema file and validates `instance` against it, see `validate`.
+pub fn validate_with_file(instance: &Expr, path: &str) -> Result<Vec<Violation>, JreplErr> {
+    let schema = read_schema(Path::new(path))?;
+    validate(instance, &schema, Some(Path::new(path)))
+}
+
+fn read_schema(path: &Path) -> Result<Expr, JreplErr> {
+    let bytes = fs::read(path).map_err(|err| {
+        JreplErr::IoErr(format!(
+            "Error reading schema '{}': {}",
+            path.display(),
+            err
+        ))
+    })?;
+    parse_json(&bytes).map_err(|err| match err {
+        JreplErr::JsonErr(msg) => {
+            JreplErr::JsonErr(format!("Schema '{}': {}", path.display(), msg))
+        }
+        other => other,
+    })
+}
+
+fn invalid_schema(schema_path: &str, message: String) -> JreplErr {
+    JreplErr::JsonErr(format!(
+        "Invalid schema at \"{}\": {}",
+        if schema_path.is_empty() {
+            "#"
+        } else {
+            schema_path
+        },
+        message
+    ))
+}
+
+// ------------------------------- Resolution ------------------------------- //
+
+struct Document {
+    schema: Expr,
+    dir: Option<PathBuf>,
+    draft: Draft,
+}
+
+/// Where a schema is being evaluated: the URI refs resolve against, the directory of the file
+/// it came from, the draft it is written in and the schema resources entered to get there.
+#[derive(Clone)]
+struct Base {
+    uri: String,
+    dir: Option<PathBuf>,
+    draft: Draft,
+    scope: Option<Rc<DynamicScope>>,
+}
+
+/// The dynamic scope "$dynamicRef" searches: the URI of each schema resource evaluation has
+/// entered, innermost first.
+struct DynamicScope {
+    uri: String,
+    parent: Option<Rc<DynamicScope>>,
+}
+
+#[derive(Default)]
+struct Validator {
+    // every loaded schema document by URI, without fragment
+    documents: RefCell<HashMap<String, Rc<Document>>>,
+    // the embedded resources ("$id") and anchors of those documents, to the URI of the document
+    // and the pointer of the subschema within it
+    locations: RefCell<HashMap<String, (String, String)>>,
+    // the URIs in `locations` that name a "$dynamicAnchor"
+    dynamic_anchors: RefCell<HashSet<String>>,
+}
+
+impl Validator {
+    fn register(
+        &self,
+        uri: &str,
+        schema: Expr,
+        dir: Option<PathBuf>,
+        default: Draft,
+    ) -> Rc<Document> {
+        let draft = Draft::of(&schema, default);
+        let document = Rc::new(Document { schema, dir, draft });
+        self.index(uri, &document.schema, uri, draft, "");
+        if let Expr::Object(o) = &document.schema
+            && let Some(Expr::String(id)) = o.get("$id")
+        {
+            let id = strip_fragment(&resolve_uri(uri, id)).to_string();
+            self.documents.borrow_mut().insert(id, document.clone());
+        }
+        self.documents
+            .borrow_mut()
+            .insert(uri.to_string(), document.clone());
+        document
+    }
+
+    // Records the "$id" and anchors of every subschema below `schema`.
+    fn index(&self, document: &str, schema: &Expr, base: &str, draft: Draft, pointer: &str) {
+        match schema {
+            Expr::Object(o) => {
+                let mut base = base.to_string();
+                let locate = |uri: String| {
+                    self.locations
+                        .borrow_mut()
+                        .insert(uri, (document.to_string(), pointer.to_string()));
+                };
+                if let Some(Expr::String(id)) = o.get("$id") {
+                    let uri = resolve_uri(&base, id);
+                    if draft == Draft::Draft7 && id.starts_with('#') {
+                        locate(uri);
+                    } else {
+                        base = strip_fragment(&uri).to_string();
+                        locate(base.clone());
+                    }
+                }
+                if let Some(Expr::String(anchor)) = o.get("$anchor") {
+                    locate(format!("{}#{}", base, anchor));
+                }
+                if let Some(Expr::String(anchor)) = o.get("$dynamicAnchor") {
+                    let uri = format!("{}#{}", base, anchor);
+                    self.dynamic_anchors.borrow_mut().insert(uri.clone());
+                    locate(uri);
+                }
+                for (key, child) in o.iter() {
+                    // these hold instance data, not schemas
+                    if !matches!(key.as_str(), "enum" | "const" | "examples" | "default") {
+                        let child_pointer = format!("{}/{}", pointer, escape_token(key));
+                        self.index(document, child, &base, draft, &child_pointer);
+                    }
+                }
+            }
+            Expr::Array(items) => {
+                for (i, child) in items.iter().enumerate() {
+                    self.index(document, child, base, draft, &format!("{}/{}", pointer, i));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve(
+        &self,
+        reference: &str,
+        from: &Base,
+        schema_path: &str,
+    ) -> Result<(Expr, Base), JreplErr> {
+        let uri = resolve_uri(&from.uri, reference);
+        let (resource_uri, fragment) = match uri.split_once('#') {
+            Some((resource, fragment)) => (resource.to_string(), percent_decode(fragment)),
+            None => (uri.clone(), String::new()),
+        };
+        let unresolved = || {
+            invalid_schema(
+                schema_path,
+                format!("cannot resolve $ref \"{}\" ({})", reference, uri),
+            )
+        };
+
+        let (resource, base) = self
+            .resource(&resource_uri, reference, from)?
+            .ok_or_else(unresolved)?;
+        if fragment.is_empty() {
+            return Ok((resource, base));
+        }
+        if fragment.starts_with('/') {
+            let target = get_in(&resource, &parse_pointer(&fragment)?).map_err(|_| unresolved())?;
+            return Ok((target.clone(), base));
+        }
+
+        let anchor = format!("{}#{}", resource_uri, fragment);
+        let location = self.locations.borrow().get(&anchor).cloned();
+        match location {
+            Some((document, pointer)) => {
+                let (target, base) = self.located(&document, &pointer).ok_or_else(unresolved)?;
+                Ok((
+                    target,
+                    Base {
+                        uri: resource_uri,
+                        ..base
+                    },
+                ))
+            }
+            None => Err(unresolved()),
+        }
+    }
+
+    // A "$dynamicRef" whose initial target is a "$dynamicAnchor" resolves to the same anchor in
+    // the outermost resource of the dynamic scope that has one. Anything else is a plain ref.
+    fn resolve_dynamic(
+        &self,
+        reference: &str,
+        from: &Base,
+        schema_path: &str,
+    ) -> Result<(Expr, Base), JreplErr> {
+        // resolving first also loads the target's file, and with it its anchors
+        let initial = self.resolve(reference, from, schema_path)?;
+        let uri = resolve_uri(&from.uri, reference);
+        let Some((resource, fragment)) = uri.split_once('#') else {
+            return Ok(initial);
+        };
+        let anchor = percent_decode(fragment);
+        let declares = |resource: &str| {
+            self.dynamic_anchors
+                .borrow()
+                .contains(&format!("{}#{}", resource, anchor))
+        };
+        if anchor.starts_with('/') || !declares(resource) {
+            return Ok(initial);
+        }
+
+        let mut outermost = None;
+        let mut scope = from.scope.as_deref();
+        while let Some(entry) = scope {
+            if declares(&entry.uri) {
+                outermost = Some(entry.uri.as_str());
+            }
+            scope = entry.parent.as_deref();
+        }
+        match outermost {
+            Some(resource) => self.resolve(&format!("{}#{}", resource, anchor), from, schema_path),
+            None => Ok(initial),
+        }
+    }
+
+    // The schema a URI without fragment names: a loaded document, a resource embedded in one,
+    // or a file on disk.
+    fn resource(
+        &self,
+        uri: &str,
+        reference: &str,
+        from: &Base,
+    ) -> Result<Option<(Expr, Base)>, JreplErr> {
+        let document = self.documents.borrow().get(uri).cloned();
+        if let Some(document) = document {
+            return Ok(Some((
+                document.schema.clone(),
+                self.base_of(uri, &document),
+            )));
+        }
+        let location = self.locations.borrow().get(uri).cloned();
+        if let Some((document, pointer)) = location {
+            return Ok(self.located(&document, &pointer).map(|(schema, base)| {
+                (
+                    schema,
+                    Base {
+                        uri: uri.to_string(),
+                        ..base
+                    },
+                )
+            }));
+        }
+
+        let path = match uri.strip_prefix("file://") {
+            Some(path) => Some(PathBuf::from(path)),
+            // a relative ref from a schema with a web $id: look next to the file it came from
+            None if !has_scheme(reference) => {
+                let relative = strip_fragment(reference);
+                from.dir
+                    .as_ref()
+                    .map(|dir| dir.join(relative))
+                    .filter(|_| !relative.is_empty())
+            }
+            None => None,
+        };
+        let Some(path) = path.filter(|p| p.is_file()) else {
+            return Ok(None);
+        };
+
+        let schema = read_schema(&path)?;
+        let document = self.register(
+            uri,
+            schema,
+            path.parent().map(Path::to_path_buf),
+            from.draft,
+        );
+        let file = file_uri(&path);
+        if file != uri {
+            self.documents.borrow_mut().insert(file, document.clone());
+        }
+        Ok(Some((
+            document.schema.clone(),
+            self.base_of(uri, &document),
+        )))
+    }
+
+    fn located(&self, document: &str, pointer: &str) -> Option<(Expr, Base)> {
+        let document = self.documents.borrow().get(document).cloned()?;
+        let path = parse_pointer(pointer).ok()?;
+        let schema = get_in(&document.schema, &path).ok()?.clone();
+        let base = self.base_of("", &document);
+        Some((schema, base))
+    }
+
+    fn base_of(&self, uri: &str, document: &Document) -> Base {
+        Base {
+            uri: uri.to_string(),
+            dir: document.dir.clone(),
+            draft: document.draft,
+            scope: None,
+        }
+    }
+
+    // ------------------------------- Validation ------------------------------- //
+
+    /// Validates into a fresh list, returning the violations and the annotations of a pass.
+    fn apply(
+        &self,
+        instance: &Expr,
+        schema: &Expr,
+        base: &Base,
+        instance_path: &[PathSegment],
+        schema_path: &str,
+        depth: usize,
+    ) -> Result<(Vec<Violation>, Evaluated), JreplErr> {
+        let mut violations = Vec::new();
+        let evaluated = self.check(
+            instance,
+            schema,
+            base,
+            instance_path,
+            schema_path,
+            depth,
+            &mut violations,
+        )?;
+        Ok((violations, evaluated))
+    }
+
+    // Like apply, adding the violations to `out` and the annotations to `evaluated` on a pass.
+    #[allow(clippy::too_many_arguments)]
+    fn apply_into(
+        &self,
+        instance: &Expr,
+        schema: &Expr,
+        base: &Base,
+        instance_path: &[PathSegment],
+        schema_path: &str,
+        depth: usize,
+        out: &mut Vec<Violation>,
+        evaluated: &mut Evaluated,
+    ) -> Result<bool, JreplErr> {
+        let (violations, annotations) =
+            self.apply(instance, schema, base, instance_path, schema_path, depth)?;
+        let valid = violations.is_empty();
+        if valid {
+            evaluated.merge(annotations);
+        }
+        out.extend(violations);
+        Ok(valid)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn check(
+        &self,
+        instance: &Expr,
+        schema: &Expr,
+        base: &Base,
+        instance_path: &[PathSegment],
+        schema_path: &str,
+        depth: usize,
+        out: &mut Vec<Violation>,
+    ) -> Result<Evaluated, JreplErr> {
+        let s = match schema {
+            Expr::Bool(true) => return Ok(Evaluated::default()),
+            Expr::Bool(false) => {
+                out.push(Violation {
+                    instance_path: to_pointer(instance_path),
+                    schema_path: schema_path.to_string(),
+                    message: "No value is allowed here".to_string(),
+                });
+                return Ok(Evaluated::default());
+            }
+            Expr::Object(s) => s,
+            other => {
+                return Err(invalid_schema(
+                    schema_path,
+                    format!("expected an object or a boolean, got {}", type_name(other)),
+                ));
+            }
+        };
+
+        let mut base = base.clone();
+        if let Some(Expr::String(id)) = s.get("$id")
+            && !(base.draft == Draft::Draft7 && id.starts_with('#'))
+        {
+            base.uri = strip_fragment(&resolve_uri(&base.uri, id)).to_string();
+        }
+        if base
+            .scope
+            .as_ref()
+            .is_none_or(|scope| scope.uri != base.uri)
+        {
+            base.scope = Some(Rc::new(DynamicScope {
+                uri: base.uri.clone(),
+                parent: base.scope.take(),
+            }));
+        }
+
+        let mut ctx = Check {
+            validator: self,
+            instance,
+            s,
+            base: &base,
+            instance_path,
+            schema_path,
+            depth,
+            out,
+            evaluated: Evaluated::default(),
+        };
+
+        for keyword in ["$ref", "$dynamicRef"] {
+            if let Some(reference) = ctx.string(keyword)? {
+                if depth >= MAX_REF_DEPTH {
+                    return Err(invalid_schema(
+                        schema_path,
+                        format!("$ref \"{}\" recurses without end", reference),
+                    ));
+                }
+                let (target, mut target_base) = if keyword == "$dynamicRef" {
+                    self.resolve_dynamic(reference, &base, schema_path)?
+                } else {
+                    self.resolve(reference, &base, schema_path)?
+                };
+                target_base.scope = base.scope.clone();
+                let path = format!("{}/{}", schema_path, keyword);
+                let mut evaluated = Evaluated::default();
+                self.apply_into(
+                    instance,
+                    &target,
+                    &target_base,
+                    instance_path,
+                    &path,
+                    depth + 1,
+                    ctx.out,
+                    &mut evaluated,
+                )?;
+                ctx.evaluated.merge(evaluated);
+                // in draft-07 a ref replaces the rest of its schema
+                if base.draft == Draft::Draft7 && keyword == "$ref" {
+                    return Ok(ctx.evaluated);
+                }
+            }
+        }
+
+        ctx.generic()?;
+        match instance {
+            Expr::Number(n) => ctx.number(n)?,
+            Expr::String(text) => ctx.string_value(text)?,
+            Expr::Array(items) => ctx.array(items)?,
+            Expr::List(items) => ctx.array(items)?,
+            Expr::Object(object) => ctx.object(object)?,
+            _ => {}
+        }
+        ctx.combinators()?;
+        if base.draft == Draft::Draft2020_12 {
+            match instance {
+                Expr::Array(items) => ctx.unevaluated_items(items)?,
+                Expr::List(items) => ctx.unevaluated_items(items)?,
+                Expr::Object(object) => ctx.unevaluated_properties(object)?,
+                _ => {}
+            }
+        }
+        Ok(ctx.evaluated)
+    }
+}
+
+/// The annotations "unevaluatedProperties" and "unevaluatedItems" depend on: which properties
+/// and items some passing subschema already looked at.
+#[derive(Default)]
+struct Evaluated {
+    properties: HashSet<String>,
+    items: HashSet<usize>,
+}
+
+impl Evaluated {
+    fn merge(&mut self, other: Evaluated) {
+        self.properties.extend(other.properties);
+        self.items.extend(other.items);
+    }
+}
+
+/// One object schema being applied to one value.
+struct Check<'a> {
+    validator: &'a Validator,
+    instance: &'a Expr,
+    s: &'a JsonObject,
+    base: &'a Base,
+    instance_path: &'a [PathSegment],
+    schema_path: &'a str,
+    depth: usize,
+    out: &'a mut Vec<Violation>,
+    evaluated: Evaluated,
+}
+
+impl Check<'_> {
+    fn draft7(&self) -> bool {
+        self.base.draft == Draft::Draft7
+    }
+
+    fn keyword_path(&self, keyword: &str) -> String {
+        format!("{}/{}", self.schema_path, escape_token(keyword))
+    }
+
+    fn fail(&mut self, keyword: &str, message: String) {
+        self.fail_at(self.instance_path.to_vec(), keyword, message);
+    }
+
+    fn fail_at(&mut self, instance_path: Vec<PathSegment>, keyword: &str, message: String) {
+        self.out.push(Violation {
+            instance_path: to_pointer(&instance_path),
+            schema_path: self.keyword_path(keyword),
+            message,
+        });
+    }
+
+    // Applies a subschema at `keyword_path` to a value at `instance_path`, see apply_into.
+    fn sub(
+        &mut self,
+        instance: &Expr,
+        schema: &Expr,
+        instance_path: &[PathSegment],
+        keyword_path: &str,
+    ) -> Result<bool, JreplErr> {
+        let mut evaluated = Evaluated::default();
+        let valid = self.validator.apply_into(
+            instance,
+            schema,
+            self.base,
+            instance_path,
+            keyword_path,
+            self.depth,
+            self.out,
+            &mut evaluated,
+        )?;
+        self.evaluated.merge(evaluated);
+        Ok(valid)
+    }
+
+    // Applies a subschema without reporting, for anyOf, oneOf, not and if.
+    fn probe(&self, schema: &Expr, keyword_path: &str) -> Result<(bool, Evaluated), JreplErr> {
+        let (violations, evaluated) = self.validator.apply(
+            self.instance,
+            schema,
+            self.base,
+            self.instance_path,
+            keyword_path,
+            self.depth,
+        )?;
+        Ok((violations.is_empty(), evaluated))
+    }
+
+    // ---- keyword values, checked for shape ----
+
+    fn string(&self, keyword: &str) -> Result<Option<&str>, JreplErr> {
+        match self.s.get(keyword) {
+            None => Ok(None),
+            Some(Expr::String(s)) => Ok(Some(s)),
+            Some(other) => Err(self.malformed(keyword, "a string", other)),
+        }
+    }
+
+    fn number(&mut self, n: &Number) -> Result<(), JreplErr> {
+        let bound = |keyword: &str| match self.s.get(keyword) {
+            None => Ok(None),
+            Some(Expr::Number(m)) => Ok(Some(m.clone())),
+            Some(other) => Err(self.malformed(keyword, "a number", other)),
+        };
+        let (minimum, maximum) = (bound("minimum")?, bound("maximum")?);
+        let (exclusive_minimum, exclusive_maximum) =
+            (bound("exclusiveMinimum")?, bound("exclusiveMaximum")?);
+        let multiple_of = bound("multipleOf")?;
+
+        if let Some(m) = minimum.filter(|m| n < m) {
+            self.fail(
+                "minimum",
+                format!("{} is less than the minimum of {}", n, m),
+            );
+        }
+        if let Some(m) = maximum.filter(|m| n > m) {
+            self.fail(
+                "maximum",
+                format!("{} is greater than the maximum of {}", n, m),
+            );
+        }
+        if let Some(m) = exclusive_minimum.filter(|m| n <= m) {
+            self.fail(
+                "exclusiveMinimum",
+                format!("{} is not greater than {}", n, m),
+            );
+        }
+        if let Some(m) = exclusive_maximum.filter(|m| n >= m) {
+            self.fail("exclusiveMaximum", format!("{} is not less than {}", n, m));
+        }
+        if let Some(m) = multiple_of {
+            if m.is_zero() || m < Number::Int(0) {
+                return Err(invalid_schema(
+                    &self.keyword_path("multipleOf"),
+                    "must be greater than 0".to_string(),
+                ));
+            }
+            if !is_multiple(n, &m) {
+                self.fail("multipleOf", format!("{} is not a multiple of {}", n, m));
+            }
+        }
+        Ok(())
+    }
+
+    fn count(&self, keyword: &str) -> Result<Option<usize>, JreplErr> {
+        match self.s.get(keyword) {
+            None => Ok(None),
+            Some(Expr::Number(n)) if n.to_usize().is_some() => Ok(n.to_usize()),
+            Some(other) => Err(self.malformed(keyword, "a non-negative integer", other)),
+        }
+    }
+
+    fn schema_map(&self, keyword: &str) -> Result<Option<&JsonObject>, JreplErr> {
+        match self.s.get(keyword) {
+            None => Ok(None),
+            Some(Expr::Object(o)) => Ok(Some(o)),
+            Some(other) => Err(self.malformed(keyword, "an object", other)),
+        }
+    }
+
+    fn schema_list(&self, keyword: &str) -> Result<Option<&[Expr]>, JreplErr> {
+        match self.s.get(keyword) {
+            None => Ok(None),
+            Some(Expr::Array(items)) if !items.is_empty() => Ok(Some(items)),
+            Some(other) => Err(self.malformed(keyword, "a non-empty array", other)),
+        }
+    }
+
+    fn names(&self, keyword: &str, value: &Expr) -> Result<Vec<String>, JreplErr> {
+        let names = match value {
+            Expr::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Expr::String(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        names.ok_or_else(|| self.malformed(keyword, "an array of strings", value))
+    }
+
+    fn regex(&self, keyword: &str, pattern: &str) -> Result<Regex, JreplErr> {
+        Regex::new(pattern).map_err(|err| {
+            invalid_schema(
+                &self.keyword_path(keyword),
+                format!("invalid pattern \"{}\": {}", pattern, err),
+            )
+        })
+    }
+
+    fn malformed(&self, keyword: &str, expected: &str, got: &Expr) -> JreplErr {
+        invalid_schema(
+            &self.keyword_path(keyword),
+            format!("expected {}, got {}", expected, type_name(got)),
+        )
+    }
+
+    // ---- keywords for any type ----
+
+    fn generic(&mut self) -> Result<(), JreplErr> {
+        if let Some(types) = self.s.get("type") {
+            let types = match types {
+                Expr::String(t) => vec![t.clone()],
+                other => self.names("type", other)?,
+            };
+            if !types.iter().any(|t| has_type(self.instance, t)) {
+                let got = type_name(self.instance);
+                self.fail(
+                    "type",
+                    format!("Expected {}, got {}", types.join(" or "), got),
+                );
+            }
+        }
+        if let Some(options) = self.s.get("enum") {
+            let Expr::Array(options) = options else {
+                return Err(self.malformed("enum", "an array", options));
+            };
+            if !options
+                .iter()
+                .any(|option| values_equal(self.instance, option))
+            {
+                let listed: Vec<String> = options.iter().map(compact).collect();
+                self.fail("enum", format!("Expected one of {}", listed.join(", ")));
+            }
+        }
+        if let Some(expected) = self.s.get("const")
+            && !values_equal(self.instance, expected)
+        {
+            self.fail("const", format!("Expected {}", compact(expected)));
+        }
+        Ok(())
+    }
+
+    // ---- strings ----
+
+    fn string_value(&mut self, text: &str) -> Result<(), JreplErr> {
+        let length = text.chars().count();
+        if let Some(min) = self.count("minLength")?.filter(|min| length < *min) {
+            self.fail(
+                "minLength",
+                format!(
+                    "String is {} characters long, expected at least {}",
+                    length, min
+                ),
+            );
+        }
+        if let Some(max) = self.count("maxLength")?.filter(|max| length > *max) {
+            self.fail(
+                "maxLength",
+                format!(
+                    "String is {} characters long, expected at most {}",
+                    length, max
+                ),
+            );
+        }
+        if let Some(pattern) = self.string("pattern")?
+            && !self.regex("pattern", pattern)?.is_match(text)
+        {
+            self.fail(
+                "pattern",
+                format!("String does not match the pattern \"{}\"", pattern),
+            );
+        }
+        Ok(())
+    }
+
+    // ---- arrays ----
+
+    fn array(&mut self, items: &[Expr]) -> Result<(), JreplErr> {
+        let child = |i: usize| {
+            let mut path = self.instance_path.to_vec();
+            path.push(PathSegment::Index(i));
+            path
+        };
+
+        // the tuple part, then the schema every remaining item must match
+        let (prefix_keyword, rest_keyword) = if self.draft7() {
+            match self.s.get("items") {
+                Some(Expr::Array(_)) => ("items", "additionalItems"),
+                _ => ("", "items"),
+            }
+        } else {
+            ("prefixItems", "items")
+        };
+        let mut prefix_len = 0;
+        if !prefix_keyword.is_empty()
+            && let Some(prefix) = self.schema_list(prefix_keyword)?
+        {
+            let prefix = prefix.to_vec();
+            prefix_len = prefix.len();
+            for (i, (item, schema)) in items.iter().zip(&prefix).enumerate() {
+                let path = format!("{}/{}", self.keyword_path(prefix_keyword), i);
+                self.sub(item, schema, &child(i), &path)?;
+                self.evaluated.items.insert(i);
+            }
+        }
+        if let Some(schema) = self.s.get(rest_keyword).cloned() {
+            let path = self.keyword_path(rest_keyword);
+            for (i, item) in items.iter().enumerate().skip(prefix_len) {
+                if schema == Expr::Bool(false) {
+                    self.fail(
+                        rest_keyword,
+                        format!("Expected at most {} items, got {}", prefix_len, items.len()),
+                    );
+                    break;
+                }
+                self.sub(item, &schema, &child(i), &path)?;
+                self.evaluated.items.insert(i);
+            }
+        }
+
+        if let Some(min) = self.count("minItems")?.filter(|min| items.len() < *min) {
+            self.fail(
+                "minItems",
+                format!("Array has {} items, expected at least {}", items.len(), min),
+            );
+        }
+        if let Some(max) = self.count("maxItems")?.filter(|max| items.len() > *max) {
+            self.fail(
+                "maxItems",
+                format!("Array has {} items, expected at most {}", items.len(), max),
+            );
+        }
+        if self.s.get("uniqueItems") == Some(&Expr::Bool(true)) {
+            let duplicate = (0..items.len())
+                .flat_map(|i| (i + 1..items.len()).map(move |j| (i, j)))
+                .find(|&(i, j)| values_equal(&items[i], &items[j]));
+            if let Some((i, j)) = duplicate {
+                self.fail("uniqueItems", format!("Items {} and {} are equal", i, j));
+            }
+        }
+
+        if let Some(schema) = self.s.get("contains").cloned() {
+            let path = self.keyword_path("contains");
+            let mut matches = Vec::new();
+            for (i, item) in items.iter().enumerate() {
+                let (violations, _) =
+                    self.validator
+                        .apply(item, &schema, self.base, &child(i), &path, self.depth)?;
+                if violations.is_empty() {
+                    matches.push(i);
+                }
+            }
+            let (min, max) = if self.draft7() {
+                (1, None)
+            } else {
+                (
+                    self.count("minContains")?.unwrap_or(1),
+                    self.count("maxContains")?,
+                )
+            };
+            if matches.len() < min {
+                let keyword = if min == 1 { "contains" } else { "minContains" };
+                self.fail(
+                    keyword,
+                    format!(
+                        "Expected at least {} item(s) matching contains, found {}",
+                        min,
+                        matches.len()
+                    ),
+                );
+            }
+            if let Some(max) = max.filter(|max| matches.len() > *max) {
+                self.fail(
+                    "maxContains",
+                    format!(
+                        "Expected at most {} item(s) matching contains, found {}",
+                        max,
+                        matches.len()
+                    ),
+                );
+            }
+            if !self.draft7() {
+                self.evaluated.items.extend(matches);
+            }
+        }
+        Ok(())
+    }
+
+    fn unevaluated_items(&mut self, items: &[Expr]) -> Result<(), JreplErr> {
+        let Some(schema) = self.s.get("unevaluatedItems").cloned() else {
+            return Ok(());
+        };
+        let path = self.keyword_path("unevaluatedItems");
+        for (i, item) in items.iter().enumerate() {
+            if self.evaluated.items.contains(&i) {
+                continue;
+            }
+            let mut child = self.instance_path.to_vec();
+            child.push(PathSegment::Index(i));
+            if schema == Expr::Bool(false) {
+                self.fail_at(
+                    child,
+                    "unevaluatedItems",
+                    format!("Item {} is not allowed", i),
+                );
+            } else {
+                self.sub(item, &schema, &child, &path)?;
+            }
+            self.evaluated.items.insert(i);
+        }
+        Ok(())
+    }
+
+    // ---- objects ----
+
+    fn object(&mut self, object: &JsonObject) -> Result<(), JreplErr> {
+        let child = |key: &str| {
+            let mut path = self.instance_path.to_vec();
+            path.push(PathSegment::Key(key.to_string()));
+            path
+        };
+
+        let properties = self.schema_map("properties")?.cloned().unwrap_or_default();
+        for (key, schema) in &properties {
+            if let Some(value) = object.get(key) {
+                let path = format!("{}/{}", self.keyword_path("properties"), escape_token(key));
+                self.sub(value, schema, &child(key), &path)?;
+                self.evaluated.properties.insert(key.clone());
+            }
+        }
+
+        let mut patterns = Vec::new();
+        for (pattern, schema) in self
+            .schema_map("patternProperties")?
+            .cloned()
+            .unwrap_or_default()
+        {
+            patterns.push((self.regex("patternProperties", &pattern)?, pattern, schema));
+        }
+        for (regex, pattern, schema) in &patterns {
+            let path = format!(
+                "{}/{}",
+                self.keyword_path("patternProperties"),
+                escape_token(pattern)
+            );
+            for (key, value) in object.iter().filter(|(key, _)| regex.is_match(key)) {
+                self.sub(value, schema, &child(key), &path)?;
+                self.evaluated.properties.insert(key.clone());
+            }
+        }
+
+        if let Some(schema) = self.s.get("additionalProperties").cloned() {
+            let path = self.keyword_path("additionalProperties");
+            let additional = object.iter().filter(|(key, _)| {
+                !properties.contains_key(*key)
+                    && !patterns.iter().any(|(regex, _, _)| regex.is_match(key))
+            });
+            for (key, value) in additional {
+                if schema == Expr::Bool(false) {
+                    self.fail(
+                        "additionalProperties",
+                        format!("Unexpected property \"{}\"", key),
+                    );
+                } else {
+                    self.sub(value, &schema, &child(key), &path)?;
+                }
+                self.evaluated.properties.insert(key.clone());
+            }
+        }
+
+        if let Some(required) = self.s.get("required") {
+            for name in self.names("required", required)? {
+                if !object.contains_key(&name) {
+                    self.fail(
+                        "required",
+                        format!("Missing required property \"{}\"", name),
+                    );
+                }
+            }
+        }
+        if let Some(min) = self
+            .count("minProperties")?
+            .filter(|min| object.len() < *min)
+        {
+            self.fail(
+                "minProperties",
+                format!(
+                    "Object has {} properties, expected at least {}",
+                    object.len(),
+                    min
+                ),
+            );
+        }
+        if let Some(max) = self
+            .count("maxProperties")?
+            .filter(|max| object.len() > *max)
+        {
+            self.fail(
+                "maxProperties",
+                format!(
+                    "Object has {} properties, expected at most {}",
+                    object.len(),
+                    max
+                ),
+            );
+        }
+
+        if let Some(schema) = self.s.get("propertyNames").cloned() {
+            let path = self.keyword_path("propertyNames");
+            for key in object.keys() {
+                let (violations, _) = self.validator.apply(
+                    &Expr::String(key.clone()),
+                    &schema,
+                    self.base,
+                    &child(key),
+                    &path,
+                    self.depth,
+                )?;
+                if !violations.is_empty() {
+                    self.fail(
+                        "propertyNames",
+                        format!("Property name \"{}\" is not allowed", key),
+                    );
+                }
+            }
+        }
+
+        // draft-07 spells both dependentRequired and dependentSchemas as dependencies
+        let dependency_keywords: &[&str] = if self.draft7() {
+            &["dependencies"]
+        } else {
+            &["dependentRequired", "dependentSchemas"]
+        };
+        for &keyword in dependency_keywords {
+            for (key, dependency) in self.schema_map(keyword)?.cloned().unwrap_or_default() {
+                if !object.contains_key(&key) {
+                    continue;
+                }
+                let path = format!("{}/{}", self.keyword_path(keyword), escape_token(&key));
+                match dependency {
+                    Expr::Array(_) if keyword != "dependentSchemas" => {
+                        for name in self.names(keyword, &dependency)? {
+                            if !object.contains_key(&name) {
+                                self.fail(
+                                    keyword,
+                                    format!(
+                                        "Property \"{}\" is required when \"{}\" is present",
+                                        name, key
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                    schema if keyword != "dependentRequired" => {
+                        self.sub(self.instance, &schema, self.instance_path, &path)?;
+                    }
+                    other => {
+                        return Err(self.malformed(
+                            keyword,
+                            "an array of strings for each property",
+                            &other,
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn unevaluated_properties(&mut self, object: &JsonObject) -> Result<(), JreplErr> {
+        let Some(schema) = self.s.get("unevaluatedProperties").cloned() else {
+            return Ok(());
+        };
+        let path = self.keyword_path("unevaluatedProperties");
+        for (key, value) in object.iter() {
+            if self.evaluated.properties.contains(key) {
+                continue;
+            }
+            if schema == Expr::Bool(false) {
+                self.fail(
+                    "unevaluatedProperties",
+                    format!("Unexpected property \"{}\"", key),
+                );
+            } else {
+                let mut child = self.instance_path.to_vec();
+                child.push(PathSegment::Key(key.clone()));
+                self.sub(value, &schema, &child, &path)?;
+            }
+            self.evaluated.properties.insert(key.clone());
+        }
+        Ok(())
+    }
+
+    // ---- combinators ----
+
+    fn combinators(&mut self) -> Result<(), JreplErr> {
+        if let Some(schemas) = self.schema_list("allOf")? {
+            let schemas = schemas.to_vec();
+            for (i, schema) in schemas.iter().enumerate() {
+                let path = format!("{}/{}", self.keyword_path("allOf"), i);
+                self.sub(self.instance, schema, self.instance_path, &path)?;
+            }
+        }
+
+        if let Some(schemas) = self.schema_list("anyOf")? {
+            let schemas = schemas.to_vec();
+            let mut any = false;
+            for (i, schema) in schemas.iter().enumerate() {
+                let (valid, evaluated) =
+                    self.probe(schema, &format!("{}/{}", self.keyword_path("anyOf"), i))?;
+                if valid {
+                    any = true;
+                    self.evaluated.merge(evaluated);
+                }
+            }
+            if !any {
+                self.fail(
+                    "anyOf",
+                    format!(
+                        "Does not match any of the {} schemas in anyOf",
+                        schemas.len()
+                    ),
+                );
+            }
+        }
+
+        if let Some(schemas) = self.schema_list("oneOf")? {
+            let schemas = schemas.to_vec();
+            let mut matched = Vec::new();
+            for (i, schema) in schemas.iter().enumerate() {
+                let (valid, evaluated) =
+                    self.probe(schema, &format!("{}/{}", self.keyword_path("oneOf"), i))?;
+                if valid {
+                    matched.push((i, evaluated));
+                }
+            }
+            match matched.len() {
+                0 => self.fail(
+                    "oneOf",
+                    format!(
+                        "Does not match any of the {} schemas in oneOf",
+                        schemas.len()
+                    ),
+                ),
+                1 => self.evaluated.merge(matched.pop().unwrap().1),
+                _ => {
+                    let indices: Vec<String> = matched.iter().map(|(i, _)| i.to_string()).collect();
+                    self.fail(
+                        "oneOf",
+                        format!(
+                            "Matches schemas {} in oneOf, expected exactly one",
+                            indices.join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+
+        if let Some(schema) = self.s.get("not") {
+            let (valid, _) = self.probe(schema, &self.keyword_path("not"))?;
+            if valid {
+                self.fail("not", "Matches the schema in not".to_string());
+            }
+        }
+
+        if let Some(condition) = self.s.get("if") {
+            let (holds, evaluated) = self.probe(condition, &self.keyword_path("if"))?;
+            let branch = if holds {
+                self.evaluated.merge(evaluated);
+                "then"
+            } else {
+                "else"
+            };
+            if let Some(schema) = self.s.get(branch) {
+                self.sub(
+                    self.instance,
+                    schema,
+                    self.instance_path,
+                    &self.keyword_path(branch),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// --------------------------------- Helpers --------------------------------- //
+
+fn has_type(value: &Expr, name: &str) -> bool {
+    match (name, value) {
+        ("integer", Expr::Number(n)) => is_whole(n),
+        ("number", Expr::Number(_)) => true,
+        _ => type_name(value) == name || (name == "number" && type_name(value) == "integer"),
+    }
+}
+
+fn type_name(value: &Expr) -> &'static str {
+    match value {
+        Expr::None => "null",
+        Expr::Bool(_) => "boolean",
+        Expr::Number(n) if is_whole(n) => "integer",
+        Expr::Number(_) => "number",
+        Expr::String(_) => "string",
+        Expr::Array(_) | Expr::List(_) => "array",
+        Expr::Object(_) => "object",
+        Expr::File(_) => "file",
+        Expr::Lambda(_) => "function",
+        Expr::Symbol(_) => "symbol",
+    }
+}
+
+// 1.0 is an integer to JSON Schema
+fn is_whole(n: &Number) -> bool {
+    n.is_integer() || n.to_f64().fract() == 0.0
+}
+
+fn is_multiple(n: &Number, m: &Number) -> bool {
+    match n.div(m) {
+        Ok(q) if q.is_integer() => true,
+        Ok(q) => {
+            let q = q.to_f64();
+            // 0.3 / 0.1 is 2.9999999999999996 in f64
+            q.is_finite() && (q - q.round()).abs() <= q.abs().max(1.0) * 1e-9
+        }
+        Err(_) => false,
+    }
+}
+
+fn compact(value: &Expr) -> String {
+    render(
+        value,
+        &PrettyOptions {
+            compact: true,
+            ..PrettyOptions::default()
+        },
+    )
+}
+
+fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", path.to_string_lossy())
+}
+
+fn strip_fragment(uri: &str) -> &str {
+    uri.split_once('#').map_or(uri, |(resource, _)| resource)
+}
+
+fn has_scheme(uri: &str) -> bool {
+    match uri.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// Resolves a URI reference against a base URI, as RFC 3986 does for the forms schemas use:
+/// absolute URIs, fragments, absolute paths and relative paths with "." and "..".
+fn resolve_uri(base: &str, reference: &str) -> String {
+    if has_scheme(reference) {
+        return reference.to_string();
+    }
+    let base = strip_fragment(base);
+    if reference.is_empty() {
+        return base.to_string();
+    }
+    if reference.starts_with('#') {
+        return format!("{}{}", base, reference);
+    }
+
+    // scheme://authority, then the path
+    let path_start = match base.find("://") {
+        Some(at) => base[at + 3..]
+            .find('/')
+            .map_or(base.len(), |slash| at + 3 + slash),
+        None => base.find(':').map_or(0, |colon| colon + 1),
+    };
+    let (origin, base_path) = base.split_at(path_start);
+    if let Some(authority) = reference.strip_prefix("//") {
+        let scheme = base.split_once(':').map_or("", |(scheme, _)| scheme);
+        return format!("{}://{}", scheme, authority);
+    }
+
+    let (reference_path, suffix) = match reference.find(['?', '#']) {
+        Some(at) => reference.split_at(at),
+        None => (reference, ""),
+    };
+    let joined = if reference_path.starts_with('/') {
+        reference_path.to_string()
+    } else {
+        let directory = base_path
+            .rfind('/')
+            .map_or("", |slash| &base_path[..=slash]);
+        format!("{}{}", directory, reference_path)
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+    let parts: Vec<&str> = joined.split('/').collect();
+    for (i, part) in parts.iter().enumerate() {
+        let last = i + 1 == parts.len();
+        match *part {
+            "." => {
+                if last {
+                    segments.push("");
+                }
+            }
+            ".." => {
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+                if last {
+                    segments.push("");
+                }
+            }
+            other => segments.push(other),
+        }
+    }
+    format!("{}{}{}", origin, segments.join("/"), suffix)
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn doc(text: &str) -> Expr {
+        parse_json(text.as_bytes()).unwrap()
+    }
+
+    fn violations(schema: &str, instance: &str) -> Vec<(String, String)> {
+        validate(&doc(instance), &doc(schema), None)
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.instance_path, v.schema_path))
+            .collect()
+    }
+
+    fn valid(schema: &str, instance: &str) -> bool {
+        violations(schema, instance).is_empty()
+    }
+
+    fn pair(instance_path: &str, schema_path: &str) -> (String, String) {
+        (instance_path.to_string(), schema_path.to_string())
+    }
+
+    const PERSON: &str = r#"{
+        "type": "object",
+        "properties": {
+            "name": {"type": "string", "minLength": 1},
+            "age": {"type": "integer", "minimum": 0},
+            "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true}
+        },
+        "required": ["name", "age"],
+        "additionalProperties": false
+    }"#;
+
+    #[test]
+    fn reports_every_violation_with_both_paths() {
+        assert!(valid(
+            PERSON,
+            r#"{"name": "Ann", "age": 30, "tags": ["a", "b"]}"#
+        ));
+        assert_eq!(
+            violations(
+                PERSON,
+                r#"{"name": "", "age": 1.5, "tags": ["a", 1, "a"], "x": 0}"#
+            ),
+            vec![
+                pair("/name", "/properties/name/minLength"),
+                pair("/age", "/properties/age/type"),
+                pair("/tags/1", "/properties/tags/items/type"),
+                pair("/tags", "/properties/tags/uniqueItems"),
+                pair("", "/additionalProperties"),
+            ]
+        );
+        let missing = validate(&doc("{}"), &doc(PERSON), None).unwrap();
+        assert_eq!(missing[0].message, "Missing required property \"name\"");
+        assert_eq!(missing.len(), 2);
+    }
+
+    #[test]
+    fn scalar_keywords() {
+        assert!(valid(r#"{"type": "integer"}"#, "2.0"));
+        assert!(!valid(r#"{"type": ["string", "null"]}"#, "1"));
+        assert!(valid(
+            r#"{"enum": [1, "a", {"b": [true]}]}"#,
+            r#"{"b": [true]}"#
+        ));
+        assert!(!valid(r#"{"const": 1}"#, r#""1""#));
+        assert!(valid(r#"{"multipleOf": 0.1}"#, "0.3"));
+        assert!(!valid(r#"{"multipleOf": 2}"#, "7"));
+        assert!(!valid(r#"{"exclusiveMaximum": 3}"#, "3"));
+        assert!(valid(
+            r#"{"pattern": "^\\d+$", "maxLength": 3}"#,
+            r#""123""#
+        ));
+        assert!(!valid(r#"{"maxLength": 2}"#, r#""日本語""#));
+        assert!(!valid("false", "null"));
+    }
+
+    #[test]
+    fn combinators_and_conditionals() {
+        let one_of = r#"{"oneOf": [{"type": "integer"}, {"minimum": 2}]}"#;
+        assert!(valid(one_of, "1"));
+        assert!(valid(one_of, "2.5"));
+        assert_eq!(violations(one_of, "3"), vec![pair("", "/oneOf")]);
+        assert!(!valid(
+            r#"{"anyOf": [{"type": "string"}, {"type": "null"}]}"#,
+            "1"
+        ));
+        assert!(!valid(r#"{"not": {"type": "null"}}"#, "null"));
+
+        let conditional = r#"{
+            "if": {"properties": {"kind": {"const": "user"}}},
+            "then": {"required": ["email"]},
+            "else": {"required": ["id"]}
+        }"#;
+        assert!(valid(conditional, r#"{"kind": "user", "email": "a@b.c"}"#));
+        assert_eq!(
+            violations(conditional, r#"{"kind": "user"}"#),
+            vec![pair("", "/then/required")]
+        );
+        assert_eq!(
+            violations(conditional, r#"{"kind": "bot"}"#),
+            vec![pair("", "/else/required")]
+        );
+    }
+
+    #[test]
+    fn draft_2020_12_keywords() {
+        let tuple = r#"{"prefixItems": [{"type": "string"}, {"type": "number"}], "items": false}"#;
+        assert!(valid(tuple, r#"["a", 1]"#));
+        assert!(!valid(tuple, r#"["a", 1, 2]"#));
+        assert!(!valid(tuple, r#"[1]"#));
+
+        let contains = r#"{"contains": {"type": "string"}, "minContains": 2, "maxContains": 3}"#;
+        assert!(valid(contains, r#"["a", 1, "b"]"#));
+        assert!(!valid(contains, r#"["a", 1]"#));
+        assert!(valid(
+            r#"{"dependentRequired": {"a": ["b"]}}"#,
+            r#"{"b": 1}"#
+        ));
+        assert!(!valid(
+            r#"{"dependentRequired": {"a": ["b"]}}"#,
+            r#"{"a": 1}"#
+        ));
+
+        let unevaluated = r#"{
+            "allOf": [{"properties": {"a": true}}],
+            "properties": {"b": true},
+            "unevaluatedProperties": false
+        }"#;
+        assert!(valid(unevaluated, r#"{"a": 1, "b": 2}"#));
+        assert_eq!(
+            violations(unevaluated, r#"{"a": 1, "c": 3}"#),
+            vec![pair("", "/unevaluatedProperties")]
+        );
+        assert!(!valid(
+            r#"{"prefixItems": [true], "unevaluatedItems": false}"#,
+            "[1, 2]"
+        ));
+    }
+
+    #[test]
+    fn draft_07_keywords() {
+        let tuple = r#"{
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "items": [{"type": "string"}],
+            "additionalItems": {"type": "integer"},
+            "dependencies": {"a": ["b"], "c": {"required": ["d"]}}
+        }"#;
+        assert!(valid(tuple, r#"["a", 1, 2]"#));
+        assert_eq!(
+            violations(tuple, r#"["a", "b"]"#),
+            vec![pair("/1", "/additionalItems/type")]
+        );
+        assert!(!valid(tuple, r#"{"a": 1}"#));
+        assert!(!valid(tuple, r#"{"c": 1}"#));
+        assert!(valid(tuple, r#"{"c": 1, "d": 2}"#));
+
+        // prefixItems and unevaluatedProperties do not exist in draft-07
+        let later = r#"{"$schema": "http://json-schema.org/draft-07/schema#", "prefixItems": [false], "unevaluatedProperties": false}"#;
+        assert!(valid(later, r#"[1]"#));
+        assert!(valid(later, r#"{"a": 1}"#));
+    }
+
+    #[test]
+    fn refs_within_a_schema() {
+        let tree = r##"{
+            "$defs": {
+                "node": {
+                    "type": "object",
+                    "properties": {"value": {"type": "number"}, "children": {"type": "array", "items": {"$ref": "#/$defs/node"}}}
+                },
+                "named": {"$anchor": "leaf", "type": "string"}
+            },
+            "properties": {"root": {"$ref": "#/$defs/node"}, "label": {"$ref": "#leaf"}}
+        }"##;
+        assert!(valid(
+            tree,
+            r#"{"root": {"value": 1, "children": [{"value": 2, "children": []}]}, "label": "x"}"#
+        ));
+        assert_eq!(
+            violations(
+                tree,
+                r#"{"root": {"children": [{"value": "2"}]}, "label": 3}"#
+            ),
+            vec![
+                pair(
+                    "/root/children/0/value",
+                    "/properties/root/$ref/properties/children/items/$ref/properties/value/type"
+                ),
+                pair("/label", "/properties/label/$ref/type"),
+            ]
+        );
+
+        let draft7 = r##"{
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "definitions": {"positive": {"$id": "#positive", "minimum": 1}},
+            "properties": {"n": {"$ref": "#positive", "type": "string"}}
+        }"##;
+        // the sibling "type" is ignored next to a draft-07 ref
+        assert!(valid(draft7, r#"{"n": 2}"#));
+        assert!(!valid(draft7, r#"{"n": 0}"#));
+
+        let unresolved = validate(&doc("1"), &doc(r##"{"$ref": "#/$defs/missing"}"##), None);
+        assert!(matches!(unresolved, Err(JreplErr::JsonErr(_))));
+        let endless = validate(&doc("1"), &doc(r##"{"$ref": "#"}"##), None);
+        assert!(matches!(endless, Err(JreplErr::JsonErr(_))));
+    }
+
+    #[test]
+    fn dynamic_refs_follow_the_dynamic_scope() {
+        let tree = r##"{
+            "$id": "https://example.com/tree",
+            "$dynamicAnchor": "node",
+            "type": "object",
+            "properties": {
+                "data": true,
+                "children": {"type": "array", "items": {"$dynamicRef": "#node"}}
+            }
+        }"##;
+        let strict_tree = format!(
+            r#"{{
+                "$id": "https://example.com/strict-tree",
+                "$dynamicAnchor": "node",
+                "$ref": "tree",
+                "unevaluatedProperties": false,
+                "$defs": {{"tree": {}}}
+            }}"#,
+            tree
+        );
+        let misspelled = r#"{"children": [{"daat": 1}]}"#;
+        assert!(valid(tree, misspelled));
+        assert!(valid(&strict_tree, r#"{"children": [{"data": 1}]}"#));
+        assert_eq!(
+            violations(&strict_tree, misspelled),
+            vec![
+                pair(
+                    "/children/0",
+                    "/$ref/properties/children/items/$dynamicRef/unevaluatedProperties"
+                ),
+                // the failed $ref evaluated nothing, so "children" is unevaluated too
+                pair("", "/unevaluatedProperties"),
+            ]
+        );
+
+        // a "$dynamicRef" to a plain "$anchor" is a plain ref, whatever encloses it
+        let plain = r##"{
+            "$id": "https://example.com/outer",
+            "$dynamicAnchor": "item",
+            "$ref": "inner",
+            "$defs": {
+                "inner": {
+                    "$id": "https://example.com/inner",
+                    "properties": {"x": {"$dynamicRef": "#item"}},
+                    "$defs": {"item": {"$anchor": "item", "type": "string"}}
+                }
+            }
+        }"##;
+        assert!(valid(plain, r#"{"x": "a"}"#));
+        assert!(!valid(plain, r#"{"x": 1}"#));
+    }
+
+    #[test]
+    fn refs_across_files() {
+        let order = doc(
+            r#"{"shipping": {"street": "Main St", "zip": "12345"}, "customer": {"name": "Ann", "email": "ann@example.com"}}"#,
+        );
+        assert_eq!(
+            validate_with_file(&order, "dev/schemas/order.json"),
+            Ok(vec![])
+        );
+
+        let bad = doc(
+            r#"{"shipping": {"street": "Main St", "zip": "1"}, "customer": {"email": "ann@example.com"}}"#,
+        );
+        let found: Vec<(String, String)> = validate_with_file(&bad, "dev/schemas/order.json")
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.instance_path, v.schema_path))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                pair(
+                    "/shipping/zip",
+                    "/properties/shipping/$ref/properties/zip/pattern"
+                ),
+                pair("/customer", "/properties/customer/$ref/required"),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_uri_follows_rfc_3986() {
+        let base = "https://example.com/schemas/order.json";
+        assert_eq!(
+            resolve_uri(base, "address.json"),
+            "https://example.com/schemas/address.json"
+        );
+        assert_eq!(
+            resolve_uri(base, "../common/x.json#/a"),
+            "https://example.com/common/x.json#/a"
+        );
+        assert_eq!(
+            resolve_uri(base, "/root.json"),
+            "https://example.com/root.json"
+        );
+        assert_eq!(
+            resolve_uri(base, "#/$defs/a"),
+            "https://example.com/schemas/order.json#/$defs/a"
+        );
+        assert_eq!(resolve_uri(base, "urn:uuid:1"), "urn:uuid:1");
+        assert_eq!(
+            resolve_uri("file:///a/b/c.json", "./d.json"),
+            "file:///a/b/d.json"
+        );
+    }
+}
@@ -0,0 +1,9 @@
+pub mod appstate;
+pub mod command_interpreter;
+pub mod errors;
+pub mod io;
+pub mod json;
+pub mod statics;
+
+#[cfg(test)]
+mod test_support;
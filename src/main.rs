@@ -1,37 +1,82 @@
-mod appstate;
-mod command_interpreter;
-mod errors;
-mod io;
-mod statics;
+use std::{
+    fs,
+    io::{IsTerminal, stdin, stdout},
+    process::ExitCode,
+    thread,
+};
 
 use clap::Parser;
-use command_interpreter::interpreter::interpret;
-use rustyline::DefaultEditor;
-use rustyline::Result;
-use statics::commands::get_commands;
-
-use crate::{
+use jrepl::{
     appstate::AppState,
-    command_interpreter::types::{Effect, Expr},
+    command_interpreter::{
+        eval::EVAL_STACK_SIZE,
+        interpreter::{interpret, interpret_each},
+        span::render_diagnostic,
+        types::{Effect, Expr, Referent},
+    },
+    errors::errors::JreplErr,
+    io::read::{extract_json, read_json_from},
+    json::pretty::{PrettyOptions, render},
+    statics::commands::get_commands,
 };
+use rustyline::DefaultEditor;
+
+// Exit statuses of script and -e runs. Command line usage errors exit with 2 from clap.
+const EXIT_SCRIPT_ERR: u8 = 1;
+const EXIT_INPUT_ERR: u8 = 3;
+const EXIT_OUTPUT_ERR: u8 = 4;
+
+/// The symbol piped input is bound to in -e mode.
+const INPUT_SYMBOL: &str = "$";
+
+fn main() -> ExitCode {
+    // user functions recurse on the Rust stack, so the session gets one deep enough for them
+    let session = thread::Builder::new()
+        .stack_size(EVAL_STACK_SIZE)
+        .spawn(session)
+        .expect("cannot start the session thread");
+    session
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
 
-fn main() -> Result<()> {
+fn session() -> ExitCode {
     let args = Args::parse();
-    // let json = extract_json(&args.input_files);
 
     let mut app_state = AppState::new();
     app_state.set_commands(get_commands());
+    // in -e mode stdout carries only the result
+    let inputs_loaded = bind_input_files(&mut app_state, &args.input_files, args.expr.is_none());
+    if !inputs_loaded && (args.expr.is_some() || args.file.is_some() || args.script.is_some()) {
+        return ExitCode::from(EXIT_INPUT_ERR);
+    }
+
+    if let Some(expr) = &args.expr {
+        return run_expr(&mut app_state, expr, args.compact);
+    }
+    match args.file.as_ref().or(args.script.as_ref()) {
+        Some(path) => run_script(&mut app_state, path),
+        None => match repl(app_state) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{}", err);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
 
+fn repl(mut app_state: AppState) -> rustyline::Result<()> {
     let mut tui = DefaultEditor::new()?;
 
     loop {
         let user_input = tui.readline(">")?;
         let effect = interpret(&app_state, &user_input);
 
-        print_effect(&effect);
+        print_effect(&effect, &user_input);
 
         if let Some(state) = effect.next_state {
-            app_state.set_next_state(state);
+            app_state.commit(state, &user_input);
         }
 
         // update UI based on changed state
@@ -44,13 +89,161 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-pub fn print_effect(effect: &Effect) {
+/// Runs every form in the file at `path` without prompting, stopping at the first error.
+fn run_script(app_state: &mut AppState, path: &str) -> ExitCode {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Error reading script '{}': {}", path, err);
+            return ExitCode::from(EXIT_INPUT_ERR);
+        }
+    };
+
+    // each form's value is printed as it is evaluated, the way a batch job's log reads
+    let effect = interpret_each(app_state, &source, print_form_output);
+    if let Some(err) = &effect.err {
+        match effect.err_span {
+            Some(span) => eprint!("{}", render_diagnostic(&source, span, err)),
+            None => eprintln!("{}", err),
+        }
+        return ExitCode::from(EXIT_SCRIPT_ERR);
+    }
+    if let Some(state) = effect.next_state {
+        app_state.set_next_state(state);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Evaluates `expr` against piped JSON bound to `$`, printing the result to stdout as JSON.
+/// Everything else, errors included, goes to stderr.
+fn run_expr(app_state: &mut AppState, expr: &str, compact: bool) -> ExitCode {
+    let input = stdin();
+    if !input.is_terminal() {
+        match read_json_from(input.lock(), "stdin") {
+            Ok(Some(value)) => {
+                let next_state = app_state.apply_action(|state| {
+                    state
+                        .symbol_table
+                        .insert(INPUT_SYMBOL.to_string(), Referent::Expr(value));
+                });
+                app_state.set_next_state(next_state);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("{}", err);
+                return ExitCode::from(EXIT_INPUT_ERR);
+            }
+        }
+    }
+
+    let effect = interpret(app_state, expr);
+    if let Some(err) = &effect.err {
+        match effect.err_span {
+            Some(span) => eprint!("{}", render_diagnostic(expr, span, err)),
+            None => eprintln!("{}", err),
+        }
+        return ExitCode::from(EXIT_SCRIPT_ERR);
+    }
+    if let Some(feedback) = &effect.user_feedback {
+        eprintln!("{}", feedback);
+    }
+
+    let value = effect.eval_value.unwrap_or(Expr::None);
+    let json = serde_json::Value::try_from(&value).and_then(|json| {
+        let text = if compact {
+            serde_json::to_string(&json)
+        } else {
+            serde_json::to_string_pretty(&json)
+        };
+        text.map_err(|err| JreplErr::JsonErr(err.to_string()))
+    });
+    match json {
+        Ok(text) => {
+            println!("{}", text);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(EXIT_OUTPUT_ERR)
+        }
+    }
+}
+
+/// Binds each `--json` file to a symbol. A file that fails to load is reported and skipped.
+/// Returns whether every file loaded. `announce` prints what was bound to stdout.
+fn bind_input_files(app_state: &mut AppState, input_files: &[String], announce: bool) -> bool {
+    let reserved: Vec<String> = app_state
+        .get_commands()
+        .iter()
+        .map(|cmd| cmd.symbol.clone())
+        .collect();
+
+    let mut inputs = Vec::new();
+    let mut all_loaded = true;
+    for result in extract_json(input_files, &reserved) {
+        match result {
+            Ok(input) => inputs.push(input),
+            Err(err) => {
+                all_loaded = false;
+                print_effect(&Effect::from_err(err), "");
+            }
+        }
+    }
+
+    if inputs.is_empty() {
+        return all_loaded;
+    }
+
+    let next_state = app_state.apply_action(|state| {
+        for input in &inputs {
+            state
+                .symbol_table
+                .insert(input.symbol.clone(), Referent::Expr(input.value.clone()));
+            state
+                .provenance
+                .insert(input.symbol.clone(), input.path.clone());
+        }
+    });
+    app_state.set_next_state(next_state);
+
+    for input in inputs.iter().filter(|_| announce) {
+        println!("Loaded '{}' as {}", input.path, input.symbol);
+    }
+    all_loaded
+}
+
+/// Prints what one form of a script produced: its value to stdout, feedback to stderr.
+fn print_form_output(effect: &Effect) {
+    match &effect.eval_value {
+        // one form's output never runs into the next one's
+        Some(Expr::String(s)) if !s.ends_with('\n') => println!("{}", s),
+        Some(value) => print_value(value),
+        None => {}
+    }
+    if let Some(feedback) = &effect.user_feedback {
+        eprintln!("{}", feedback);
+    }
+}
+
+fn print_value(value: &Expr) {
+    match value {
+        Expr::String(s) => print!("{}", s), // <-- preserves newlines
+        other => {
+            let options = PrettyOptions {
+                color: stdout().is_terminal(),
+                ..PrettyOptions::default()
+            };
+            println!("{}", render(other, &options))
+        }
+    }
+}
+
+/// Prints every part of `effect`. Errors with a span are shown against `source`, the input that
+/// produced them.
+pub fn print_effect(effect: &Effect, source: &str) {
     // Value
     if let Some(ev) = &effect.eval_value {
-        match ev {
-            Expr::String(s) => print!("{}", s), // <-- preserves newlines
-            other => println!("Evaluated value: {:?}", other),
-        }
+        print_value(ev);
     }
 
     // Next state
@@ -67,9 +260,12 @@ pub fn print_effect(effect: &Effect) {
         println!("User feedback: {}", feedback);
     }
 
-    // Error (keep debug unless you implement Display for JreplErr)
+    // Error
     if let Some(err) = &effect.err {
-        eprintln!("Error: {:?}", err);
+        match effect.err_span {
+            Some(span) => eprint!("{}", render_diagnostic(source, span, err)),
+            None => eprintln!("{}", err),
+        }
     }
 
     // Empty effect
@@ -83,8 +279,37 @@ pub fn print_effect(effect: &Effect) {
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(
+    author,
+    version,
+    about,
+    after_help = "Exit status: 0 on success, 1 if evaluation fails, 2 on a usage error, \
+                  3 if an input (stdin, --json file, script) cannot be read or parsed, \
+                  4 if the result of -e cannot be written as JSON."
+)]
 struct Args {
+    /// JSON files to bind at startup, as `path` (symbol from the file stem) or `name=path`.
     #[arg(short = 'j', long = "json", num_args = 1..)]
     input_files: Vec<String>,
+
+    /// Run a script of jrepl forms instead of starting the REPL. Exits nonzero on the first error.
+    #[arg(value_name = "SCRIPT")]
+    script: Option<String>,
+
+    /// Same as SCRIPT, for use after --json.
+    #[arg(
+        short = 'f',
+        long = "file",
+        value_name = "SCRIPT",
+        conflicts_with = "script"
+    )]
+    file: Option<String>,
+
+    /// Evaluate EXPR against JSON read from stdin, bound to `$`, and print the result as JSON.
+    #[arg(short = 'e', long = "expr", value_name = "EXPR", conflicts_with_all = ["script", "file"])]
+    expr: Option<String>,
+
+    /// With -e, print the result on one line instead of indented.
+    #[arg(short = 'c', long = "compact", requires = "expr")]
+    compact: bool,
 }
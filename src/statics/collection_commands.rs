@@ -0,0 +1,627 @@
+/*
+    Collection commands.
+
+    They accept evaluated lists, JSON arrays and JSON objects, and always return new values.
+    Objects act as the sequence of their values: map, filter, sort-by, distinct, take and drop
+    keep each value's key and return an object, the others work on the values alone. Results
+    come back as a list when the input was a list and as a JSON array otherwise.
+
+    Function arguments may be a command symbol such as +, a user function, or a fn expression.
+*/
+
+use std::{cmp::Ordering, collections::HashSet, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::FromPrimitive;
+
+use crate::{
+    appstate::AppState,
+    command_interpreter::{
+        command::Command,
+        eval::{apply, number_of, value_of},
+        number::Number,
+        types::{Effect, Expr, JsonObject},
+    },
+    errors::errors::JreplErr,
+};
+
+pub fn get_collection_commands() -> Vec<Command> {
+    vec![
+        Command {
+            symbol: "map".to_string(),
+            description: "map \t Apply f to every element. Usage: (map <f> <coll>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("map", app_state, exprs, 2, 1)?;
+                let entries = coll
+                    .entries()
+                    .into_iter()
+                    .map(|(key, v)| Ok((key, apply(app_state, &exprs[0], &[v])?)))
+                    .collect::<Result<Vec<_>, JreplErr>>()?;
+                Ok(Effect::from_eval_value(coll.rebuild(entries)))
+            }),
+        },
+        Command {
+            symbol: "filter".to_string(),
+            description: "filter \t Keep the elements for which pred returns true. Usage: (filter <pred> <coll>)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("filter", app_state, exprs, 2, 1)?;
+                let mut kept = Vec::new();
+                for (key, v) in coll.entries() {
+                    if truth_of("filter", apply(app_state, &exprs[0], std::slice::from_ref(&v))?)? {
+                        kept.push((key, v));
+                    }
+                }
+                Ok(Effect::from_eval_value(coll.rebuild(kept)))
+            }),
+        },
+        Command {
+            symbol: "reduce".to_string(),
+            description: "reduce \t Fold the elements with f. Usage: (reduce <f> <init> <coll>) or (reduce <f> <coll>)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let (init, coll) = match exprs.len() {
+                    2 => (None, Coll::from_value("reduce", value_of(app_state, &exprs[1])?)?),
+                    3 => (
+                        Some(value_of(app_state, &exprs[1])?),
+                        Coll::from_value("reduce", value_of(app_state, &exprs[2])?)?,
+                    ),
+                    _ => {
+                        return Err(JreplErr::OperatorFormatErr(
+                            "'reduce' expects (reduce <f> <init> <coll>) or (reduce <f> <coll>)".to_string(),
+                        ));
+                    }
+                };
+
+                let mut values = coll.values().into_iter();
+                let init = match init.or_else(|| values.next()) {
+                    Some(init) => init,
+                    None => {
+                        return Err(JreplErr::OperatorFormatErr(
+                            "'reduce' of an empty collection needs an initial value".to_string(),
+                        ));
+                    }
+                };
+                let result = values.try_fold(init, |acc, v| apply(app_state, &exprs[0], &[acc, v]))?;
+                Ok(Effect::from_eval_value(result))
+            }),
+        },
+        Command {
+            symbol: "sort-by".to_string(),
+            description: "sort-by \t Stable sort by the key f returns. Usage: (sort-by <f> <coll>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("sort-by", app_state, exprs, 2, 1)?;
+                let mut keyed = coll
+                    .entries()
+                    .into_iter()
+                    .map(|(key, v)| Ok((apply(app_state, &exprs[0], std::slice::from_ref(&v))?, (key, v))))
+                    .collect::<Result<Vec<_>, JreplErr>>()?;
+                keyed.sort_by(|(a, _), (b, _)| compare_values(a, b));
+                Ok(Effect::from_eval_value(
+                    coll.rebuild(keyed.into_iter().map(|(_, entry)| entry).collect()),
+                ))
+            }),
+        },
+        Command {
+            symbol: "group-by".to_string(),
+            description: "group-by \t Group the elements into an object keyed by what f returns. Usage: (group-by <f> <coll>)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("group-by", app_state, exprs, 2, 1)?;
+                let mut groups: indexmap::IndexMap<String, Vec<Expr>> = indexmap::IndexMap::new();
+                for v in coll.values() {
+                    let key = group_key(&apply(app_state, &exprs[0], std::slice::from_ref(&v))?);
+                    groups.entry(key).or_default().push(v);
+                }
+                let groups = groups
+                    .into_iter()
+                    .map(|(key, items)| (key, coll.sequence(items)))
+                    .collect::<JsonObject>();
+                Ok(Effect::from_eval_value(Expr::object(groups)))
+            }),
+        },
+        Command {
+            symbol: "distinct".to_string(),
+            description: "distinct \t Drop repeated elements, keeping the first. Usage: (distinct <coll>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("distinct", app_state, exprs, 1, 0)?;
+                let mut seen = HashSet::new();
+                let kept = coll
+                    .entries()
+                    .into_iter()
+                    .filter(|(_, v)| seen.insert(canonical_key(v)))
+                    .collect();
+                Ok(Effect::from_eval_value(coll.rebuild(kept)))
+            }),
+        },
+        Command {
+            symbol: "flatten".to_string(),
+            description: "flatten \t Splice nested arrays and lists into one sequence. Usage: (flatten <coll>) or (flatten <coll> <depth>)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let depth = match exprs.len() {
+                    1 => usize::MAX,
+                    2 => count_of("flatten", number_of(app_state, &exprs[1])?)?,
+                    _ => {
+                        return Err(JreplErr::OperatorFormatErr(
+                            "'flatten' expects (flatten <coll>) or (flatten <coll> <depth>)".to_string(),
+                        ));
+                    }
+                };
+                let coll = Coll::from_value("flatten", value_of(app_state, &exprs[0])?)?;
+                let mut out = Vec::new();
+                flatten_into(coll.values(), depth, &mut out);
+                Ok(Effect::from_eval_value(coll.sequence(out)))
+            }),
+        },
+        Command {
+            symbol: "zip".to_string(),
+            description: "zip \t Pair up elements by position, stopping at the shortest. Usage: (zip <coll> <coll> ...)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() < 2 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'zip' expects at least 2 collections".to_string(),
+                    ));
+                }
+                let colls = exprs
+                    .iter()
+                    .map(|e| Coll::from_value("zip", value_of(app_state, e)?))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let columns: Vec<Vec<Expr>> = colls.iter().map(|c| c.values()).collect();
+                let len = columns.iter().map(|c| c.len()).min().unwrap_or(0);
+                let rows = (0..len)
+                    .map(|i| colls[0].sequence(columns.iter().map(|c| c[i].clone()).collect()))
+                    .collect();
+                Ok(Effect::from_eval_value(colls[0].sequence(rows)))
+            }),
+        },
+        Command {
+            symbol: "take".to_string(),
+            description: "take \t The first n elements. Usage: (take <n> <coll>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("take", app_state, exprs, 2, 1)?;
+                let n = count_of("take", number_of(app_state, &exprs[0])?)?;
+                Ok(Effect::from_eval_value(
+                    coll.rebuild(coll.entries().into_iter().take(n).collect()),
+                ))
+            }),
+        },
+        Command {
+            symbol: "drop".to_string(),
+            description: "drop \t Everything but the first n elements. Usage: (drop <n> <coll>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("drop", app_state, exprs, 2, 1)?;
+                let n = count_of("drop", number_of(app_state, &exprs[0])?)?;
+                Ok(Effect::from_eval_value(
+                    coll.rebuild(coll.entries().into_iter().skip(n).collect()),
+                ))
+            }),
+        },
+        Command {
+            symbol: "count".to_string(),
+            description: "count \t Number of elements, keys or characters. Usage: (count <coll>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() != 1 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'count' expects exactly 1 argument: <coll>".to_string(),
+                    ));
+                }
+                let n = match value_of(app_state, &exprs[0])? {
+                    Expr::String(s) => s.chars().count(),
+                    other => Coll::from_value("count", other)?.len(),
+                };
+                Ok(Effect::from_eval_value(Expr::Number(n.into())))
+            }),
+        },
+        Command {
+            symbol: "first".to_string(),
+            description: "first \t The first element, or None when empty. Usage: (first <coll>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("first", app_state, exprs, 1, 0)?;
+                Ok(Effect::from_eval_value(
+                    coll.values().into_iter().next().unwrap_or(Expr::None),
+                ))
+            }),
+        },
+        Command {
+            symbol: "last".to_string(),
+            description: "last \t The last element, or None when empty. Usage: (last <coll>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let coll = coll_arg("last", app_state, exprs, 1, 0)?;
+                Ok(Effect::from_eval_value(
+                    coll.values().into_iter().last().unwrap_or(Expr::None),
+                ))
+            }),
+        },
+    ]
+}
+
+/// A collection argument, kept in its original shape so results can be rebuilt to match.
+enum Coll {
+    List(Vec<Expr>),
+    Array(Rc<Vec<Expr>>),
+    Object(Rc<JsonObject>),
+}
+
+impl Coll {
+    fn from_value(symbol: &str, value: Expr) -> Result<Coll, JreplErr> {
+        match value {
+            Expr::List(items) => Ok(Coll::List(items)),
+            Expr::Array(items) => Ok(Coll::Array(items)),
+            Expr::Object(map) => Ok(Coll::Object(map)),
+            other => Err(JreplErr::OperatorFormatErr(format!(
+                "'{}' expects a list, array or object, got {:?}",
+                symbol, other
+            ))),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Coll::List(items) => items.len(),
+            Coll::Array(items) => items.len(),
+            Coll::Object(map) => map.len(),
+        }
+    }
+
+    fn values(&self) -> Vec<Expr> {
+        match self {
+            Coll::List(items) => items.clone(),
+            Coll::Array(items) => items.to_vec(),
+            Coll::Object(map) => map.values().cloned().collect(),
+        }
+    }
+
+    fn entries(&self) -> Vec<(Option<String>, Expr)> {
+        match self {
+            Coll::Object(map) => map
+                .iter()
+                .map(|(k, v)| (Some(k.clone()), v.clone()))
+                .collect(),
+            _ => self.values().into_iter().map(|v| (None, v)).collect(),
+        }
+    }
+
+    /// Same shape as the input: an object keeps the entries' keys.
+    fn rebuild(&self, entries: Vec<(Option<String>, Expr)>) -> Expr {
+        match self {
+            Coll::Object(_) => Expr::object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.unwrap_or_default(), v))
+                    .collect(),
+            ),
+            _ => self.sequence(entries.into_iter().map(|(_, v)| v).collect()),
+        }
+    }
+
+    /// A plain sequence: a list for list input, a JSON array otherwise.
+    fn sequence(&self, items: Vec<Expr>) -> Expr {
+        match self {
+            Coll::List(_) => Expr::List(items),
+            _ => Expr::array(items),
+        }
+    }
+}
+
+// Checks the argument count and evaluates the collection argument at `coll_index`.
+fn coll_arg(
+    symbol: &str,
+    app_state: &AppState,
+    exprs: &[Expr],
+    arity: usize,
+    coll_index: usize,
+) -> Result<Coll, JreplErr> {
+    if exprs.len() != arity {
+        return Err(JreplErr::OperatorFormatErr(format!(
+            "'{}' expects exactly {} argument(s), got {}",
+            symbol,
+            arity,
+            exprs.len()
+        )));
+    }
+    Coll::from_value(symbol, value_of(app_state, &exprs[coll_index])?)
+}
+
+fn truth_of(symbol: &str, value: Expr) -> Result<bool, JreplErr> {
+    match value {
+        Expr::Bool(b) => Ok(b),
+        other => Err(JreplErr::OperatorFormatErr(format!(
+            "'{}' predicate must return a Bool, got {:?}",
+            symbol, other
+        ))),
+    }
+}
+
+fn count_of(symbol: &str, n: Number) -> Result<usize, JreplErr> {
+    n.to_usize().ok_or_else(|| {
+        JreplErr::OperatorFormatErr(format!(
+            "'{}' expects a non-negative whole number, got {}",
+            symbol, n
+        ))
+    })
+}
+
+fn flatten_into(items: Vec<Expr>, depth: usize, out: &mut Vec<Expr>) {
+    for item in items {
+        match item {
+            Expr::Array(inner) if depth > 0 => flatten_into(inner.to_vec(), depth - 1, out),
+            Expr::List(inner) if depth > 0 => flatten_into(inner, depth - 1, out),
+            other => out.push(other),
+        }
+    }
+}
+
+// Object keys for group-by: strings as they are, everything else as its JSON text.
+fn group_key(key: &Expr) -> String {
+    match key {
+        Expr::String(s) => s.clone(),
+        other => canonical_key(other),
+    }
+}
+
+/// A string that is equal for structurally equal values, object key order aside.
+fn canonical_key(value: &Expr) -> String {
+    match value {
+        Expr::String(s) => format!("{:?}", s),
+        Expr::Number(n) => number_key(n),
+        Expr::Bool(b) => b.to_string(),
+        Expr::None => "null".to_string(),
+        Expr::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(canonical_key)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Expr::List(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(canonical_key)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Expr::Object(map) => {
+            let mut entries: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{:?}:{}", k, canonical_key(v)))
+                .collect();
+            entries.sort();
+            format!("{{{}}}", entries.join(","))
+        }
+        Expr::Lambda(lambda) => format!("<fn {:p}>", Rc::as_ptr(lambda)),
+        Expr::File(file) => format!("<file {:?}>", file.path),
+        Expr::Symbol(s) => s.clone(),
+    }
+}
+
+// Equal numbers share a key however they were written: whole numbers as exact integers, so 2,
+// 2.0 and 2e0 agree, anything else as the shortest text that reads back as the same f64.
+fn number_key(n: &Number) -> String {
+    match n {
+        Number::Float { value, .. } if value.fract() == 0.0 => {
+            BigInt::from_f64(*value).map_or_else(|| value.to_string(), |i| i.to_string())
+        }
+        Number::Float { value, .. } => Number::float(*value).to_string(),
+        exact => exact.to_string(),
+    }
+}
+
+/// Total order used for sorting: null < bool < number < string < array < object, then by value.
+pub fn compare_values(a: &Expr, b: &Expr) -> Ordering {
+    fn rank(e: &Expr) -> u8 {
+        match e {
+            Expr::None => 0,
+            Expr::Bool(_) => 1,
+            Expr::Number(_) => 2,
+            Expr::String(_) => 3,
+            Expr::Array(_) | Expr::List(_) => 4,
+            Expr::Object(_) => 5,
+            _ => 6,
+        }
+    }
+    fn items(e: &Expr) -> &[Expr] {
+        match e {
+            Expr::Array(items) => items,
+            Expr::List(items) => items,
+            _ => &[],
+        }
+    }
+
+    match (a, b) {
+        (Expr::Bool(x), Expr::Bool(y)) => x.cmp(y),
+        (Expr::Number(x), Expr::Number(y)) => x.total_cmp(y),
+        (Expr::String(x), Expr::String(y)) => x.cmp(y),
+        (Expr::Object(x), Expr::Object(y)) => x.len().cmp(&y.len()),
+        _ if rank(a) == 4 && rank(b) == 4 => {
+            let (xs, ys) = (items(a), items(b));
+            xs.iter()
+                .zip(ys)
+                .map(|(x, y)| compare_values(x, y))
+                .find(|o| o.is_ne())
+                .unwrap_or_else(|| xs.len().cmp(&ys.len()))
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{value, value_session};
+    use serde_json::json;
+
+    fn run_session(inputs: &[&str]) -> Result<serde_json::Value, JreplErr> {
+        value_session(inputs).map(|v| serde_json::Value::try_from(&v).unwrap())
+    }
+
+    // binds `users` to the array in dev/array_of_objects.json
+    fn run(input: &str) -> Result<serde_json::Value, JreplErr> {
+        run_session(&[
+            "(defn users (get (ld \"dev/array_of_objects.json\") \"users\"))",
+            input,
+        ])
+    }
+
+    #[test]
+    fn map_with_command_and_lambda() {
+        assert_eq!(
+            run("(map (fn (u) (get u \"name\")) users)"),
+            Ok(json!(["Bob", "Carol"]))
+        );
+        assert_eq!(
+            run_session(&["(defn double (x) (* x 2))", "(map double (1 2 3))"]),
+            Ok(json!([2, 4, 6]))
+        );
+        assert_eq!(run("(map count users)"), Ok(json!([2, 2])));
+    }
+
+    #[test]
+    fn map_over_object_keeps_keys() {
+        assert_eq!(
+            run(
+                "(map (fn (n) (* n 10)) (dissoc-in (get (ld \"dev/deeply_nested.json\") \"/config/window\") \"theme\"))"
+            ),
+            Ok(json!({"width": 8000, "height": 6000}))
+        );
+    }
+
+    #[test]
+    fn filter_keeps_matching_elements() {
+        assert_eq!(
+            run_session(&["(defn keep (x) true)", "(filter keep (1 2))"]),
+            Ok(json!([1, 2]))
+        );
+
+        assert_eq!(
+            run("(filter (fn (v) false) (ld \"dev/simple_object.json\"))"),
+            Ok(json!({}))
+        );
+    }
+
+    #[test]
+    fn filter_requires_bool_predicate() {
+        assert!(matches!(
+            run("(filter (fn (u) 1) users)"),
+            Err(JreplErr::OperatorFormatErr(_))
+        ));
+    }
+
+    #[test]
+    fn reduce_with_and_without_init() {
+        assert_eq!(
+            run("(reduce + 0 (map (fn (u) (get u \"age\")) users))"),
+            Ok(json!(53))
+        );
+        assert_eq!(run("(reduce * (2 3 4))"), Ok(json!(24)));
+        assert_eq!(
+            run("(reduce + 7 (query users \"$.nothing\"))"),
+            Ok(json!(7))
+        );
+        assert!(run("(reduce + (query users \"$.nothing\"))").is_err());
+    }
+
+    #[test]
+    fn sort_by_orders_by_key() {
+        assert_eq!(
+            run("(map (fn (u) (get u \"name\")) (sort-by (fn (u) (- 0 (get u \"age\"))) users))"),
+            Ok(json!(["Carol", "Bob"]))
+        );
+        assert_eq!(
+            run_session(&["(defn id (x) x)", "(sort-by id (\"b\" 3 \"a\" 1 true))"]),
+            Ok(json!([true, 1, 3, "a", "b"]))
+        );
+    }
+
+    #[test]
+    fn group_by_builds_object_of_groups() {
+        assert_eq!(
+            run("(group-by (fn (u) (get u \"age\")) users)"),
+            Ok(json!({"25": [{"name": "Bob", "age": 25}], "28": [{"name": "Carol", "age": 28}]}))
+        );
+        assert_eq!(
+            run_session(&[
+                "(defn same (x) \"all\")",
+                "(map count (group-by same (1 2)))"
+            ]),
+            Ok(json!({"all": 2}))
+        );
+    }
+
+    #[test]
+    fn distinct_compares_structurally() {
+        assert_eq!(run("(distinct (1 2 1 \"1\" 2))"), Ok(json!([1, 2, "1"])));
+        assert_eq!(run("(count (distinct (zip users users)))"), Ok(json!(2)));
+    }
+
+    #[test]
+    fn equal_numbers_written_differently_are_the_same_key() {
+        assert_eq!(
+            value("(distinct [1.5 1.50 2.0 2 2e0 -0.0 0])"),
+            value("[1.5 2.0 -0.0]")
+        );
+        assert_eq!(
+            value("(group-by (fn (x) x) [2 2.0 1.50 1.5])"),
+            value(r#"{"2" [2 2.0] "1.5" [1.50 1.5]}"#)
+        );
+        assert_eq!(
+            value("(count (distinct [{\"a\" 1} {\"a\" 1.0} [0.1] [0.10]]))"),
+            Ok(Expr::Number(Number::Int(2)))
+        );
+    }
+
+    #[test]
+    fn flatten_splices_nested_sequences() {
+        assert_eq!(
+            run("(flatten (query (ld \"dev/mixed_types.json\") \"$[*]\"))"),
+            Ok(json!([1, "rust", "json", "cli", null, 99.5]))
+        );
+        assert_eq!(
+            run("(flatten (zip (zip (1) (2)) (3)) 1)"),
+            Ok(json!([[1, 2], 3]))
+        );
+        assert_eq!(
+            run("(flatten (zip (zip (1) (2)) (3)))"),
+            Ok(json!([1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn zip_stops_at_shortest() {
+        assert_eq!(
+            run("(zip (1 2 3) (\"a\" \"b\"))"),
+            Ok(json!([[1, "a"], [2, "b"]]))
+        );
+    }
+
+    #[test]
+    fn take_drop_count_first_last() {
+        assert_eq!(run("(take 2 (1 2 3))"), Ok(json!([1, 2])));
+        assert_eq!(run("(drop 2 (1 2 3))"), Ok(json!([3])));
+        assert_eq!(
+            run("(take 1 (ld \"dev/simple_object.json\"))"),
+            Ok(json!({"name": "Alice"}))
+        );
+        assert_eq!(run("(count users)"), Ok(json!(2)));
+        assert_eq!(run("(count \"hello\")"), Ok(json!(5)));
+        assert_eq!(run("(get (first users) \"name\")"), Ok(json!("Bob")));
+        assert_eq!(run("(get (last users) \"name\")"), Ok(json!("Carol")));
+        assert_eq!(run("(first (query users \"$.nothing\"))"), Ok(json!(null)));
+        assert!(run("(take 1.5 (1 2))").is_err());
+    }
+
+    #[test]
+    fn collection_commands_leave_input_unchanged() {
+        assert_eq!(
+            run_session(&[
+                "(defn xs (query (ld \"dev/mixed_types.json\") \"$.tags[*]\"))",
+                "(defn ys (drop 1 xs))",
+                "(count xs)"
+            ]),
+            Ok(json!(3))
+        );
+    }
+}
@@ -1,22 +1,33 @@
-use std::fs;
-
-use clap::builder;
+use std::{collections::HashMap, fs, path::Path, rc::Rc};
 
+use crate::command_interpreter::command::Command;
 use crate::{
     appstate::AppState,
     command_interpreter::{
-        eval::number_of,
-        types::{Effect, FileValue},
+        eval::{eval, eval_sequence, number_of},
+        number::Number,
+        types::{Effect, FileValue, JsonObject, Lambda, Scope},
     },
 };
-use crate::{appstate::State, command_interpreter::command::Command};
 use crate::{
-    command_interpreter::{eval, types::Expr},
-    errors::errors::JreplErr,
+    command_interpreter::types::Expr,
+    errors::errors::{ERROR_INFO, JreplErr, explain},
 };
 
-use crate::command_interpreter::eval::value_of;
+use crate::command_interpreter::eval::{apply, value_of, values_of};
 use crate::command_interpreter::types::Referent;
+use crate::io::write::{SaveOptions, serialize, write_atomic};
+use crate::json::convert::parse_json;
+use crate::json::diff::{Change, diff, render_unified};
+use crate::json::edit::{assoc_in, dissoc_in, update_in};
+use crate::json::infer::infer_schema;
+use crate::json::jsonpath::{JsonPath, parse_jsonpath, to_normalized_path};
+use crate::json::patch::{apply_patch, merge_patch};
+use crate::json::path::{get_in, path_from_args, path_from_value};
+use crate::json::pretty::{PrettyOptions, render};
+use crate::json::schema::{Violation, validate, validate_with_file};
+use crate::statics::collection_commands::get_collection_commands;
+use crate::statics::logic_commands::get_logic_commands;
 
 pub fn get_commands() -> Vec<Command> {
     vec![
@@ -83,6 +94,7 @@ pub fn get_commands() -> Vec<Command> {
                     next_state: Some(next_state),
                     user_feedback: None,
                     err: None,
+                    err_span: None,
                 })
             }),
         },
@@ -92,7 +104,7 @@ pub fn get_commands() -> Vec<Command> {
             eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
                 let sum = exprs
                     .iter()
-                    .try_fold(0.0, |acc, e| Ok::<_, JreplErr>(acc + number_of(app_state, e)?))?;
+                    .try_fold(Number::Int(0), |acc, e| Ok::<_, JreplErr>(acc.add(&number_of(app_state, e)?)))?;
 
                 Ok(Effect::from_eval_value(Expr::Number(sum)))
             }),
@@ -102,18 +114,18 @@ pub fn get_commands() -> Vec<Command> {
             description: "- \t Usage: (- Number Number ...)".to_string(),
             eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
                 if exprs.is_empty() {
-                    return Err(JreplErr::UndefinedSymbol(
+                    return Err(JreplErr::OperatorFormatErr(
                         "'-' expects at least one argument".to_string(),
                     ));
                 }
 
                 let first = number_of(app_state, &exprs[0])?;
                 let result = if exprs.len() == 1 {
-                    -first
+                    first.neg()
                 } else {
                     exprs[1..]
                         .iter()
-                        .try_fold(first, |acc, e| Ok::<f64, JreplErr>(acc - number_of(app_state, e)?))?
+                        .try_fold(first, |acc, e| Ok::<_, JreplErr>(acc.sub(&number_of(app_state, e)?)))?
                 };
 
                 Ok(Effect::from_eval_value(Expr::Number(result)))
@@ -125,7 +137,7 @@ pub fn get_commands() -> Vec<Command> {
             eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
                 let product = exprs
                     .iter()
-                    .try_fold(1.0, |acc, e| Ok::<f64, JreplErr>(acc * number_of(app_state, e)?))?;
+                    .try_fold(Number::Int(1), |acc, e| Ok::<_, JreplErr>(acc.mul(&number_of(app_state, e)?)))?;
                 Ok(Effect::from_eval_value(Expr::Number(product)))
             }),
         },
@@ -141,18 +153,11 @@ pub fn get_commands() -> Vec<Command> {
 
                 let first = number_of(app_state, &exprs[0])?;
                 let result = if exprs.len() == 1 {
-                    if first == 0.0 {
-                        return Err(JreplErr::ArithmeticErr("Division by zero".to_string()));
-                    }
-                    1.0 / first
+                    Number::Int(1).div(&first)?
                 } else {
-                    exprs[1..].iter().try_fold(first, |acc, e| {
-                        let n = number_of(app_state, e)?;
-                        if n == 0.0 {
-                            return Err(JreplErr::ArithmeticErr("Division by zero".to_string()));
-                        }
-                        Ok::<f64, JreplErr>(acc / n)
-                    })?
+                    exprs[1..]
+                        .iter()
+                        .try_fold(first, |acc, e| acc.div(&number_of(app_state, e)?))?
                 };
 
                 Ok(Effect::from_eval_value(Expr::Number(result)))
@@ -169,18 +174,107 @@ pub fn get_commands() -> Vec<Command> {
         //     description: "".into(),
         //     // param_format: [description="list the avaliable 'things (symbols, commands, files, etc)'" type="String" size="1..n"]
         // },
-        // Command {
-        //     symbol: "undo".into(),
-        //     description: "".into(),
-        //     // param_format: ""
-        // },
+        Command {
+            symbol: "explain".to_string(),
+            description: "explain \t Describe an error code, or list them all. Usage: (explain E0204) or (explain)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let code = match exprs {
+                    [] => {
+                        let list = ERROR_INFO
+                            .iter()
+                            .map(|info| format!("{}  {:<7} {}\n", info.code, info.category, info.title))
+                            .collect::<String>();
+                        return Ok(Effect::from_eval_value(Expr::String(list)));
+                    }
+                    // codes are not bound to anything, so a bare E0204 is taken as written
+                    [Expr::Symbol(code)] => code.clone(),
+                    [other] => match value_of(app_state, other)? {
+                        Expr::String(code) => code,
+                        other => {
+                            return Err(JreplErr::OperatorFormatErr(format!(
+                                "'explain' expects an error code like E0204, got {:?}",
+                                other
+                            )));
+                        }
+                    },
+                    _ => {
+                        return Err(JreplErr::OperatorFormatErr(
+                            "'explain' expects at most 1 argument: <code>".to_string(),
+                        ));
+                    }
+                };
+
+                let info = explain(&code).ok_or_else(|| {
+                    JreplErr::EvalErr(format!("Unknown error code '{}'. Run (explain) to list them.", code))
+                })?;
+                let mut text = format!("{} ({}): {}\n\n{}\n", info.code, info.category, info.title, info.explanation);
+                if let Some(hint) = info.hint {
+                    text.push_str(&format!("\nHint: {}\n", hint));
+                }
+                Ok(Effect::from_eval_value(Expr::String(text)))
+            }),
+        },
+        Command {
+            symbol: "undo".to_string(),
+            description: "undo \t Step back through state changes. Usage: (undo) or (undo <steps>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let state = app_state.undo(steps_arg("undo", app_state, exprs)?)?;
+                Ok(Effect {
+                    eval_value: None,
+                    next_state: Some(state),
+                    user_feedback: Some("Undone.".to_string()),
+                    err: None,
+                    err_span: None,
+                })
+            }),
+        },
+        Command {
+            symbol: "redo".to_string(),
+            description: "redo \t Reapply undone state changes. Usage: (redo) or (redo <steps>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let state = app_state.redo(steps_arg("redo", app_state, exprs)?)?;
+                Ok(Effect {
+                    eval_value: None,
+                    next_state: Some(state),
+                    user_feedback: Some("Redone.".to_string()),
+                    err: None,
+                    err_span: None,
+                })
+            }),
+        },
+        Command {
+            symbol: "history".to_string(),
+            description: "history \t List the inputs that changed state, with what can be redone. Usage: (history)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, _exprs: &[Expr]| {
+                let history = app_state.history();
+                let past: Vec<&str> = history.past().collect();
+                let future: Vec<&str> = history.future().collect();
+                if past.is_empty() && future.is_empty() {
+                    return Ok(Effect::from_eval_value(Expr::String("No history yet.\n".to_string())));
+                }
+
+                let mut out = String::new();
+                for (i, input) in past.iter().enumerate() {
+                    out.push_str(&format!("{:>4}  {}\n", i + 1, input));
+                }
+                out.push_str("   *  (current)\n");
+                for (i, input) in future.iter().enumerate() {
+                    out.push_str(&format!("{:>4}  {}  [undone]\n", past.len() + i + 1, input));
+                }
+                Ok(Effect::from_eval_value(Expr::String(out)))
+            }),
+        },
         Command {
             symbol: "defn".to_string(),
-            description: "defn \t Define or redefine a symbol. Usage: (defn <name> <expr>)".to_string(),
+            description:
+                "defn \t Define or redefine a symbol or function. Usage: (defn <name> <expr>) or (defn <name> (<params>...) <body>...)"
+                    .to_string(),
             eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
-                if exprs.len() != 2 {
+                if exprs.len() < 2 {
                     return Err(JreplErr::OperatorFormatErr(
-                        "'defn' expects exactly 2 arguments: <name> <expr>".to_string(),
+                        "'defn' expects <name> <expr> or <name> (<params>...) <body>...".to_string(),
                     ));
                 }
 
@@ -194,19 +288,25 @@ pub fn get_commands() -> Vec<Command> {
                     }
                 };
 
-                // Evaluate the value expression
-                let value = value_of(app_state, &exprs[1])?;
+                // (defn name (params) body...) defines a function, otherwise evaluate the value expression
+                let value = if exprs.len() > 2 {
+                    Expr::Lambda(Rc::new(make_lambda(app_state, Some(name.clone()), &exprs[1], &exprs[2..])?))
+                } else {
+                    value_of(app_state, &exprs[1])?
+                };
 
                 // Only allow terminals to be bound, so later resolution works predictably.
                 if !value.is_literal() {
-                    return Err(JreplErr::UndefinedSymbol(format!(
-                        "'defn' value must evaluate to a terminal (String|Number|Bool|None), got {:?}",
+                    return Err(JreplErr::TypeErr(format!(
+                        "'defn' value must evaluate to a terminal (String|Number|Bool|None|Array|Object|Function), got {:?}",
                         value
                     )));
                 }
 
+                // the new value did not come from the old one's file
                 let next_state = app_state.apply_action(|state| {
                     state.symbol_table.insert(name.clone(), Referent::Expr(value.clone()));
+                    state.provenance.remove(&name);
                 });
 
                 Ok(Effect {
@@ -214,12 +314,15 @@ pub fn get_commands() -> Vec<Command> {
                     next_state: Some(next_state),
                     user_feedback: None,
                     err: None,
+                    err_span: None,
                 })
             }),
         },
         Command {
             symbol: "ld".to_string(),
-            description: r#"ld 	 Load a file. Usage: (ld "<path>") or (ld pathSymbol)"#.to_string(),
+            description:
+                r#"ld 	 Load a file, .json files are parsed into values. Usage: (ld "<path>") or (ld pathSymbol)"#
+                    .to_string(),
             eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
                 if exprs.len() != 1 {
                     return Err(JreplErr::OperatorFormatErr(
@@ -263,27 +366,474 @@ pub fn get_commands() -> Vec<Command> {
                     }
                 };
 
-                let file = FileValue { path, bytes, mime };
+                // JSON files are loaded as structured values, anything else stays raw bytes
+                if mime.as_deref() == Some("application/json") {
+                    let value = parse_json(&bytes).map_err(|e| match e {
+                        JreplErr::JsonErr(msg) => JreplErr::JsonErr(format!("ld: '{}': {}", path, msg)),
+                        other => other,
+                    })?;
+                    return Ok(Effect::from_eval_value(value));
+                }
+
+                let file = FileValue {
+                    path,
+                    bytes: Rc::from(bytes),
+                    mime,
+                };
                 Ok(Effect::from_eval_value(Expr::File(file)))
             }),
         },
-        // Command {
-        //     symbol: "def".into(),
-        //     description: "".into(),
-        //     // param_format: [description="Symbol name" type="String" size="1"]
-        //     //               [description="value to be bound to the symbol" type="Expr" size="1"]
-        // },
-        // Command {
-        //     symbol: "do".into(),
-        //     description: "".into(),
-        //     // param_format: [description="Exprs to evaluate" type="Expr" size="1..n"]
-        // },
-        // Command {
-        //     symbol: "set".into(),
-        //     description: "".into(),
-        //     // param_format: [description="existing symbol name" type="Expr" size="1"]
-        //     // param_format: [description="expression to eval and bind to the symbol" type="Expr" size="1"]
-        // },
+        // ---- JSON ------------------
+        Command {
+            symbol: "get".to_string(),
+            description: r#"get 	 Look up a value by path. Usage: (get <json> "/json/pointer") or (get <json> "a.b[0]") or (get <json> "a" "b" 0)"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.is_empty() {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'get' expects a value followed by a path: (get <json> <path> ...)".to_string(),
+                    ));
+                }
+
+                let value = value_of(app_state, &exprs[0])?;
+                let path_args = exprs[1..]
+                    .iter()
+                    .map(|e| value_of(app_state, e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let path = path_from_args(&path_args)?;
+
+                let found = get_in(&value, &path)?;
+                Ok(Effect::from_eval_value(found.clone()))
+            }),
+        },
+        Command {
+            symbol: "query".to_string(),
+            description: r#"query 	 Select every value matching a JSONPath. Usage: (query <json> "$..name")"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let (value, jsonpath) = query_args("query", app_state, exprs)?;
+                let matches = jsonpath
+                    .select(&value)?
+                    .into_iter()
+                    .map(|(_, found)| found.clone())
+                    .collect();
+                Ok(Effect::from_eval_value(Expr::List(matches)))
+            }),
+        },
+        Command {
+            symbol: "query-paths".to_string(),
+            description: r#"query-paths 	 Like query, but pairs each match with its normalized path. Usage: (query-paths <json> "$..name")"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let (value, jsonpath) = query_args("query-paths", app_state, exprs)?;
+                let matches = jsonpath
+                    .select(&value)?
+                    .into_iter()
+                    .map(|(path, found)| {
+                        let mut pair = JsonObject::new();
+                        pair.insert("path".to_string(), Expr::String(to_normalized_path(&path)));
+                        pair.insert("value".to_string(), found.clone());
+                        Expr::object(pair)
+                    })
+                    .collect();
+                Ok(Effect::from_eval_value(Expr::List(matches)))
+            }),
+        },
+        Command {
+            symbol: "assoc-in".to_string(),
+            description: r#"assoc-in 	 Return a copy with the value at path set. Usage: (assoc-in <json> "/a/b" <value>)"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() != 3 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'assoc-in' expects exactly 3 arguments: <json> <path> <value>".to_string(),
+                    ));
+                }
+
+                let value = value_of(app_state, &exprs[0])?;
+                let path = path_from_value(&value_of(app_state, &exprs[1])?)?;
+                let new_value = value_of(app_state, &exprs[2])?;
+
+                Ok(Effect::from_eval_value(assoc_in(&value, &path, new_value)?))
+            }),
+        },
+        Command {
+            symbol: "update-in".to_string(),
+            description: r#"update-in 	 Return a copy with the value at path replaced by (f old args...). Usage: (update-in <json> "/a/b" <f> <args>...)"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() < 3 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'update-in' expects at least 3 arguments: <json> <path> <f> <args>...".to_string(),
+                    ));
+                }
+
+                let value = value_of(app_state, &exprs[0])?;
+                let path = path_from_value(&value_of(app_state, &exprs[1])?)?;
+                let extra_args = exprs[3..]
+                    .iter()
+                    .map(|e| value_of(app_state, e))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let updated = update_in(&value, &path, |old| {
+                    let mut args = vec![old.clone()];
+                    args.extend(extra_args);
+                    apply(app_state, &exprs[2], &args)
+                })?;
+                Ok(Effect::from_eval_value(updated))
+            }),
+        },
+        Command {
+            symbol: "dissoc-in".to_string(),
+            description: r#"dissoc-in 	 Return a copy with the key or element at path removed. Usage: (dissoc-in <json> "/a/b")"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() != 2 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'dissoc-in' expects exactly 2 arguments: <json> <path>".to_string(),
+                    ));
+                }
+
+                let value = value_of(app_state, &exprs[0])?;
+                let path = path_from_value(&value_of(app_state, &exprs[1])?)?;
+
+                Ok(Effect::from_eval_value(dissoc_in(&value, &path)?))
+            }),
+        },
+        Command {
+            symbol: "diff".to_string(),
+            description: r#"diff 	 Compare two values: the added, removed and changed paths, as records, an RFC 6902 patch (:patch) or text (:text, :color). :by matches array elements by a key. Usage: (diff <old> <new> :by "id" :patch)"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let keywords = Keywords::parse("diff", exprs, &[":patch", ":text", ":color"], &[":by"])?;
+                let [old, new] = keywords.args[..] else {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'diff' expects 2 values and options: (diff <old> <new> :by \"id\" :patch)".to_string(),
+                    ));
+                };
+                let array_key = match keywords.value(":by").map(|key| value_of(app_state, key)).transpose()? {
+                    None => None,
+                    Some(Expr::String(key)) => Some(key),
+                    Some(other) => {
+                        return Err(JreplErr::OperatorFormatErr(format!(
+                            "'diff' option :by expects a key String, got {:?}",
+                            other
+                        )));
+                    }
+                };
+
+                let changes = diff(&value_of(app_state, old)?, &value_of(app_state, new)?, array_key.as_deref());
+                let result = if keywords.has(":text") {
+                    Expr::String(render_unified(&changes, keywords.has(":color")))
+                } else if keywords.has(":patch") {
+                    Expr::array(changes.iter().map(Change::to_patch_op).collect())
+                } else {
+                    Expr::array(changes.iter().map(Change::to_expr).collect())
+                };
+                Ok(Effect::from_eval_value(result))
+            }),
+        },
+        Command {
+            symbol: "patch".to_string(),
+            description: r#"patch 	 Apply an RFC 6902 JSON Patch, an array of add, remove, replace, move, copy and test operations, returning the new value. Usage: (patch <json> [{"op" "add" "path" "/a" "value" 1}])"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() != 2 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'patch' expects exactly 2 arguments: <json> <operations>".to_string(),
+                    ));
+                }
+
+                let value = value_of(app_state, &exprs[0])?;
+                match value_of(app_state, &exprs[1])? {
+                    Expr::Array(ops) => Ok(Effect::from_eval_value(apply_patch(&value, &ops)?)),
+                    other => Err(JreplErr::OperatorFormatErr(format!(
+                        "'patch' operations must be an array, got {:?}",
+                        other
+                    ))),
+                }
+            }),
+        },
+        Command {
+            symbol: "merge-patch".to_string(),
+            description: r#"merge-patch 	 Apply an RFC 7396 JSON Merge Patch: objects merge, null removes a key, anything else replaces. Usage: (merge-patch <json> {"a" 1 "b" null})"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() != 2 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'merge-patch' expects exactly 2 arguments: <json> <patch>".to_string(),
+                    ));
+                }
+
+                let target = value_of(app_state, &exprs[0])?;
+                let patch = value_of(app_state, &exprs[1])?;
+                Ok(Effect::from_eval_value(merge_patch(&target, &patch)))
+            }),
+        },
+        Command {
+            symbol: "validate".to_string(),
+            description: r#"validate 	 Check a value against a JSON Schema (draft 2020-12 or 07), returning the violations. Usage: (validate <json> "schema.json") or (validate <json> schemaSymbol)"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() != 2 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'validate' expects exactly 2 arguments: <json> <schema>".to_string(),
+                    ));
+                }
+
+                let value = value_of(app_state, &exprs[0])?;
+                let violations = match value_of(app_state, &exprs[1])? {
+                    Expr::String(path) => validate_with_file(&value, &path)?,
+                    schema @ (Expr::Object(_) | Expr::Bool(_)) => {
+                        // a schema bound with --json or saved resolves its refs next to its file
+                        let source = match &exprs[1] {
+                            Expr::Symbol(name) => app_state.provenance(name).map(Path::new),
+                            _ => None,
+                        };
+                        validate(&value, &schema, source)?
+                    }
+                    other => {
+                        return Err(JreplErr::OperatorFormatErr(format!(
+                            "'validate' schema must be a path or a schema object, got {:?}",
+                            other
+                        )));
+                    }
+                };
+                let violations = violations.iter().map(Violation::to_expr).collect();
+                Ok(Effect::from_eval_value(Expr::Array(Rc::new(violations))))
+            }),
+        },
+        Command {
+            symbol: "infer-schema".to_string(),
+            description: r#"infer-schema 	 Infer a JSON Schema describing the samples: the elements of one array, or each argument. Usage: (infer-schema <array>) or (infer-schema <json> <json>...)"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.is_empty() {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'infer-schema' expects at least 1 argument: (infer-schema <json>...)".to_string(),
+                    ));
+                }
+
+                let values = values_of(app_state, exprs)?;
+                let schema = match values.as_slice() {
+                    [Expr::Array(samples)] => infer_schema(samples),
+                    [Expr::List(samples)] => infer_schema(samples),
+                    samples => infer_schema(samples),
+                };
+                Ok(Effect::from_eval_value(schema))
+            }),
+        },
+        Command {
+            symbol: "pp".to_string(),
+            description: r#"pp 	 Render a value as indented JSON text. Options: indent, sort-keys, compact, color, depth, width. Usage: (pp <value>) or (pp <value> {"indent" 4 "depth" 2})"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let options = match exprs {
+                    [_] => PrettyOptions::default(),
+                    [_, options] => match value_of(app_state, options)? {
+                        Expr::Object(options) => PrettyOptions::from_object("pp", &options)?,
+                        other => {
+                            return Err(JreplErr::OperatorFormatErr(format!(
+                                "'pp' options must be an object, got {:?}",
+                                other
+                            )));
+                        }
+                    },
+                    _ => {
+                        return Err(JreplErr::OperatorFormatErr(
+                            "'pp' expects a value and optional options: (pp <value> {...})".to_string(),
+                        ));
+                    }
+                };
+
+                let value = value_of(app_state, &exprs[0])?;
+                Ok(Effect::from_eval_value(Expr::String(render(&value, &options) + "\n")))
+            }),
+        },
+        Command {
+            symbol: "save".to_string(),
+            description: r#"save 	 Write a value to a file as JSON, files are written as loaded. Options: :force :compact :sort-keys :no-newline. Usage: (save <value> "<path>" :force) or (save symbol :force) to write it back where it was loaded or last saved"#.to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let keywords = Keywords::parse("save", exprs, &[":force", ":compact", ":sort-keys", ":no-newline"], &[])?;
+                let options = SaveOptions {
+                    compact: keywords.has(":compact"),
+                    sort_keys: keywords.has(":sort-keys"),
+                    trailing_newline: !keywords.has(":no-newline"),
+                    force: keywords.has(":force"),
+                };
+                let args = keywords.args;
+                if args.is_empty() || args.len() > 2 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'save' expects a value, an optional path and options: (save <value> \"<path>\" :force)".to_string(),
+                    ));
+                }
+
+                // a global binding remembers where it was saved, a local one shadowing it does not
+                let symbol = match args[0] {
+                    Expr::Symbol(name)
+                        if !app_state.is_local(name)
+                            && matches!(app_state.global_referent(name), Some(Referent::Expr(_))) =>
+                    {
+                        Some(name.clone())
+                    }
+                    _ => None,
+                };
+                let value = value_of(app_state, args[0])?;
+
+                let (path, force) = match args.get(1) {
+                    Some(path) => match value_of(app_state, path)? {
+                        Expr::String(path) => (path, options.force),
+                        other => {
+                            return Err(JreplErr::OperatorFormatErr(format!(
+                                "'save' path must evaluate to a String, got {:?}",
+                                other
+                            )));
+                        }
+                    },
+                    // the file is still only replaced with :force, it may be a source the user meant to keep
+                    None => {
+                        let known = symbol.as_deref().and_then(|name| app_state.provenance(name));
+                        match (known, &value) {
+                            (Some(path), _) => (path.to_string(), options.force),
+                            (None, Expr::File(file)) => (file.path.clone(), options.force),
+                            _ => {
+                                return Err(JreplErr::OperatorFormatErr(
+                                    "'save' needs a path: the value was not loaded from or saved to a file".to_string(),
+                                ));
+                            }
+                        }
+                    }
+                };
+
+                let bytes = serialize(&value, &options)?;
+                write_atomic(&path, &bytes, force)?;
+
+                let next_state = symbol.map(|name| {
+                    app_state.apply_action(|state| {
+                        state.provenance.insert(name, path.clone());
+                    })
+                });
+                Ok(Effect {
+                    eval_value: Some(Expr::String(path.clone())),
+                    next_state,
+                    user_feedback: Some(format!("Saved {} bytes to '{}'", bytes.len(), path)),
+                    err: None,
+                    err_span: None,
+                })
+            }),
+        },
+        Command {
+            symbol: "do".to_string(),
+            description: "do \t Evaluate forms in order and return the last value. Usage: (do <expr>...)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| eval_sequence(app_state, exprs)),
+        },
+        Command {
+            symbol: "let".to_string(),
+            description:
+                "let \t Bind local names for the body only. Usage: (let ((<name> <expr>)...) <body>...)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let bindings = match exprs.first() {
+                    Some(Expr::List(bindings)) => bindings,
+                    _ => {
+                        return Err(JreplErr::OperatorFormatErr(
+                            "'let' expects a binding list and a body: (let ((<name> <expr>)...) <body>...)"
+                                .to_string(),
+                        ));
+                    }
+                };
+
+                // each binding gets its own frame, so later ones see earlier ones
+                let mut scoped = app_state.clone();
+                let mut changed = false;
+                for binding in bindings {
+                    let (name, expr) = match binding {
+                        Expr::List(pair) => match pair.as_slice() {
+                            [Expr::Symbol(name), expr] => (name.clone(), expr),
+                            _ => {
+                                return Err(JreplErr::OperatorFormatErr(format!(
+                                    "'let' bindings must be (<name> <expr>) pairs, got {:?}",
+                                    binding
+                                )));
+                            }
+                        },
+                        other => {
+                            return Err(JreplErr::OperatorFormatErr(format!(
+                                "'let' bindings must be (<name> <expr>) pairs, got {:?}",
+                                other
+                            )));
+                        }
+                    };
+
+                    let effect = eval(&scoped, expr)?;
+                    let value = effect.eval_value.ok_or_else(|| {
+                        JreplErr::OperatorFormatErr(format!("The value of '{}' returned no value", name))
+                    })?;
+                    if let Some(state) = effect.next_state {
+                        scoped = scoped.with_state(state);
+                        changed = true;
+                    }
+                    let frame = Scope {
+                        bindings: HashMap::from([(name, value)]),
+                        parent: scoped.scope(),
+                    };
+                    scoped = scoped.with_scope(Rc::new(frame));
+                }
+
+                let mut effect = eval_sequence(&scoped, &exprs[1..])?;
+                if effect.next_state.is_none() && changed {
+                    effect.next_state = Some(scoped.into_state());
+                }
+                Ok(effect)
+            }),
+        },
+        Command {
+            symbol: "set!".to_string(),
+            description: "set! \t Rebind an existing symbol to a new value. Usage: (set! <name> <expr>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let (name, expr) = match exprs {
+                    [Expr::Symbol(name), expr] => (name, expr),
+                    _ => {
+                        return Err(JreplErr::OperatorFormatErr(
+                            "'set!' expects exactly 2 arguments: <name> <expr>".to_string(),
+                        ));
+                    }
+                };
+
+                if app_state.scope().is_some_and(|scope| scope.lookup(name).is_some()) {
+                    return Err(JreplErr::EvalErr(format!(
+                        "'{}' is a local binding, which cannot be changed. Use a new let instead.",
+                        name
+                    )));
+                }
+                match app_state.global_referent(name) {
+                    Some(Referent::Expr(_)) => {}
+                    Some(Referent::Command(_)) => {
+                        return Err(JreplErr::InvalidSymbol(format!(
+                            "'{}' is a command and cannot be rebound.",
+                            name
+                        )));
+                    }
+                    None => {
+                        return Err(JreplErr::UndefinedSymbol {
+                            name: Some(name.clone()),
+                            msg: format!(
+                                "Cannot set! '{}': it is not defined. Define it first with (defn {} <expr>).",
+                                name, name
+                            ),
+                        });
+                    }
+                }
+
+                let effect = eval(app_state, expr)?;
+                let value = effect
+                    .eval_value
+                    .ok_or_else(|| JreplErr::OperatorFormatErr("Subexpression returned no value".to_string()))?;
+                let current = match effect.next_state {
+                    Some(state) => app_state.with_state(state),
+                    None => app_state.clone(),
+                };
+                let next_state = current.apply_action(|state| {
+                    state.symbol_table.insert(name.clone(), Referent::Expr(value.clone()));
+                    state.provenance.remove(name);
+                });
+
+                Ok(Effect {
+                    eval_value: Some(value),
+                    next_state: Some(next_state),
+                    user_feedback: None,
+                    err: None,
+                    err_span: None,
+                })
+            }),
+        },
         // Command {
         //     symbol: "print".into(),
         //     description: "".into(),
@@ -296,6 +846,9 @@ pub fn get_commands() -> Vec<Command> {
         //     // param_format: [description="" type="Expr" size="1..n"]
         // },
 
+        // ---- Functions ------------------
+        lambda_command("fn"),
+        lambda_command("lambda"),
         // ---- Control Flow ------------------
         Command {
             symbol: "if".to_string(),
@@ -304,7 +857,7 @@ pub fn get_commands() -> Vec<Command> {
                 use crate::command_interpreter::eval::{eval, value_of};
 
                 if exprs.len() < 2 {
-                    return Err(JreplErr::UndefinedSymbol(
+                    return Err(JreplErr::OperatorFormatErr(
                         "'if' expects at least 2 arguments: condition and then-branch".to_string(),
                     ));
                 }
@@ -313,7 +866,7 @@ pub fn get_commands() -> Vec<Command> {
                 let cond = match cond_ev {
                     Expr::Bool(b) => b,
                     other => {
-                        return Err(JreplErr::UndefinedSymbol(format!(
+                        return Err(JreplErr::TypeErr(format!(
                             "Type error in 'if': expected Bool for condition, got {:?}",
                             other
                         )));
@@ -332,4 +885,167 @@ pub fn get_commands() -> Vec<Command> {
             }),
         },
     ]
+    .into_iter()
+    .chain(get_collection_commands())
+    .chain(get_logic_commands())
+    .collect()
+}
+
+fn query_args(
+    symbol: &str,
+    app_state: &AppState,
+    exprs: &[Expr],
+) -> Result<(Expr, JsonPath), JreplErr> {
+    if exprs.len() != 2 {
+        return Err(JreplErr::OperatorFormatErr(format!(
+            "'{}' expects exactly 2 arguments: <json> <jsonpath>",
+            symbol
+        )));
+    }
+
+    let value = value_of(app_state, &exprs[0])?;
+    let jsonpath = match value_of(app_state, &exprs[1])? {
+        Expr::String(s) => parse_jsonpath(&s)?,
+        other => {
+            return Err(JreplErr::OperatorFormatErr(format!(
+                "'{}' path must evaluate to a String, got {:?}",
+                symbol, other
+            )));
+        }
+    };
+    Ok((value, jsonpath))
+}
+
+/// A command's :keyword options, split from its other arguments. Options may come anywhere, and
+/// the ones in `valued` take the argument that follows them, e.g. :by "id".
+struct Keywords<'a> {
+    args: Vec<&'a Expr>,
+    flags: Vec<&'a str>,
+    values: HashMap<&'a str, &'a Expr>,
+}
+
+impl<'a> Keywords<'a> {
+    fn parse(
+        symbol: &str,
+        exprs: &'a [Expr],
+        flags: &[&str],
+        valued: &[&str],
+    ) -> Result<Keywords<'a>, JreplErr> {
+        let mut keywords = Keywords {
+            args: Vec::new(),
+            flags: Vec::new(),
+            values: HashMap::new(),
+        };
+        let mut exprs = exprs.iter();
+        while let Some(expr) = exprs.next() {
+            match expr {
+                Expr::Symbol(keyword) if flags.contains(&keyword.as_str()) => {
+                    keywords.flags.push(keyword)
+                }
+                Expr::Symbol(keyword) if valued.contains(&keyword.as_str()) => {
+                    let value = exprs.next().ok_or_else(|| {
+                        JreplErr::OperatorFormatErr(format!(
+                            "'{}' option {} expects a value after it",
+                            symbol, keyword
+                        ))
+                    })?;
+                    keywords.values.insert(keyword, value);
+                }
+                Expr::Symbol(keyword) if keyword.starts_with(':') => {
+                    return Err(JreplErr::OperatorFormatErr(format!(
+                        "'{}' has no option {}, expected one of {}",
+                        symbol,
+                        keyword,
+                        flags
+                            .iter()
+                            .chain(valued)
+                            .copied()
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )));
+                }
+                _ => keywords.args.push(expr),
+            }
+        }
+        Ok(keywords)
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    fn value(&self, keyword: &str) -> Option<&'a Expr> {
+        self.values.get(keyword).copied()
+    }
+}
+
+// Optional step count for undo and redo, 1 by default.
+fn steps_arg(symbol: &str, app_state: &AppState, exprs: &[Expr]) -> Result<usize, JreplErr> {
+    match exprs {
+        [] => Ok(1),
+        [steps] => match number_of(app_state, steps)? {
+            n if n.to_usize().is_some_and(|steps| steps >= 1) => Ok(n.to_usize().unwrap()),
+            n => Err(JreplErr::OperatorFormatErr(format!(
+                "'{}' expects a positive whole number of steps, got {}",
+                symbol, n
+            ))),
+        },
+        _ => Err(JreplErr::OperatorFormatErr(format!(
+            "'{}' expects at most 1 argument: <steps>",
+            symbol
+        ))),
+    }
+}
+
+fn lambda_command(symbol: &str) -> Command {
+    Command {
+        symbol: symbol.to_string(),
+        description: format!(
+            "{} \t Create a function that closes over the local bindings. Usage: ({} (<params>...) <body>...)",
+            symbol, symbol
+        ),
+        eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+            if exprs.len() < 2 {
+                return Err(JreplErr::OperatorFormatErr(
+                    "A function needs a parameter list and a body: (fn (<params>...) <body>...)"
+                        .to_string(),
+                ));
+            }
+            let lambda = make_lambda(app_state, None, &exprs[0], &exprs[1..])?;
+            Ok(Effect::from_eval_value(Expr::Lambda(Rc::new(lambda))))
+        }),
+    }
+}
+
+fn make_lambda(
+    app_state: &AppState,
+    name: Option<String>,
+    params: &Expr,
+    body: &[Expr],
+) -> Result<Lambda, JreplErr> {
+    let params = match params {
+        Expr::List(params) => params
+            .iter()
+            .map(|p| match p {
+                Expr::Symbol(s) => Ok(s.clone()),
+                other => Err(JreplErr::OperatorFormatErr(format!(
+                    "Function parameters must be symbols, got {:?}",
+                    other
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        other => {
+            return Err(JreplErr::OperatorFormatErr(format!(
+                "Expected a parameter list like (x y), got {:?}",
+                other
+            )));
+        }
+    };
+
+    Ok(Lambda {
+        name,
+        params,
+        body: body.to_vec(),
+        scope: app_state.scope(),
+    })
 }
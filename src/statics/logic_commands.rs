@@ -0,0 +1,241 @@
+/*
+    Comparison and boolean commands.
+
+    = and != compare any values structurally: arrays and lists element by element, objects by
+    their entries whatever the key order. The orderings compare numbers with numbers and strings
+    with strings, and chain like (< 1 2 3). and, or and not take Bools only, and and/or stop
+    evaluating at the first argument that decides the result, the way if evaluates one branch.
+*/
+
+use std::cmp::Ordering;
+
+use crate::{
+    appstate::AppState,
+    command_interpreter::{
+        command::Command,
+        eval::{value_of, values_of},
+        types::{Effect, Expr},
+    },
+    errors::errors::JreplErr,
+};
+
+pub fn get_logic_commands() -> Vec<Command> {
+    vec![
+        Command {
+            symbol: "=".to_string(),
+            description: "= \t True if all values are deeply equal. Usage: (= <a> <b>...)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                let values = operands("=", app_state, exprs)?;
+                let equal = values.windows(2).all(|pair| values_equal(&pair[0], &pair[1]));
+                Ok(Effect::from_eval_value(Expr::Bool(equal)))
+            }),
+        },
+        Command {
+            symbol: "!=".to_string(),
+            description: "!= \t True if the two values are not deeply equal. Usage: (!= <a> <b>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() != 2 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'!=' expects exactly 2 arguments".to_string(),
+                    ));
+                }
+                let (a, b) = (value_of(app_state, &exprs[0])?, value_of(app_state, &exprs[1])?);
+                Ok(Effect::from_eval_value(Expr::Bool(!values_equal(&a, &b))))
+            }),
+        },
+        ordering_command("<", "increasing", |o| o.is_lt()),
+        ordering_command("<=", "non-decreasing", |o| o.is_le()),
+        ordering_command(">", "decreasing", |o| o.is_gt()),
+        ordering_command(">=", "non-increasing", |o| o.is_ge()),
+        Command {
+            symbol: "and".to_string(),
+            description: "and \t True if every argument is true, stopping at the first false. Usage: (and <bool>...)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                for expr in exprs {
+                    if !bool_of("and", app_state, expr)? {
+                        return Ok(Effect::from_eval_value(Expr::Bool(false)));
+                    }
+                }
+                Ok(Effect::from_eval_value(Expr::Bool(true)))
+            }),
+        },
+        Command {
+            symbol: "or".to_string(),
+            description: "or \t True if any argument is true, stopping at the first true. Usage: (or <bool>...)"
+                .to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                for expr in exprs {
+                    if bool_of("or", app_state, expr)? {
+                        return Ok(Effect::from_eval_value(Expr::Bool(true)));
+                    }
+                }
+                Ok(Effect::from_eval_value(Expr::Bool(false)))
+            }),
+        },
+        Command {
+            symbol: "not".to_string(),
+            description: "not \t Negate a Bool. Usage: (not <bool>)".to_string(),
+            eval_fn_ptr: Box::new(|app_state: &AppState, exprs: &[Expr]| {
+                if exprs.len() != 1 {
+                    return Err(JreplErr::OperatorFormatErr(
+                        "'not' expects exactly 1 argument".to_string(),
+                    ));
+                }
+                Ok(Effect::from_eval_value(Expr::Bool(!bool_of("not", app_state, &exprs[0])?)))
+            }),
+        },
+    ]
+}
+
+fn ordering_command(symbol: &'static str, order: &str, holds: fn(Ordering) -> bool) -> Command {
+    Command {
+        symbol: symbol.to_string(),
+        description: format!(
+            "{} \t True if the numbers or strings are in {} order. Usage: ({} <a> <b>...)",
+            symbol, order, symbol
+        ),
+        eval_fn_ptr: Box::new(move |app_state: &AppState, exprs: &[Expr]| {
+            let values = operands(symbol, app_state, exprs)?;
+            let mut in_order = true;
+            for pair in values.windows(2) {
+                // every pair is checked, so a type error is reported even after a false
+                in_order &= holds(order_of(symbol, &pair[0], &pair[1])?);
+            }
+            Ok(Effect::from_eval_value(Expr::Bool(in_order)))
+        }),
+    }
+}
+
+/// Deep equality of two values. Lists and JSON arrays with the same elements are equal, and
+/// objects are equal when they hold the same keys with equal values, in any order.
+pub fn values_equal(a: &Expr, b: &Expr) -> bool {
+    fn items(e: &Expr) -> Option<&[Expr]> {
+        match e {
+            Expr::Array(items) => Some(items),
+            Expr::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    match (a, b) {
+        (Expr::Object(x), Expr::Object(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(key, v)| y.get(key).is_some_and(|w| values_equal(v, w)))
+        }
+        _ => match (items(a), items(b)) {
+            (Some(xs), Some(ys)) => {
+                xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| values_equal(x, y))
+            }
+            _ => a == b,
+        },
+    }
+}
+
+fn order_of(symbol: &str, a: &Expr, b: &Expr) -> Result<Ordering, JreplErr> {
+    match (a, b) {
+        (Expr::Number(x), Expr::Number(y)) => x.partial_cmp(y).ok_or_else(|| {
+            JreplErr::ArithmeticErr(format!("'{}' cannot order {} and {}", symbol, x, y))
+        }),
+        (Expr::String(x), Expr::String(y)) => Ok(x.cmp(y)),
+        _ => Err(JreplErr::TypeErr(format!(
+            "Type error in '{}': can only order two Numbers or two Strings, got {:?} and {:?}",
+            symbol, a, b
+        ))),
+    }
+}
+
+fn operands(symbol: &str, app_state: &AppState, exprs: &[Expr]) -> Result<Vec<Expr>, JreplErr> {
+    if exprs.len() < 2 {
+        return Err(JreplErr::OperatorFormatErr(format!(
+            "'{}' expects at least 2 arguments",
+            symbol
+        )));
+    }
+    values_of(app_state, exprs)
+}
+
+fn bool_of(symbol: &str, app_state: &AppState, expr: &Expr) -> Result<bool, JreplErr> {
+    match value_of(app_state, expr)? {
+        Expr::Bool(b) => Ok(b),
+        other => Err(JreplErr::TypeErr(format!(
+            "Type error in '{}': expected Bool, got {:?}",
+            symbol, other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{value, value_session};
+
+    #[test]
+    fn equality_is_deep() {
+        let users = "(defn users (get (ld \"dev/array_of_objects.json\") \"users\"))";
+        // the same user with "name" moved after "age"
+        let reordered =
+            "(defn bob (assoc-in (dissoc-in (get users 0) \"/name\") \"/name\" \"Bob\"))";
+        assert_eq!(
+            value_session(&[users, reordered, "(= bob (get users 0))"]),
+            Ok(Expr::Bool(true))
+        );
+        assert_eq!(
+            value_session(&[users, "(= users (assoc-in users \"/1/age\" 29))"]),
+            Ok(Expr::Bool(false))
+        );
+        assert_eq!(
+            value_session(&[
+                users,
+                "(!= users (get (ld \"dev/array_of_objects.json\") \"users\"))"
+            ]),
+            Ok(Expr::Bool(false))
+        );
+        assert_eq!(value("(= (1 2) (1 2))"), Ok(Expr::Bool(true)));
+        assert_eq!(value("(= (1 2) (2 1))"), Ok(Expr::Bool(false)));
+        assert_eq!(value("(= 1 1 1)"), Ok(Expr::Bool(true)));
+        assert_eq!(value("(= 1 \"1\")"), Ok(Expr::Bool(false)));
+        assert_eq!(value("(!= true false)"), Ok(Expr::Bool(true)));
+    }
+
+    #[test]
+    fn ordering_chains_over_numbers_and_strings() {
+        assert_eq!(value("(< 1 2 3)"), Ok(Expr::Bool(true)));
+        assert_eq!(value("(< 1 3 2)"), Ok(Expr::Bool(false)));
+        assert_eq!(value("(<= 1 1 2)"), Ok(Expr::Bool(true)));
+        assert_eq!(value("(> 3 2 1)"), Ok(Expr::Bool(true)));
+        assert_eq!(value("(>= 2 2 3)"), Ok(Expr::Bool(false)));
+        assert_eq!(value(r#"(< "apple" "banana")"#), Ok(Expr::Bool(true)));
+        assert!(matches!(value(r#"(< 1 "2")"#), Err(JreplErr::TypeErr(_))));
+        assert!(matches!(
+            value("(< 1)"),
+            Err(JreplErr::OperatorFormatErr(_))
+        ));
+    }
+
+    #[test]
+    fn and_or_short_circuit() {
+        assert_eq!(value("(and true (< 1 2))"), Ok(Expr::Bool(true)));
+        assert_eq!(value("(and false (nope))"), Ok(Expr::Bool(false)));
+        assert_eq!(value("(or true (nope))"), Ok(Expr::Bool(true)));
+        assert_eq!(value("(or false false)"), Ok(Expr::Bool(false)));
+        assert_eq!(value("(and)"), Ok(Expr::Bool(true)));
+        assert_eq!(value("(or)"), Ok(Expr::Bool(false)));
+        assert!(matches!(value("(and true 1)"), Err(JreplErr::TypeErr(_))));
+    }
+
+    #[test]
+    fn not_negates_bools() {
+        assert_eq!(value("(not (= 1 2))"), Ok(Expr::Bool(true)));
+        assert!(matches!(value("(not 1)"), Err(JreplErr::TypeErr(_))));
+    }
+
+    #[test]
+    fn comparisons_drive_if() {
+        assert_eq!(
+            value("(if (>= 3 2) \"yes\" \"no\")"),
+            Ok(Expr::String("yes".to_string()))
+        );
+    }
+}
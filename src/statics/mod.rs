@@ -1 +1,3 @@
+pub mod collection_commands;
 pub mod commands;
+pub mod logic_commands;
@@ -0,0 +1,50 @@
+// Fixtures shared by the unit tests: a fresh session with the built-in commands, fed inputs the
+// way the REPL would.
+
+use crate::{
+    appstate::AppState,
+    command_interpreter::{
+        interpreter::interpret,
+        types::{Effect, Expr},
+    },
+    errors::errors::JreplErr,
+    statics::commands::get_commands,
+};
+
+pub fn session() -> AppState {
+    let mut app_state = AppState::new();
+    app_state.set_commands(get_commands());
+    app_state
+}
+
+/// Runs each input in turn against the state the previous one left behind and returns the effect
+/// of the last one. Every input before the last must succeed.
+pub fn run_session(inputs: &[&str]) -> Effect {
+    let mut app_state = session();
+    let (last, setup) = inputs.split_last().unwrap();
+    for input in setup {
+        let effect = interpret(&app_state, input);
+        assert!(effect.err.is_none(), "{}: {:?}", input, effect.err);
+        if let Some(state) = effect.next_state {
+            app_state.set_next_state(state);
+        }
+    }
+    interpret(&app_state, last)
+}
+
+pub fn run(input: &str) -> Effect {
+    run_session(&[input])
+}
+
+/// Like `run_session`, but returns the value of the last input or its error.
+pub fn value_session(inputs: &[&str]) -> Result<Expr, JreplErr> {
+    let effect = run_session(inputs);
+    match effect.err {
+        Some(err) => Err(err),
+        None => Ok(effect.eval_value.unwrap()),
+    }
+}
+
+pub fn value(input: &str) -> Result<Expr, JreplErr> {
+    value_session(&[input])
+}